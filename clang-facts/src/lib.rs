@@ -0,0 +1,580 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Facts about C++ which bindgen doesn't tell autocxx, so which we find
+//! by asking libclang directly. This is separate from autocxx-engine so
+//! that the engine needn't contain any unsafe code.
+
+mod templated_superclasses;
+
+pub use templated_superclasses::{declare_templated_superclasses, UnredeclarableMethod};
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int, c_uint, c_ulong},
+};
+
+use clang_sys::*;
+use once_cell::unsync::OnceCell;
+
+/// Facts about C++ which bindgen doesn't tell us, so we ask libclang
+/// directly: exception specifications, nullability,
+/// `[[clang::lifetimebound]]`, the constness of template arguments,
+/// which fields are protected and which bases are virtual.
+///
+/// Functions and fields are named in the same way as for directives such
+/// as `throws!`, e.g. `ns::Class::method`. Where a function is overloaded or redeclared,
+/// we only record facts which hold for every declaration.
+///
+/// libclang only parses the header when we first ask for a fact, which
+/// for many headers is never.
+#[derive(Default)]
+pub struct ClangFacts {
+    header: Option<Header>,
+    facts: OnceCell<Facts>,
+}
+
+struct Header {
+    name: String,
+    contents: String,
+    clang_args: Vec<String>,
+}
+
+#[derive(Default)]
+struct Facts {
+    noexcept_functions: HashSet<String>,
+    /// Keyed by function and parameter name; `true` if `_Nullable`,
+    /// `false` if `_Nonnull`.
+    pointer_nullability: HashMap<(String, String), bool>,
+    /// The parameter (or `self`) marked `[[clang::lifetimebound]]`.
+    lifetime_sources: HashMap<String, String>,
+    /// Keyed by function and parameter name.
+    const_first_template_args: HashSet<(String, String)>,
+    /// Whether each protected field is mutable.
+    protected_fields: HashMap<String, bool>,
+    /// The virtual bases of each class, and whether each is public.
+    virtual_bases: HashMap<String, HashMap<String, bool>>,
+}
+
+impl ClangFacts {
+    /// Prepares to ask libclang about a header.
+    pub fn new(header_name: &str, header_contents: &str, clang_args: &[String]) -> Self {
+        Self {
+            header: Some(Header {
+                name: header_name.to_string(),
+                contents: header_contents.to_string(),
+                clang_args: clang_args.to_vec(),
+            }),
+            facts: OnceCell::new(),
+        }
+    }
+
+    fn facts(&self) -> &Facts {
+        self.facts.get_or_init(|| match &self.header {
+            None => Facts::default(),
+            Some(header) => {
+                log::info!("Asking libclang about {}", header.name);
+                gather(header)
+            }
+        })
+    }
+
+    /// Whether this function is declared never to throw, either `noexcept`
+    /// or `throw()`.
+    pub fn is_noexcept(&self, function: &str) -> bool {
+        self.facts().noexcept_functions.contains(function)
+    }
+
+    /// Whether this pointer parameter is annotated `_Nullable` (`Some(true)`)
+    /// or `_Nonnull` (`Some(false)`).
+    pub fn pointer_nullability(&self, function: &str, param: &str) -> Option<bool> {
+        self.facts()
+            .pointer_nullability
+            .get(&(function.to_string(), param.to_string()))
+            .copied()
+    }
+
+    /// The parameter from which a returned reference borrows, if exactly
+    /// one is marked `[[clang::lifetimebound]]`. This is `self` if the
+    /// attribute applies to `this`.
+    pub fn lifetime_source(&self, function: &str) -> Option<&str> {
+        self.facts()
+            .lifetime_sources
+            .get(function)
+            .map(String::as_str)
+    }
+
+    /// Whether this parameter is of a template instantiation whose first
+    /// argument is const, such as `std::span<const int>`. bindgen doesn't
+    /// tell us about the constness of template arguments.
+    pub fn first_template_arg_is_const(&self, function: &str, param: &str) -> bool {
+        self.facts()
+            .const_first_template_args
+            .contains(&(function.to_string(), param.to_string()))
+    }
+
+    /// If this field is protected, whether it's mutable, i.e. not `const`.
+    /// Reference and bit-field members aren't included, since there's no
+    /// way to make a reference to them.
+    pub fn protected_field_is_mutable(&self, field: &str) -> Option<bool> {
+        self.facts().protected_fields.get(field).copied()
+    }
+
+    /// The direct virtual bases of a class, each with whether it's a public
+    /// base. bindgen doesn't tell us about these at all.
+    pub fn virtual_bases(&self, class: &str) -> impl Iterator<Item = (&str, bool)> {
+        self.facts()
+            .virtual_bases
+            .get(class)
+            .into_iter()
+            .flatten()
+            .map(|(base, is_public)| (base.as_str(), *is_public))
+    }
+}
+
+/// Parses the header with libclang.
+fn gather(header: &Header) -> Facts {
+    let mut visitor = Visitor::default();
+    parse(
+        &header.name,
+        &header.contents,
+        &header.clang_args,
+        // We need the preprocessing record to see macros which
+        // expand to attributes.
+        CXTranslationUnit_SkipFunctionBodies | CXTranslationUnit_DetailedPreprocessingRecord,
+        |tu| {
+            visitor.tu = tu;
+            visit_children(
+                unsafe { clang_getTranslationUnitCursor(tu) },
+                visit,
+                &mut visitor,
+            );
+        },
+    );
+    visitor.into_facts()
+}
+
+/// Accumulates facts across every declaration of each function.
+struct Visitor {
+    tu: CXTranslationUnit,
+    noexcept: HashMap<String, bool>,
+    /// `None` where declarations disagree.
+    nullability: HashMap<(String, String), Option<bool>>,
+    /// `None` where declarations disagree, or there's no single source.
+    lifetime_sources: HashMap<String, Option<String>>,
+    /// `false` where declarations disagree.
+    const_first_template_args: HashMap<(String, String), bool>,
+    protected_fields: HashMap<String, bool>,
+    virtual_bases: HashMap<String, HashMap<String, bool>>,
+    /// Tokens which signify `[[clang::lifetimebound]]`: the attribute
+    /// itself, and macros which expand to it.
+    lifetimebound_tokens: HashSet<String>,
+}
+
+impl Default for Visitor {
+    fn default() -> Self {
+        Self {
+            tu: std::ptr::null_mut(),
+            noexcept: HashMap::new(),
+            nullability: HashMap::new(),
+            lifetime_sources: HashMap::new(),
+            const_first_template_args: HashMap::new(),
+            protected_fields: HashMap::new(),
+            virtual_bases: HashMap::new(),
+            lifetimebound_tokens: ["lifetimebound".to_string()].into_iter().collect(),
+        }
+    }
+}
+
+impl Visitor {
+    fn into_facts(self) -> Facts {
+        Facts {
+            noexcept_functions: self
+                .noexcept
+                .into_iter()
+                .filter_map(|(name, noexcept)| if noexcept { Some(name) } else { None })
+                .collect(),
+            pointer_nullability: self
+                .nullability
+                .into_iter()
+                .filter_map(|(key, nullable)| nullable.map(|nullable| (key, nullable)))
+                .collect(),
+            lifetime_sources: self
+                .lifetime_sources
+                .into_iter()
+                .filter_map(|(name, source)| source.map(|source| (name, source)))
+                .collect(),
+            const_first_template_args: self
+                .const_first_template_args
+                .into_iter()
+                .filter_map(|(key, is_const)| if is_const { Some(key) } else { None })
+                .collect(),
+            protected_fields: self.protected_fields,
+            virtual_bases: self.virtual_bases,
+        }
+    }
+
+    fn visit_base(&mut self, cursor: CXCursor, class: CXCursor) {
+        let (is_virtual, is_public, ty) = unsafe {
+            (
+                clang_isVirtualBase(cursor) != 0,
+                clang_getCXXAccessSpecifier(cursor) == CX_CXXPublic,
+                clang_getCanonicalType(clang_getCursorType(cursor)),
+            )
+        };
+        // We'd have to name the template arguments of an instantiation
+        // to cast to it, so we leave those out.
+        if !is_virtual || unsafe { clang_Type_getNumTemplateArguments(ty) } > 0 {
+            return;
+        }
+        let base = unsafe { clang_getTypeDeclaration(ty) };
+        self.virtual_bases
+            .entry(qualified_name(class))
+            .or_default()
+            .insert(qualified_name(base), is_public);
+    }
+
+    fn visit_field(&mut self, cursor: CXCursor) {
+        let (access, ty, is_bit_field) = unsafe {
+            (
+                clang_getCXXAccessSpecifier(cursor),
+                clang_getCursorType(cursor),
+                clang_Cursor_isBitField(cursor) != 0,
+            )
+        };
+        if access != CX_CXXProtected
+            || is_bit_field
+            || ty.kind == CXType_LValueReference
+            || ty.kind == CXType_RValueReference
+        {
+            return;
+        }
+        let is_mutable = unsafe { clang_isConstQualifiedType(ty) } == 0;
+        self.protected_fields
+            .insert(qualified_name(cursor), is_mutable);
+    }
+
+    fn visit_macro_definition(&mut self, cursor: CXCursor) {
+        let tokens = self.tokens(cursor);
+        if tokens
+            .iter()
+            .skip(1)
+            .any(|(token, _)| self.lifetimebound_tokens.contains(token))
+        {
+            self.lifetimebound_tokens.insert(spelling(cursor));
+        }
+    }
+
+    fn visit_function(&mut self, cursor: CXCursor) {
+        let name = qualified_name(cursor);
+        let spec = unsafe { clang_getCursorExceptionSpecificationType(cursor) };
+        let noexcept = spec == CXCursor_ExceptionSpecificationKind_BasicNoexcept
+            || spec == CXCursor_ExceptionSpecificationKind_DynamicNone;
+        *self.noexcept.entry(name.clone()).or_insert(true) &= noexcept;
+        let mut lifetime_sources = Vec::new();
+        if self.this_is_lifetimebound(cursor) {
+            lifetime_sources.push("self".to_string());
+        }
+        let num_args = unsafe { clang_Cursor_getNumArguments(cursor) };
+        for idx in 0..num_args.max(0) as c_uint {
+            let arg = unsafe { clang_Cursor_getArgument(cursor, idx) };
+            let param = spelling(arg);
+            if param.is_empty() {
+                continue;
+            }
+            if self.param_is_lifetimebound(arg) {
+                lifetime_sources.push(param.clone());
+            }
+            let ty = unsafe { clang_getCursorType(arg) };
+            *self
+                .const_first_template_args
+                .entry((name.clone(), param.clone()))
+                .or_insert(true) &= first_template_arg_is_const(ty);
+            if let Some(nullable) = nullability(ty) {
+                self.nullability
+                    .entry((name.clone(), param))
+                    .and_modify(|existing| {
+                        if *existing != Some(nullable) {
+                            *existing = None
+                        }
+                    })
+                    .or_insert(Some(nullable));
+            }
+        }
+        let lifetime_source = if lifetime_sources.len() == 1 {
+            lifetime_sources.pop()
+        } else {
+            None
+        };
+        self.lifetime_sources
+            .entry(name)
+            .and_modify(|existing| {
+                if *existing != lifetime_source {
+                    *existing = None
+                }
+            })
+            .or_insert(lifetime_source);
+    }
+
+    /// A parameter's attributes are children of it. libclang doesn't
+    /// tell us what they are, so we look at their tokens.
+    fn param_is_lifetimebound(&self, param: CXCursor) -> bool {
+        let mut attrs: Vec<CXCursor> = Vec::new();
+        visit_children(param, collect_attrs, &mut attrs);
+        attrs.into_iter().any(|attr| {
+            self.tokens(attr)
+                .iter()
+                .any(|(token, _)| self.lifetimebound_tokens.contains(token))
+        })
+    }
+
+    /// `[[clang::lifetimebound]]` applies to `this` if it's written after
+    /// the parameter list, where it's part of the function's type.
+    fn this_is_lifetimebound(&self, function: CXCursor) -> bool {
+        if unsafe { clang_getCursorKind(function) } != CXCursor_CXXMethod {
+            return false;
+        }
+        let name_offset = offset(unsafe { clang_getCursorLocation(function) });
+        let tokens = self.tokens(function);
+        let mut remaining = tokens
+            .iter()
+            .skip_while(|(_, offset)| *offset < name_offset)
+            .map(|(token, _)| token.as_str());
+        // The call operator's name has its own parentheses.
+        let mut param_lists = if spelling(function) == "operator()" {
+            2
+        } else {
+            1
+        };
+        while param_lists > 0 {
+            if !remaining.by_ref().any(|token| token == "(") {
+                return false;
+            }
+            let mut depth = 1;
+            for token in remaining.by_ref() {
+                match token {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+            param_lists -= 1;
+        }
+        remaining
+            .take_while(|token| !matches!(*token, "{" | ";" | "=" | ":"))
+            .any(|token| self.lifetimebound_tokens.contains(token))
+    }
+
+    /// The spelling and file offset of each token of a cursor.
+    fn tokens(&self, cursor: CXCursor) -> Vec<(String, c_uint)> {
+        let mut tokens = std::ptr::null_mut();
+        let mut num_tokens = 0;
+        unsafe {
+            clang_tokenize(
+                self.tu,
+                clang_getCursorExtent(cursor),
+                &mut tokens,
+                &mut num_tokens,
+            );
+            if tokens.is_null() {
+                return Vec::new();
+            }
+            let results = std::slice::from_raw_parts(tokens, num_tokens as usize)
+                .iter()
+                .map(|token| {
+                    (
+                        to_string(clang_getTokenSpelling(self.tu, *token)),
+                        offset(clang_getTokenLocation(self.tu, *token)),
+                    )
+                })
+                .collect();
+            clang_disposeTokens(self.tu, tokens, num_tokens);
+            results
+        }
+    }
+}
+
+extern "C" fn collect_attrs(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let attrs = unsafe { &mut *(data as *mut Vec<CXCursor>) };
+    if unsafe { clang_getCursorKind(cursor) } == CXCursor_UnexposedAttr {
+        attrs.push(cursor);
+    }
+    CXChildVisit_Continue
+}
+
+fn offset(location: CXSourceLocation) -> c_uint {
+    let mut offset = 0;
+    unsafe {
+        clang_getFileLocation(
+            location,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut offset,
+        );
+    }
+    offset
+}
+
+/// Parses a header with libclang, and calls `f` with the translation unit
+/// if that succeeds.
+fn parse(
+    header_name: &str,
+    header_contents: &str,
+    clang_args: &[String],
+    options: CXTranslationUnit_Flags,
+    f: impl FnOnce(CXTranslationUnit),
+) {
+    // bindgen loads libclang when it runs, but we may be first.
+    #[cfg(feature = "runtime")]
+    if !is_loaded() {
+        if let Err(err) = load() {
+            log::warn!("Unable to load libclang: {}", err);
+            return;
+        }
+    }
+    let header_name = CString::new(header_name).unwrap();
+    let header_contents = CString::new(header_contents).unwrap();
+    let clang_args: Vec<CString> = clang_args
+        .iter()
+        .map(|arg| CString::new(arg.as_str()).unwrap())
+        .collect();
+    let clang_arg_ptrs: Vec<*const c_char> = clang_args.iter().map(|arg| arg.as_ptr()).collect();
+    let mut unsaved = CXUnsavedFile {
+        Filename: header_name.as_ptr(),
+        Contents: header_contents.as_ptr(),
+        Length: header_contents.as_bytes().len() as c_ulong,
+    };
+    unsafe {
+        let index = clang_createIndex(0, 0);
+        let tu = clang_parseTranslationUnit(
+            index,
+            header_name.as_ptr(),
+            clang_arg_ptrs.as_ptr(),
+            clang_arg_ptrs.len() as c_int,
+            &mut unsaved,
+            1,
+            options,
+        );
+        if tu.is_null() {
+            log::warn!("libclang couldn't parse the header");
+        } else {
+            f(tu);
+            clang_disposeTranslationUnit(tu);
+        }
+        clang_disposeIndex(index);
+    }
+}
+
+/// Calls `visitor` for the children of `cursor`, passing it `data`.
+fn visit_children<T>(cursor: CXCursor, visitor: CXCursorVisitor, data: &mut T) {
+    unsafe {
+        clang_visitChildren(cursor, visitor, data as *mut T as CXClientData);
+    }
+}
+
+extern "C" fn visit(cursor: CXCursor, parent: CXCursor, data: CXClientData) -> CXChildVisitResult {
+    let visitor = unsafe { &mut *(data as *mut Visitor) };
+    let kind = unsafe { clang_getCursorKind(cursor) };
+    if kind == CXCursor_FunctionDecl || kind == CXCursor_CXXMethod {
+        visitor.visit_function(cursor);
+    } else if kind == CXCursor_FieldDecl {
+        visitor.visit_field(cursor);
+    } else if kind == CXCursor_CXXBaseSpecifier {
+        visitor.visit_base(cursor, parent);
+    } else if kind == CXCursor_MacroDefinition {
+        visitor.visit_macro_definition(cursor);
+    }
+    CXChildVisit_Recurse
+}
+
+fn first_template_arg_is_const(ty: CXType) -> bool {
+    unsafe {
+        let ty = clang_getCanonicalType(ty);
+        clang_Type_getNumTemplateArguments(ty) > 0
+            && clang_isConstQualifiedType(clang_Type_getTemplateArgumentAsType(ty, 0)) != 0
+    }
+}
+
+/// Finds any nullability annotation on the outermost pointer of a type.
+/// libclang only tells us about nullability directly from version 8, so
+/// we look at the type's spelling, e.g. `const int *_Nonnull`.
+fn nullability(ty: CXType) -> Option<bool> {
+    nullability_from_spelling(&to_string(unsafe { clang_getTypeSpelling(ty) }))
+}
+
+fn nullability_from_spelling(spelling: &str) -> Option<bool> {
+    let outermost = &spelling[spelling.rfind('*')? + 1..];
+    if outermost.contains("_Nullable") {
+        Some(true)
+    } else if outermost.contains("_Nonnull") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Names a function, field or class by its enclosing namespaces and classes.
+fn qualified_name(cursor: CXCursor) -> String {
+    let mut segments = vec![spelling(cursor)];
+    let mut parent = unsafe { clang_getCursorSemanticParent(cursor) };
+    while unsafe { clang_Cursor_isNull(parent) } == 0
+        && unsafe { clang_getCursorKind(parent) } != CXCursor_TranslationUnit
+    {
+        let segment = spelling(parent);
+        if !segment.is_empty() {
+            segments.push(segment);
+        }
+        parent = unsafe { clang_getCursorSemanticParent(parent) };
+    }
+    segments.reverse();
+    segments.join("::")
+}
+
+fn spelling(cursor: CXCursor) -> String {
+    to_string(unsafe { clang_getCursorSpelling(cursor) })
+}
+
+fn to_string(string: CXString) -> String {
+    unsafe {
+        let result = CStr::from_ptr(clang_getCString(string))
+            .to_string_lossy()
+            .into_owned();
+        clang_disposeString(string);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::nullability_from_spelling;
+
+    #[test]
+    fn test_nullability_from_spelling() {
+        assert_eq!(nullability_from_spelling("int *"), None);
+        assert_eq!(nullability_from_spelling("int"), None);
+        assert_eq!(nullability_from_spelling("uint32_t *_Nonnull"), Some(false));
+        assert_eq!(
+            nullability_from_spelling("const uint32_t *_Nullable"),
+            Some(true)
+        );
+        assert_eq!(nullability_from_spelling("int *_Nonnull *"), None);
+        assert_eq!(
+            nullability_from_spelling("int *_Nonnull *_Nullable"),
+            Some(true)
+        );
+    }
+}
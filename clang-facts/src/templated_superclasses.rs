@@ -0,0 +1,366 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! bindgen doesn't tell us about the methods of class templates, so can't
+//! tell us which virtual methods a Rust subclass of a template
+//! instantiation such as `ns::Listener<Event>` could override. Instead,
+//! we ask libclang for those methods, and declare a non-template class
+//! with the same name as the `concrete!` type, and the same virtual
+//! methods, for bindgen to look at. In the C++ we generate, that name is
+//! a typedef of the instantiation itself.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Write},
+};
+
+use clang_sys::*;
+use itertools::Itertools;
+
+use super::{parse, spelling, to_string, visit_children};
+
+const PROBE_PREFIX: &str = "autocxx_superclass_probe_";
+
+/// A virtual method of a templated superclass which we're unable to
+/// redeclare, so can't offer for a Rust subclass to override.
+#[derive(Debug)]
+pub struct UnredeclarableMethod {
+    pub superclass: String,
+    pub method: String,
+    pub problem: String,
+}
+
+impl Display for UnredeclarableMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unable to redeclare virtual method {} of templated superclass {} because {}",
+            self.method, self.superclass, self.problem
+        )
+    }
+}
+
+/// A method we found in an instantiation: its access, and either its
+/// declaration or why we can't declare it.
+type FoundMethod = (CX_CXXAccessSpecifier, Result<String, (String, String)>);
+
+/// What we learn about a superclass from its class template.
+#[derive(Default)]
+struct Template {
+    /// Names of the virtual methods which a subclass may override.
+    virtual_methods: Vec<String>,
+    has_constructors: bool,
+    default_constructor: Option<CX_CXXAccessSpecifier>,
+}
+
+impl Template {
+    /// The access of the superclass's default constructor, if it has one.
+    fn default_constructor(&self) -> Option<CX_CXXAccessSpecifier> {
+        if self.has_constructors {
+            self.default_constructor
+        } else {
+            Some(CX_CXXPublic)
+        }
+    }
+}
+
+/// Declares a class for each templated superclass, given as the template
+/// instantiation and the name by which we refer to it, for bindgen to
+/// parse along with the header. Fails if any virtual method can't be
+/// faithfully redeclared, rather than silently omitting it.
+pub fn declare_templated_superclasses(
+    header_name: &str,
+    header_contents: &str,
+    clang_args: &[String],
+    superclasses: &[(&str, &str)],
+) -> Result<String, UnredeclarableMethod> {
+    // First we find which virtual methods each class template declares...
+    let mut probes = header_contents.to_string();
+    for (idx, (cpp_definition, _)) in superclasses.iter().enumerate() {
+        writeln!(
+            probes,
+            "typedef {} {}{};",
+            cpp_definition, PROBE_PREFIX, idx
+        )
+        .unwrap();
+    }
+    let mut templates: HashMap<usize, Template> = HashMap::new();
+    parse(
+        header_name,
+        &probes,
+        clang_args,
+        CXTranslationUnit_SkipFunctionBodies,
+        |tu| {
+            visit_children(
+                unsafe { clang_getTranslationUnitCursor(tu) },
+                find_templates,
+                &mut templates,
+            )
+        },
+    );
+    // ... then we bring those methods into a class derived from each
+    // instantiation, such that libclang tells us about their instantiated
+    // signatures.
+    let mut probes = header_contents.to_string();
+    for (idx, (cpp_definition, _)) in superclasses.iter().enumerate() {
+        writeln!(
+            probes,
+            "struct {}{} : public {} {{",
+            PROBE_PREFIX, idx, cpp_definition
+        )
+        .unwrap();
+        for method in templates
+            .get(&idx)
+            .into_iter()
+            .flat_map(|template| &template.virtual_methods)
+        {
+            writeln!(probes, "    using {}::{};", cpp_definition, method).unwrap();
+        }
+        writeln!(probes, "}};").unwrap();
+    }
+    let mut methods: HashMap<usize, Vec<FoundMethod>> = HashMap::new();
+    parse(
+        header_name,
+        &probes,
+        clang_args,
+        CXTranslationUnit_SkipFunctionBodies,
+        |tu| {
+            visit_children(
+                unsafe { clang_getTranslationUnitCursor(tu) },
+                find_methods,
+                &mut methods,
+            )
+        },
+    );
+    superclasses
+        .iter()
+        .enumerate()
+        .map(|(idx, (cpp_definition, name))| {
+            let mut members = methods
+                .remove(&idx)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(access, declaration)| {
+                    declaration
+                        .map(|declaration| (access, declaration))
+                        .map_err(|(method, problem)| UnredeclarableMethod {
+                            superclass: cpp_definition.to_string(),
+                            method,
+                            problem,
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            match templates.get(&idx) {
+                None => log::warn!(
+                    "Couldn't find the class template for superclass {}",
+                    cpp_definition
+                ),
+                Some(template) => {
+                    if let Some(access) = template.default_constructor() {
+                        members.push((access, format!("{}();", name)));
+                    }
+                }
+            }
+            let section = |access: CX_CXXAccessSpecifier| {
+                members
+                    .iter()
+                    .filter(|(member_access, _)| *member_access == access)
+                    .map(|(_, member)| format!("    {}\n", member))
+                    .join("")
+            };
+            Ok(format!(
+                "class {} : public {} {{\npublic:\n{}protected:\n{}}};\n",
+                name,
+                cpp_definition,
+                section(CX_CXXPublic),
+                section(CX_CXXProtected)
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|classes| classes.join("\n"))
+}
+
+/// Which of our probes this is, if any.
+fn probe_index(cursor: CXCursor) -> Option<usize> {
+    spelling(cursor).strip_prefix(PROBE_PREFIX)?.parse().ok()
+}
+
+extern "C" fn find_templates(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let templates = unsafe { &mut *(data as *mut HashMap<usize, Template>) };
+    if unsafe { clang_getCursorKind(cursor) } == CXCursor_TypedefDecl {
+        if let Some(idx) = probe_index(cursor) {
+            let template = unsafe {
+                let ty = clang_getCanonicalType(clang_getTypedefDeclUnderlyingType(cursor));
+                clang_getSpecializedCursorTemplate(clang_getTypeDeclaration(ty))
+            };
+            if unsafe { clang_Cursor_isNull(template) } == 0 {
+                let mut found = Template::default();
+                visit_children(template, find_template_members, &mut found);
+                templates.insert(idx, found);
+            }
+        }
+    }
+    CXChildVisit_Continue
+}
+
+extern "C" fn find_template_members(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let template = unsafe { &mut *(data as *mut Template) };
+    let kind = unsafe { clang_getCursorKind(cursor) };
+    let access = unsafe { clang_getCXXAccessSpecifier(cursor) };
+    if kind == CXCursor_Constructor {
+        template.has_constructors = true;
+        if access != CX_CXXPrivate && unsafe { clang_Cursor_getNumArguments(cursor) } == 0 {
+            template.default_constructor = Some(access);
+        }
+    } else if kind == CXCursor_CXXMethod
+        && access != CX_CXXPrivate
+        && unsafe { clang_CXXMethod_isVirtual(cursor) } != 0
+    {
+        let name = spelling(cursor);
+        if !template.virtual_methods.contains(&name) {
+            template.virtual_methods.push(name);
+        }
+    }
+    CXChildVisit_Continue
+}
+
+extern "C" fn find_methods(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let methods = unsafe { &mut *(data as *mut HashMap<usize, Vec<FoundMethod>>) };
+    if unsafe { clang_getCursorKind(cursor) } == CXCursor_StructDecl {
+        if let Some(idx) = probe_index(cursor) {
+            let mut found = Vec::new();
+            visit_children(cursor, find_using_declarations, &mut found);
+            methods.insert(idx, found);
+        }
+    }
+    CXChildVisit_Continue
+}
+
+/// Each using-declaration refers to every overload of a method in the
+/// instantiation.
+extern "C" fn find_using_declarations(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let methods = unsafe { &mut *(data as *mut Vec<FoundMethod>) };
+    if unsafe { clang_getCursorKind(cursor) } == CXCursor_UsingDeclaration {
+        let overloads = unsafe { clang_getCursorReferenced(cursor) };
+        for idx in 0..unsafe { clang_getNumOverloadedDecls(overloads) } {
+            let method = unsafe { clang_getOverloadedDecl(overloads, idx) };
+            if unsafe { clang_getCursorKind(method) } == CXCursor_CXXMethod
+                && unsafe { clang_CXXMethod_isVirtual(method) } != 0
+            {
+                let declaration =
+                    declare_method(method).map_err(|problem| (spelling(method), problem));
+                methods.push((unsafe { clang_getCXXAccessSpecifier(method) }, declaration));
+            }
+        }
+    }
+    CXChildVisit_Continue
+}
+
+/// Redeclares a virtual method of a template instantiation, with the
+/// template arguments substituted into its signature, or explains why we
+/// can't.
+fn declare_method(method: CXCursor) -> Result<String, String> {
+    if unsafe { clang_Cursor_isVariadic(method) } != 0 {
+        return Err("it's variadic".into());
+    }
+    let return_type = type_spelling(unsafe { clang_getCursorResultType(method) })?;
+    let num_args = unsafe { clang_Cursor_getNumArguments(method) };
+    let params = (0..num_args.max(0) as u32)
+        .map(|idx| {
+            let arg = unsafe { clang_Cursor_getArgument(method, idx) };
+            let ty = type_spelling(unsafe { clang_getCursorType(arg) })?;
+            let name = spelling(arg);
+            if has_default_argument(arg) {
+                return Err(format!("parameter {} has a default argument", name));
+            }
+            Ok(if name.is_empty() {
+                ty
+            } else {
+                format!("{} {}", ty, name)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let is_const = unsafe { clang_CXXMethod_isConst(method) } != 0;
+    let ref_qualifier = unsafe { clang_Type_getCXXRefQualifier(clang_getCursorType(method)) };
+    let ref_qualifier = if ref_qualifier == CXRefQualifier_LValue {
+        " &"
+    } else if ref_qualifier == CXRefQualifier_RValue {
+        " &&"
+    } else {
+        ""
+    };
+    let spec = unsafe { clang_getCursorExceptionSpecificationType(method) };
+    let exception_spec = if spec == CXCursor_ExceptionSpecificationKind_None {
+        ""
+    } else if spec == CXCursor_ExceptionSpecificationKind_BasicNoexcept
+        || spec == CXCursor_ExceptionSpecificationKind_DynamicNone
+    {
+        " noexcept"
+    } else {
+        return Err("of its exception specification".into());
+    };
+    let is_pure = unsafe { clang_CXXMethod_isPureVirtual(method) } != 0;
+    Ok(format!(
+        "virtual {} {}({}){}{}{}{};",
+        return_type,
+        spelling(method),
+        params.join(", "),
+        if is_const { " const" } else { "" },
+        ref_qualifier,
+        exception_spec,
+        if is_pure { " = 0" } else { "" }
+    ))
+}
+
+/// Types such as function pointers can't be declared by writing the
+/// type's spelling before the parameter name.
+fn type_spelling(ty: CXType) -> Result<String, String> {
+    let spelling = to_string(unsafe { clang_getTypeSpelling(ty) });
+    if spelling.contains(['(', '[']) {
+        Err(format!("it uses the type {}", spelling))
+    } else {
+        Ok(spelling)
+    }
+}
+
+/// A parameter's default argument is an expression among its children.
+fn has_default_argument(param: CXCursor) -> bool {
+    let mut found = false;
+    visit_children(param, find_expression, &mut found);
+    found
+}
+
+extern "C" fn find_expression(
+    cursor: CXCursor,
+    _parent: CXCursor,
+    data: CXClientData,
+) -> CXChildVisitResult {
+    let found = unsafe { &mut *(data as *mut bool) };
+    if unsafe { clang_isExpression(clang_getCursorKind(cursor)) } != 0 {
+        *found = true;
+        CXChildVisit_Break
+    } else {
+        CXChildVisit_Continue
+    }
+}
@@ -9,8 +9,8 @@
 use std::collections::HashSet;
 
 use autocxx_parser::{
-    directives::{EXTERN_RUST_FUN, EXTERN_RUST_TYPE},
-    RustFun, RustPath,
+    directives::{EXTERN_RUST_FUN, EXTERN_RUST_TRAIT, EXTERN_RUST_TYPE},
+    RustFun, RustPath, RustTrait,
 };
 use itertools::Itertools;
 use proc_macro2::Ident;
@@ -27,6 +27,7 @@ pub(super) struct Discoveries {
     pub(super) cpp_list: HashSet<String>,
     pub(super) extern_rust_funs: Vec<RustFun>,
     pub(super) extern_rust_types: Vec<RustPath>,
+    pub(super) extern_rust_traits: Vec<RustTrait>,
 }
 
 #[derive(Debug)]
@@ -55,13 +56,16 @@ impl Discoveries {
     }
 
     pub(crate) fn found_rust(&self) -> bool {
-        !self.extern_rust_funs.is_empty() || !self.extern_rust_types.is_empty()
+        !self.extern_rust_funs.is_empty()
+            || !self.extern_rust_types.is_empty()
+            || !self.extern_rust_traits.is_empty()
     }
 
     pub(crate) fn extend(&mut self, other: Self) {
         self.cpp_list.extend(other.cpp_list);
         self.extern_rust_funs.extend(other.extern_rust_funs);
         self.extern_rust_types.extend(other.extern_rust_types);
+        self.extern_rust_traits.extend(other.extern_rust_traits);
     }
 }
 
@@ -143,6 +147,12 @@ impl<'b> PerModDiscoveries<'b> {
                 for item in &tr.items {
                     self.search_trait_item(item)?
                 }
+                if Self::has_attr(&tr.attrs, EXTERN_RUST_TRAIT) {
+                    self.discoveries.extern_rust_traits.push(RustTrait {
+                        path: self.deeper_path(&tr.ident),
+                        item: tr.clone(),
+                    });
+                }
             }
             Item::Struct(ItemStruct { ident, attrs, .. })
             | Item::Enum(ItemEnum { ident, attrs, .. })
@@ -683,6 +693,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extern_rust_trait() {
+        let mut discoveries = Discoveries::default();
+        let itm = parse_quote! {
+            mod sinks {
+                #[autocxx::extern_rust::extern_rust_trait]
+                pub trait Sink {
+                    fn write(&mut self, data: &[u8]);
+                }
+            }
+        };
+        discoveries.search_item(&itm, None).unwrap();
+        let rust_trait = discoveries.extern_rust_traits.first().unwrap();
+        assert!(rust_trait.item.ident == "Sink");
+        assert_eq!(
+            rust_trait.path.to_token_stream().to_string(),
+            "sinks :: Sink"
+        );
+    }
+
     #[test]
     fn test_add_receiver() {
         let meth: ImplItemMethod = parse_quote! {
@@ -60,6 +60,7 @@ pub(crate) fn mark_types_abstract(mut apis: ApiVec<FnPrePhase2>) -> ApiVec<FnPre
                                 pod:
                                     PodAnalysis {
                                         bases,
+                                        virtual_bases,
                                         kind: TypeKind::Pod | TypeKind::NonPod,
                                         castable_bases,
                                         field_deps,
@@ -80,6 +81,7 @@ pub(crate) fn mark_types_abstract(mut apis: ApiVec<FnPrePhase2>) -> ApiVec<FnPre
                             analysis: PodAndConstructorAnalysis {
                                 pod: PodAnalysis {
                                     bases,
+                                    virtual_bases,
                                     kind: TypeKind::Abstract,
                                     castable_bases,
                                     field_deps,
@@ -6,6 +6,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::collections::{HashMap, HashSet};
+
 use itertools::Itertools;
 use quote::quote;
 use syn::{parse_quote, FnArg};
@@ -27,18 +29,21 @@ const SUPPORT_MUTABLE_CASTS: bool = false;
 
 use super::{
     fun::function_wrapper::{CppFunctionBody, CppFunctionKind},
-    pod::{PodAnalysis, PodPhase},
+    pod::{has_own_vtable, PodAnalysis, PodPhase},
 };
 
 pub(crate) fn add_casts(apis: ApiVec<PodPhase>) -> ApiVec<PodPhase> {
+    let polymorphic_types = find_polymorphic_types(&apis);
+    let ancestors = ClassHierarchy::new(&apis).all_castable_ancestors();
     apis.into_iter()
         .flat_map(|api| {
             let mut resultant_apis = match api {
-                Api::Struct {
-                    ref name,
-                    details: _,
-                    ref analysis,
-                } => create_casts(&name.name, analysis).collect_vec(),
+                Api::Struct { ref name, .. } => {
+                    let ancestors = &ancestors[&name.name];
+                    create_casts(&name.name, ancestors)
+                        .chain(create_downcasts(&name.name, ancestors, &polymorphic_types))
+                        .collect_vec()
+                }
                 _ => Vec::new(),
             };
             resultant_apis.push(api);
@@ -47,18 +52,173 @@ pub(crate) fn add_casts(apis: ApiVec<PodPhase>) -> ApiVec<PodPhase> {
         .collect()
 }
 
+/// The inheritance graph formed by the direct bases of each struct.
+struct ClassHierarchy<'a> {
+    analyses: HashMap<&'a QualifiedName, &'a PodAnalysis>,
+}
+
+impl<'a> ClassHierarchy<'a> {
+    fn new(apis: &'a ApiVec<PodPhase>) -> Self {
+        Self {
+            analyses: apis
+                .iter()
+                .filter_map(|api| match api {
+                    Api::Struct { name, analysis, .. } => Some((&name.name, analysis)),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn all_castable_ancestors(&self) -> HashMap<QualifiedName, Vec<QualifiedName>> {
+        self.analyses
+            .keys()
+            .map(|name| ((*name).clone(), self.castable_ancestors(name)))
+            .collect()
+    }
+
+    /// All the direct and indirect bases of `name` to which we can cast.
+    /// Each step must be a public base on the allowlist, and the
+    /// ancestor must be reachable by only one path, else C++ would reject
+    /// the conversion as ambiguous. The conversion itself always happens
+    /// in a C++ shim, so any pointer adjustment is done by the compiler.
+    fn castable_ancestors(&self, name: &QualifiedName) -> Vec<QualifiedName> {
+        let mut ancestors = Vec::new();
+        let mut to_visit = vec![name];
+        while let Some(current) = to_visit.pop() {
+            if let Some(analysis) = self.analyses.get(current) {
+                for base in &analysis.castable_bases {
+                    if !ancestors.contains(base) {
+                        ancestors.push(base.clone());
+                        to_visit.push(base);
+                    }
+                }
+            }
+        }
+        ancestors.retain(|ancestor| self.count_paths(name, ancestor) == 1);
+        ancestors
+    }
+
+    /// How many `to` subobjects there are within a `from`, considering
+    /// all bases, not just castable ones. That's one for each route
+    /// through the inheritance graph, except that all the routes through
+    /// a given virtual base share a single instance of it.
+    fn count_paths(&self, from: &QualifiedName, to: &QualifiedName) -> usize {
+        let mut virtual_bases = HashSet::new();
+        self.find_virtual_bases(from, &mut virtual_bases);
+        self.count_non_virtual_paths(from, to)
+            + virtual_bases
+                .into_iter()
+                .map(|base| {
+                    if base == to {
+                        1
+                    } else {
+                        self.count_non_virtual_paths(base, to)
+                    }
+                })
+                .sum::<usize>()
+    }
+
+    /// How many routes there are from `from` to `to` which don't pass
+    /// through any virtual base.
+    fn count_non_virtual_paths(&self, from: &QualifiedName, to: &QualifiedName) -> usize {
+        self.analyses
+            .get(from)
+            .map(|analysis| {
+                analysis
+                    .bases
+                    .difference(&analysis.virtual_bases)
+                    .map(|base| {
+                        if base == to {
+                            1
+                        } else {
+                            self.count_non_virtual_paths(base, to)
+                        }
+                    })
+                    .sum()
+            })
+            .unwrap_or_default()
+    }
+
+    /// All the virtual bases of `name`, direct or indirect.
+    fn find_virtual_bases<'b>(
+        &'b self,
+        name: &QualifiedName,
+        virtual_bases: &mut HashSet<&'b QualifiedName>,
+    ) {
+        if let Some(analysis) = self.analyses.get(name) {
+            virtual_bases.extend(analysis.virtual_bases.iter());
+            for base in &analysis.bases {
+                self.find_virtual_bases(base, virtual_bases);
+            }
+        }
+    }
+}
+
 fn create_casts<'a>(
     name: &'a QualifiedName,
-    analysis: &'a PodAnalysis,
+    ancestors: &'a [QualifiedName],
 ) -> impl Iterator<Item = Api<PodPhase>> + 'a {
     // Create casts only to base classes which are on the allowlist
     // because otherwise we won't know for sure whether they're abstract or not.
-    analysis
-        .castable_bases
+    ancestors
         .iter()
         .flat_map(move |base| cast_types().map(|mutable| create_cast(name, base, mutable)))
 }
 
+/// Find all types which have a vtable, either directly or because one
+/// of their bases does. Only these can be the source of a `dynamic_cast`.
+fn find_polymorphic_types(apis: &ApiVec<PodPhase>) -> HashSet<QualifiedName> {
+    let mut polymorphic_types: HashSet<QualifiedName> = apis
+        .iter()
+        .filter_map(|api| match api {
+            Api::Struct { name, details, .. } if has_own_vtable(&details.item) => {
+                Some(name.name.clone())
+            }
+            _ => None,
+        })
+        .collect();
+    loop {
+        let newly_polymorphic = apis
+            .iter()
+            .filter_map(|api| match api {
+                Api::Struct { name, analysis, .. }
+                    if !polymorphic_types.contains(&name.name)
+                        && analysis
+                            .bases
+                            .iter()
+                            .any(|base| polymorphic_types.contains(base)) =>
+                {
+                    Some(name.name.clone())
+                }
+                _ => None,
+            })
+            .collect_vec();
+        if newly_polymorphic.is_empty() {
+            break;
+        }
+        polymorphic_types.extend(newly_polymorphic);
+    }
+    polymorphic_types
+}
+
+/// Create checked downcasts from each polymorphic base class to
+/// this class.
+fn create_downcasts<'a>(
+    name: &'a QualifiedName,
+    ancestors: &'a [QualifiedName],
+    polymorphic_types: &'a HashSet<QualifiedName>,
+) -> impl Iterator<Item = Api<PodPhase>> + 'a {
+    ancestors
+        .iter()
+        .filter(move |base| polymorphic_types.contains(base))
+        .flat_map(move |base| {
+            [CastMutability::ConstToConst, CastMutability::MutToMut]
+                .into_iter()
+                .map(move |mutable| create_downcast(base, name, mutable))
+        })
+}
+
 /// Iterate through the types of cast we should make.
 fn cast_types() -> impl Iterator<Item = CastMutability> {
     if SUPPORT_MUTABLE_CASTS {
@@ -74,7 +234,7 @@ fn cast_types() -> impl Iterator<Item = CastMutability> {
 }
 
 fn create_cast(from: &QualifiedName, to: &QualifiedName, mutable: CastMutability) -> Api<PodPhase> {
-    let name = name_for_cast(from, to, mutable);
+    let name = name_for_cast("cast", from, to, mutable);
     let ident = name.get_final_ident();
     let from_typ = from.to_type_path();
     let to_typ = to.to_type_path();
@@ -113,6 +273,55 @@ fn create_cast(from: &QualifiedName, to: &QualifiedName, mutable: CastMutability
             }),
             synthetic_cpp: Some((CppFunctionBody::Cast, CppFunctionKind::Function)),
             is_deleted: false,
+            is_variadic: false,
+            provenance: Provenance::SynthesizedOther,
+        }),
+        analysis: (),
+    }
+}
+
+fn create_downcast(
+    from: &QualifiedName,
+    to: &QualifiedName,
+    mutable: CastMutability,
+) -> Api<PodPhase> {
+    let name = name_for_cast("downcast", from, to, mutable);
+    let ident = name.get_final_ident();
+    let from_typ = from.to_type_path();
+    let to_typ = to.to_type_path();
+    let mutability = match mutable {
+        CastMutability::MutToMut => quote! { mut },
+        _ => quote! { const },
+    };
+    let fnarg: FnArg = parse_quote! {
+        this: * #mutability #from_typ
+    };
+    Api::Function {
+        name: ApiName::new_from_qualified_name(name),
+        fun: Box::new(crate::conversion::api::FuncToConvert {
+            ident,
+            doc_attrs: Vec::new(),
+            inputs: [fnarg].into_iter().collect(),
+            output: parse_quote! {
+                -> * #mutability #to_typ
+            },
+            vis: parse_quote! { pub },
+            virtualness: crate::conversion::api::Virtualness::None,
+            cpp_vis: crate::conversion::api::CppVisibility::Public,
+            special_member: None,
+            unused_template_param: false,
+            // The result may be null, so isn't a reference.
+            references: References::new_with_this_as_reference(),
+            original_name: None,
+            self_ty: Some(from.clone()),
+            synthesized_this_type: None,
+            add_to_trait: Some(TraitSynthesis::Downcast {
+                to_type: to.clone(),
+                mutable,
+            }),
+            synthetic_cpp: Some((CppFunctionBody::Downcast, CppFunctionKind::Function)),
+            is_deleted: false,
+            is_variadic: false,
             provenance: Provenance::SynthesizedOther,
         }),
         analysis: (),
@@ -120,6 +329,7 @@ fn create_cast(from: &QualifiedName, to: &QualifiedName, mutable: CastMutability
 }
 
 fn name_for_cast(
+    prefix: &str,
     from: &QualifiedName,
     to: &QualifiedName,
     mutable: CastMutability,
@@ -130,7 +340,8 @@ fn name_for_cast(
         CastMutability::MutToMut => "_mut",
     };
     let name = format!(
-        "cast_{}_to_{}{}",
+        "{}_{}_to_{}{}",
+        prefix,
         from.get_final_item(),
         to.get_final_item(),
         suffix
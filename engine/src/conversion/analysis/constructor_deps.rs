@@ -27,7 +27,8 @@ use super::fun::{
 /// constructors). Decorate each struct with a note of its constructors,
 /// which will later be used as edges in the garbage collection, because
 /// typically any use of a type will require us to call its copy or move
-/// constructor. The same applies to its alloc/free functions.
+/// constructor. The same applies to its alloc/free functions, and to the
+/// function which pushes it onto a vector.
 pub(crate) fn decorate_types_with_constructor_deps(apis: ApiVec<FnPrePhase2>) -> ApiVec<FnPhase> {
     let mut constructors_and_allocators_by_type = find_important_constructors(&apis);
     let mut results = ApiVec::new();
@@ -85,7 +86,8 @@ fn find_important_constructors(
                                 TraitMethodKind::Alloc
                                 | TraitMethodKind::Dealloc
                                 | TraitMethodKind::CopyConstructor
-                                | TraitMethodKind::MoveConstructor,
+                                | TraitMethodKind::MoveConstructor
+                                | TraitMethodKind::VectorPush,
                             impl_for,
                             ..
                         },
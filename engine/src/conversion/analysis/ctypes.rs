@@ -18,14 +18,15 @@ use crate::{conversion::api::Api, known_types::known_types, types::QualifiedName
 use super::deps::HasDependencies;
 use super::fun::FnPhase;
 
-/// Spot any variable-length C types (e.g. unsigned long)
-/// used in the [Api]s and append those as extra APIs.
+/// Spot any variable-length C types (e.g. unsigned long), or C++ string
+/// types unknown to cxx, used in the [Api]s and append those as extra APIs.
 pub(crate) fn append_ctype_information(apis: &mut ApiVec<FnPhase>) {
     let ctypes: HashMap<Ident, QualifiedName> = apis
         .iter()
         .flat_map(|api| api.deps())
         .filter(|ty| known_types().is_ctype(ty))
-        .map(|ty| (ty.get_final_ident(), ty.clone()))
+        .map(|ty| known_types().canonical_name(ty).unwrap())
+        .map(|ty| (ty.get_final_ident(), ty))
         .collect();
     for (id, typename) in ctypes {
         apis.push(Api::CType {
@@ -49,8 +49,8 @@ impl HasDependencies for Api<FnPrePhase1> {
             Api::Function { analysis, .. } => Box::new(analysis.deps.iter()),
             Api::Subclass {
                 name: _,
-                superclass,
-            } => Box::new(std::iter::once(superclass)),
+                superclasses,
+            } => Box::new(superclasses.iter()),
             Api::RustSubclassFn { details, .. } => Box::new(details.dependencies.iter()),
             Api::RustFn { receiver, .. } => Box::new(receiver.iter()),
             _ => Box::new(std::iter::empty()),
@@ -102,8 +102,8 @@ impl HasDependencies for Api<FnPhase> {
             Api::Function { analysis, .. } => Box::new(analysis.deps.iter()),
             Api::Subclass {
                 name: _,
-                superclass,
-            } => Box::new(std::iter::once(superclass)),
+                superclasses,
+            } => Box::new(superclasses.iter()),
             Api::RustSubclassFn { details, .. } => Box::new(details.dependencies.iter()),
             Api::RustFn { receiver, .. } => Box::new(receiver.iter()),
             _ => Box::new(std::iter::empty()),
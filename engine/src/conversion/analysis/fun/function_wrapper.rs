@@ -8,9 +8,11 @@
 
 use crate::{
     conversion::api::SubclassName,
-    types::{Namespace, QualifiedName},
+    known_types::CallbackSignature,
+    types::{make_ident, Namespace, QualifiedName},
 };
-use syn::{parse_quote, Ident, Type};
+use quote::{format_ident, ToTokens};
+use syn::{parse_quote, Expr, GenericArgument, Ident, PathArguments, Type};
 
 #[derive(Clone, Debug)]
 pub(crate) enum CppConversionType {
@@ -20,6 +22,24 @@ pub(crate) enum CppConversionType {
     FromPtrToValue,
     FromValueToUniquePtr,
     FromPtrToMove,
+    /// A `rust::Slice` parameter which the C++ API wants as a span type.
+    FromSliceToSpan,
+    /// A span of const elements which the C++ API takes, which we make
+    /// from a shared Rust slice.
+    FromSharedSliceToSpan,
+    /// A span type returned by the C++ API which we return as a `rust::Slice`.
+    FromSpanToSlice,
+    /// A function pointer, which cxx can't represent, so is passed
+    /// across the bridge as a `usize`.
+    FromUsizeToFunctionPointer,
+    /// A `std::function` which we construct from a boxed Rust closure.
+    FromBoxToFunction,
+    /// A raw pointer returned by the C++ API, which the caller owns, so
+    /// we return it within a `std::unique_ptr`.
+    FromPtrToUniquePtr,
+    /// A `std::unique_ptr` whose contents we hand over to a C++ API which
+    /// takes ownership via a raw pointer.
+    FromUniquePtrToPtr,
 }
 
 impl CppConversionType {
@@ -33,6 +53,8 @@ impl CppConversionType {
                 CppConversionType::FromValueToUniquePtr
             }
             CppConversionType::FromValueToUniquePtr => CppConversionType::FromUniquePtrToValue,
+            CppConversionType::FromPtrToUniquePtr => CppConversionType::FromUniquePtrToPtr,
+            CppConversionType::FromUniquePtrToPtr => CppConversionType::FromPtrToUniquePtr,
             _ => panic!("Did not expect to have to invert this conversion"),
         }
     }
@@ -43,10 +65,112 @@ pub(crate) enum RustConversionType {
     None,
     FromStr,
     ToBoxedUpHolder(SubclassName),
+    /// As [`RustConversionType::ToBoxedUpHolder`], for a subclass declared
+    /// `thread_safe`.
+    ToBoxedUpThreadSafeHolder(SubclassName),
     FromPinMaybeUninitToPtr,
     FromPinMoveRefToPtr,
     FromTypeToPtr,
     FromValueParamToPtr,
+    /// The data pointer of a pointer+length pair, accepted from Rust
+    /// as a slice.
+    FromSliceToPtr,
+    /// The length of a pointer+length pair. This doesn't appear in the Rust
+    /// wrapper at all; it's calculated from the named slice parameter.
+    FromSliceToLength(Ident),
+    /// A `std::pair` return value, which arrives in a `UniquePtr` and which
+    /// we split into a Rust tuple.
+    FromUniquePtrToTuple(Vec<TupleElement>),
+    /// A function pointer, which we pass across the bridge as a `usize`.
+    FromFunctionPointerToUsize,
+    /// A Rust closure which we box up such that C++ can call it as a
+    /// `std::function`.
+    FromClosureToBox(Box<CallbackSignature>),
+    /// A pointer parameter declared by `nonnull!` never to be null, which
+    /// we accept from Rust as a reference.
+    FromReferenceToPtr,
+    /// A pointer parameter declared by `nullable!`, which we accept from
+    /// Rust as an `Option` of a reference.
+    FromOptionalReferenceToPtr,
+    /// A `const char*` argument to a variadic function, which we accept
+    /// from Rust as a `&CStr`.
+    FromCStrToPtr,
+    /// An out-parameter of a `result_of!` function. This doesn't appear in
+    /// the Rust wrapper's parameters; it's returned within the `Result`.
+    FromOutParamToPtr,
+    /// The pointer returned by a downcast, which is null if the object
+    /// wasn't of the requested type, so we return an `Option` of a reference.
+    FromPtrToOptionalReference,
+    /// A status code which we turn into a `Result`, per `result_of!`.
+    FromStatusToResult(Box<StatusConversion>),
+}
+
+/// How to turn a status code returned by C++ into a Rust `Result`.
+#[derive(Clone)]
+pub(crate) struct StatusConversion {
+    /// How we determine success.
+    pub(crate) check: SuccessCheck,
+    /// The out-parameter returned on success, and the type it points to.
+    pub(crate) out: Option<(Ident, Type)>,
+}
+
+/// How we determine whether a status code means success.
+#[derive(Clone)]
+pub(crate) enum SuccessCheck {
+    /// The status code is a primitive, enum or POD type, which is
+    /// compared with this Rust expression.
+    OkWhen(Box<Expr>),
+    /// The status is a non-POD type, returned to Rust in a `UniquePtr`.
+    /// A C++ shim evaluates this predicate on it.
+    OkIf(String),
+}
+
+impl StatusConversion {
+    /// The name of the shim function which evaluates an `OkIf` predicate
+    /// on the status returned by a given wrapper function.
+    pub(crate) fn checker_name(wrapper_function_name: &Ident) -> Ident {
+        format_ident!("{}_is_ok", wrapper_function_name)
+    }
+}
+
+impl std::fmt::Debug for StatusConversion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.check {
+            SuccessCheck::OkWhen(ok_when) => write!(f, "ok_when={}", ok_when.to_token_stream())?,
+            SuccessCheck::OkIf(ok_if) => write!(f, "ok_if={}", ok_if)?,
+        }
+        if let Some((out, ty)) = &self.out {
+            write!(f, ", out={}: {}", out, ty.to_token_stream())?;
+        }
+        Ok(())
+    }
+}
+
+/// One element of a `std::pair` or `std::tuple` which we're returning as a Rust tuple.
+#[derive(Clone)]
+pub(crate) struct TupleElement {
+    pub(crate) ty: Type,
+    /// Whether this is returned by value, or otherwise within a `UniquePtr`.
+    pub(crate) by_value: bool,
+}
+
+impl std::fmt::Debug for TupleElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (by_value={})",
+            self.ty.to_token_stream(),
+            self.by_value
+        )
+    }
+}
+
+impl TupleElement {
+    /// The name of the shim function which extracts this element from the
+    /// tuple returned by a given wrapper function.
+    pub(crate) fn accessor_name(wrapper_function_name: &Ident, idx: usize) -> Ident {
+        format_ident!("{}_get_{}", wrapper_function_name, idx)
+    }
 }
 
 impl RustConversionType {
@@ -56,6 +180,12 @@ impl RustConversionType {
             _ => None,
         }
     }
+
+    /// Whether this parameter appears in the signature of the Rust
+    /// wrapper function.
+    pub(crate) fn is_wrapper_param(&self) -> bool {
+        !matches!(self, Self::FromSliceToLength(_) | Self::FromOutParamToPtr)
+    }
 }
 
 /// A policy for converting types. Conversion may occur on both the Rust and
@@ -100,6 +230,13 @@ impl TypeConversionPolicy {
     pub(crate) fn unconverted_rust_type(&self) -> Type {
         match self.cpp_conversion {
             CppConversionType::FromValueToUniquePtr => self.make_unique_ptr_type(),
+            CppConversionType::FromPtrToUniquePtr => self.make_owning_unique_ptr_type(),
+            CppConversionType::FromSpanToSlice => {
+                let innerty = self.span_element_type();
+                parse_quote! {
+                    &[#innerty]
+                }
+            }
             _ => self.unwrapped_type.clone(),
         }
     }
@@ -113,10 +250,93 @@ impl TypeConversionPolicy {
                     *mut #innerty
                 }
             }
+            // Unless we know the C++ span is of const elements, we demand
+            // exclusive access.
+            CppConversionType::FromSliceToSpan => {
+                let innerty = self.span_element_type();
+                parse_quote! {
+                    &mut [#innerty]
+                }
+            }
+            CppConversionType::FromSharedSliceToSpan => {
+                let innerty = self.span_element_type();
+                parse_quote! {
+                    &[#innerty]
+                }
+            }
+            CppConversionType::FromUniquePtrToPtr => self.make_owning_unique_ptr_type(),
+            CppConversionType::FromUsizeToFunctionPointer => parse_quote! { usize },
+            CppConversionType::FromBoxToFunction => {
+                let closure = self.callback_closure_type();
+                parse_quote! {
+                    Box< #closure >
+                }
+            }
             _ => self.unwrapped_type.clone(),
         }
     }
 
+    /// The Rust type which holds a closure for a `std::function` parameter.
+    pub(crate) fn callback_closure_type(&self) -> Type {
+        let mut typ = match &self.unwrapped_type {
+            Type::Path(typ) => typ.clone(),
+            _ => panic!("std::function was not a path"),
+        };
+        let last_seg = typ.path.segments.last_mut().unwrap();
+        last_seg.ident = make_ident(CallbackSignature::closure_name(&last_seg.ident.to_string()));
+        Type::Path(typ)
+    }
+
+    /// If this is a `std::function` parameter which we construct from a
+    /// Rust closure, its signature.
+    pub(crate) fn callback_signature(&self) -> Option<&CallbackSignature> {
+        match &self.rust_conversion {
+            RustConversionType::FromClosureToBox(signature) => Some(signature),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a span type, which behaves as a reference
+    /// for lifetime purposes.
+    pub(crate) fn is_span(&self) -> bool {
+        matches!(
+            self.cpp_conversion,
+            CppConversionType::FromSliceToSpan
+                | CppConversionType::FromSharedSliceToSpan
+                | CppConversionType::FromSpanToSlice
+        )
+    }
+
+    /// The element type `T` of a `std::span<T>` or similar.
+    pub(crate) fn span_element_type(&self) -> &Type {
+        match &self.unwrapped_type {
+            Type::Path(typ) => match &typ.path.segments.last().unwrap().arguments {
+                PathArguments::AngleBracketed(ab) => match ab.args.first() {
+                    Some(GenericArgument::Type(ty)) => ty,
+                    _ => panic!("Span did not have a type argument"),
+                },
+                _ => panic!("Span was not templated"),
+            },
+            _ => panic!("Span was not a path"),
+        }
+    }
+
+    /// The type `T` pointed to by a `T*`.
+    pub(crate) fn pointee_type(&self) -> &Type {
+        match &self.unwrapped_type {
+            Type::Ptr(ptr) => &ptr.elem,
+            _ => panic!("Not a ptr"),
+        }
+    }
+
+    /// A `UniquePtr` which owns the object pointed to by a raw pointer.
+    fn make_owning_unique_ptr_type(&self) -> Type {
+        let innerty = self.pointee_type();
+        parse_quote! {
+            cxx::UniquePtr < #innerty >
+        }
+    }
+
     fn make_unique_ptr_type(&self) -> Type {
         let innerty = &self.unwrapped_type;
         parse_quote! {
@@ -128,6 +348,24 @@ impl TypeConversionPolicy {
         !matches!(self.rust_conversion, RustConversionType::None)
     }
 
+    /// If this is a return value which we're splitting into a Rust tuple,
+    /// the elements of that tuple.
+    pub(crate) fn tuple_elements(&self) -> Option<&[TupleElement]> {
+        match &self.rust_conversion {
+            RustConversionType::FromUniquePtrToTuple(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// If this is a status code which we're turning into a `Result`,
+    /// how to do so.
+    pub(crate) fn status_conversion(&self) -> Option<&StatusConversion> {
+        match &self.rust_conversion {
+            RustConversionType::FromStatusToResult(status) => Some(status),
+            _ => None,
+        }
+    }
+
     /// Subclass support involves calls from Rust -> C++, but
     /// also from C++ -> Rust. Work out the correct argument conversion
     /// type for the latter call, when given the former.
@@ -143,6 +381,10 @@ impl TypeConversionPolicy {
         matches!(
             self.rust_conversion,
             RustConversionType::FromValueParamToPtr
+                | RustConversionType::FromSliceToPtr
+                | RustConversionType::FromReferenceToPtr
+                | RustConversionType::FromOptionalReferenceToPtr
+                | RustConversionType::FromCStrToPtr
         )
     }
 }
@@ -155,9 +397,11 @@ pub(crate) enum CppFunctionBody {
     MakeUnique,
     ConstructSuperclass(String),
     Cast,
+    Downcast,
     Destructor(Namespace, Ident),
     AllocUninitialized(QualifiedName),
     FreeUninitialized(QualifiedName),
+    FieldAccess(Ident),
 }
 
 #[derive(Clone)]
@@ -26,21 +26,25 @@ use crate::{
         apivec::ApiVec,
         convert_error::ErrorContext,
         convert_error::{ConvertErrorWithContext, ErrorContextType},
-        error_reporter::{convert_apis, report_any_error},
+        error_reporter::{convert_apis, convert_item_apis, report_any_error},
     },
     known_types::known_types,
     types::validate_ident_ok_for_rust,
 };
 use std::collections::{HashMap, HashSet};
 
-use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
-use function_wrapper::{CppFunction, CppFunctionBody, TypeConversionPolicy};
+use autocxx_clang_facts::ClangFacts;
+use autocxx_parser::{IncludeCppConfig, StatusCheck, UnsafePolicy};
+use function_wrapper::{
+    CppFunction, CppFunctionBody, StatusConversion, SuccessCheck, TupleElement,
+    TypeConversionPolicy,
+};
 use itertools::Itertools;
 use proc_macro2::Span;
-use quote::quote;
+use quote::{quote, ToTokens};
 use syn::{
     parse_quote, punctuated::Punctuated, token::Comma, FnArg, Ident, Pat, ReturnType, Type,
-    TypePtr, Visibility,
+    TypePtr, TypeReference, Visibility,
 };
 
 use crate::{
@@ -57,8 +61,9 @@ use self::{
     implicit_constructors::{find_constructors_present, ItemsFound},
     overload_tracker::OverloadTracker,
     subclass::{
+        create_protected_access_shim, create_protected_field_accessors,
         create_subclass_constructor, create_subclass_fn_wrapper, create_subclass_function,
-        create_subclass_trait_item,
+        create_subclass_trait_item, returns_reference,
     },
 };
 
@@ -67,6 +72,7 @@ use super::{
     pod::{PodAnalysis, PodPhase},
     tdef::TypedefAnalysis,
     type_converter::Annotated,
+    vectors::create_vector_push,
 };
 
 #[derive(Clone, Debug)]
@@ -90,9 +96,11 @@ pub(crate) enum TraitMethodKind {
     CopyConstructor,
     MoveConstructor,
     Cast,
+    Downcast,
     Destructor,
     Alloc,
     Dealloc,
+    VectorPush,
 }
 
 #[derive(Clone)]
@@ -170,6 +178,12 @@ pub(crate) struct FnAnalysis {
     pub(crate) externally_callable: bool,
     /// Whether we need to generate a Rust-side calling function
     pub(crate) rust_wrapper_needed: bool,
+    /// Whether C++ exceptions thrown by this function should be returned
+    /// to Rust as an `Err`, per the `throws!` directive.
+    pub(crate) throws: bool,
+    /// The parameter from which any returned reference borrows, per the
+    /// `lifetime!` directive.
+    pub(crate) lifetime_source: Option<Ident>,
 }
 
 #[derive(Clone)]
@@ -273,11 +287,17 @@ pub(crate) struct FnAnalyzer<'a> {
     bridge_name_tracker: BridgeNameTracker,
     pod_safe_types: HashSet<QualifiedName>,
     config: &'a IncludeCppConfig,
+    /// Facts about the C++ which bindgen doesn't tell us.
+    clang_facts: &'a ClangFacts,
     overload_trackers_by_mod: HashMap<Namespace, OverloadTracker>,
     subclasses_by_superclass: HashMap<QualifiedName, Vec<SubclassName>>,
+    subclasses_by_primary_superclass: HashMap<QualifiedName, Vec<SubclassName>>,
     nested_type_name_map: HashMap<QualifiedName, String>,
     generic_types: HashSet<QualifiedName>,
     existing_superclass_trait_api_names: HashSet<QualifiedName>,
+    /// Mocks which can't be generated, and a pure virtual method which
+    /// is the reason why.
+    unmockable_subclasses: HashMap<QualifiedName, String>,
 }
 
 impl<'a> FnAnalyzer<'a> {
@@ -285,6 +305,7 @@ impl<'a> FnAnalyzer<'a> {
         apis: ApiVec<PodPhase>,
         unsafe_policy: UnsafePolicy,
         config: &'a IncludeCppConfig,
+        clang_facts: &'a ClangFacts,
     ) -> ApiVec<FnPrePhase2> {
         let mut me = Self {
             unsafe_policy,
@@ -292,13 +313,17 @@ impl<'a> FnAnalyzer<'a> {
             type_converter: TypeConverter::new(config, &apis),
             bridge_name_tracker: BridgeNameTracker::new(),
             config,
+            clang_facts,
             overload_trackers_by_mod: HashMap::new(),
             pod_safe_types: Self::build_pod_safe_type_set(&apis),
             subclasses_by_superclass: subclass::subclasses_by_superclass(&apis),
+            subclasses_by_primary_superclass: subclass::subclasses_by_primary_superclass(&apis),
             nested_type_name_map: Self::build_nested_type_map(&apis),
             generic_types: Self::build_generic_type_set(&apis),
             existing_superclass_trait_api_names: HashSet::new(),
+            unmockable_subclasses: HashMap::new(),
         };
+        let protected_field_accessors = me.protected_field_accessors(&apis);
         let mut results = ApiVec::new();
         convert_apis(
             apis,
@@ -308,12 +333,78 @@ impl<'a> FnAnalyzer<'a> {
             Api::enum_unchanged,
             Api::typedef_unchanged,
         );
+        for (name, fun) in protected_field_accessors {
+            me.analyze_synthesized_fn(&Namespace::new(), name, fun, &mut results);
+        }
+        me.add_vector_pushes(&mut results);
         let mut results = me.add_constructors_present(results);
         me.add_make_uniques(&mut results);
         results.extend(me.extra_apis.into_iter().map(add_analysis));
+        Self::ignore_unmockable_subclasses(results, &me.unmockable_subclasses)
+    }
+
+    /// A mock implements each pure virtual method by calling a closure,
+    /// which can't return a reference that lives as long as the mock, so
+    /// we can't generate mocks of classes with pure virtual methods which
+    /// return references.
+    fn ignore_unmockable_subclasses(
+        apis: ApiVec<FnPrePhase2>,
+        unmockable_subclasses: &HashMap<QualifiedName, String>,
+    ) -> ApiVec<FnPrePhase2> {
+        if unmockable_subclasses.is_empty() {
+            return apis;
+        }
+        let mut results = ApiVec::new();
+        convert_item_apis(apis, &mut results, |api| {
+            if let Api::Subclass { name, .. } = &api {
+                if let Some(method) = unmockable_subclasses.get(&name.0.name) {
+                    return Err(ConvertError::UnmockableMethod(method.clone()));
+                }
+            }
+            Ok(Box::new(std::iter::once(api)))
+        });
         results
     }
 
+    /// Protected fields can't be accessed from Rust, but a Rust subclass
+    /// may access those of its superclasses through its C++ peer. bindgen
+    /// doesn't tell us which of its non-public fields are protected, so we
+    /// ask libclang, but only about superclasses.
+    fn protected_field_accessors(
+        &self,
+        apis: &ApiVec<PodPhase>,
+    ) -> Vec<(ApiName, Box<FuncToConvert>)> {
+        let mut accessors = Vec::new();
+        for api in apis.iter() {
+            if let Api::Struct { name, details, .. } = api {
+                let subs: Vec<_> = self.subclasses_by_superclass(&name.name).collect();
+                if subs.is_empty() {
+                    continue;
+                }
+                for field in &details.item.fields {
+                    let field_name = match (&field.ident, &field.vis) {
+                        (Some(field_name), Visibility::Inherited) => field_name,
+                        _ => continue,
+                    };
+                    let is_mutable = match self.clang_facts.protected_field_is_mutable(&format!(
+                        "{}::{}",
+                        name.name.to_cpp_name(),
+                        field_name
+                    )) {
+                        Some(is_mutable) => is_mutable,
+                        None => continue,
+                    };
+                    for sub in &subs {
+                        accessors.extend(create_protected_field_accessors(
+                            sub, &name.name, field_name, &field.ty, is_mutable,
+                        ));
+                    }
+                }
+            }
+        }
+        accessors
+    }
+
     fn build_pod_safe_type_set(apis: &ApiVec<PodPhase>) -> HashSet<QualifiedName> {
         apis.iter()
             .filter_map(|api| match api {
@@ -470,6 +561,7 @@ impl<'a> FnAnalyzer<'a> {
                     FuncToConvert {
                         special_member: Some(SpecialMemberKind::Destructor),
                         is_deleted: false,
+                        is_variadic: false,
                         cpp_vis: CppVisibility::Public,
                         ..
                     }
@@ -509,7 +601,7 @@ impl<'a> FnAnalyzer<'a> {
                 // Create a make_unique too
                 self.create_make_unique(fun, initial_name, &mut results);
 
-                for sub in self.subclasses_by_superclass(sup) {
+                for sub in self.subclasses_by_primary_superclass(sup) {
                     // Create a subclass constructor. This is a synthesized function
                     // which didn't exist in the original C++.
                     let (subclass_constructor_func, subclass_constructor_name) =
@@ -546,6 +638,47 @@ impl<'a> FnAnalyzer<'a> {
             self.analyze_foreign_fn(name, &fun, TypeConversionSophistication::Regular, None);
         let mut results = ApiVec::new();
 
+        // If the C++ function has default arguments, synthesize variants
+        // which leave them out.
+        if analysis.ignore_reason.is_ok() {
+            self.create_default_arg_variants(&name, &fun, &analysis, &mut results);
+        }
+
+        // Protected methods can't be called from Rust, but a Rust subclass
+        // may call them through its C++ peer, which makes them public.
+        if let FnKind::Method {
+            impl_for: sup,
+            method_kind: MethodKind::Normal(..),
+            ..
+        } = &analysis.kind
+        {
+            if matches!(fun.cpp_vis, CppVisibility::Protected) && analysis.ignore_reason.is_ok() {
+                for sub in self.subclasses_by_superclass(sup) {
+                    let shim = create_protected_access_shim(&sub, sup, &fun);
+                    let shim_name = ApiName::new_with_cpp_name(
+                        &Namespace::new(),
+                        fun.ident.clone(),
+                        name.cpp_name_if_present().cloned(),
+                    );
+                    self.analyze_and_add(
+                        shim_name,
+                        shim,
+                        &mut results,
+                        TypeConversionSophistication::Regular,
+                    );
+                }
+            }
+        }
+
+        // A variadic function can't be called as it stands, only through
+        // the fixed-arity forms requested by variadic!, if any.
+        if fun.is_variadic
+            && matches!(fun.provenance, Provenance::Bindgen)
+            && self.create_variadic_variants(&name, &fun, &analysis, &mut results)
+        {
+            return Ok(Box::new(results.into_iter()));
+        }
+
         // Consider whether we need to synthesize subclass items.
         if let FnKind::Method {
             impl_for: sup,
@@ -582,7 +715,18 @@ impl<'a> FnAnalyzer<'a> {
                 );
                 let trait_api_name = SubclassName::get_trait_api_name(sup, &analysis.rust_name);
 
-                let mut subclass_fn_deps = vec![trait_api_name.clone()];
+                if is_pure_virtual
+                    && self.config.is_mock(&sub.id())
+                    && returns_reference(&simpler_analysis.ret_type)
+                {
+                    self.unmockable_subclasses
+                        .entry(sub.0.name.clone())
+                        .or_insert_with(|| analysis.rust_name.clone());
+                }
+
+                // Depending upon the subclass itself means we'll be ignored
+                // if it is.
+                let mut subclass_fn_deps = vec![trait_api_name.clone(), sub.0.name.clone()];
                 if !is_pure_virtual {
                     // Create a C++ API representing the superclass implementation (allowing
                     // calls from Rust->C++)
@@ -656,6 +800,129 @@ impl<'a> FnAnalyzer<'a> {
         name.name
     }
 
+    /// Take a function declared with `defaults!` and synthesize a variant
+    /// omitting each defaulted parameter onwards, e.g. for
+    /// `draw(canvas, color, width)` we add `draw_defaulting_width(canvas, color)`
+    /// and `draw_defaulting_color(canvas)`. These call through a C++ wrapper
+    /// so that the C++ compiler supplies the missing arguments.
+    fn create_default_arg_variants(
+        &mut self,
+        name: &ApiName,
+        fun: &FuncToConvert,
+        analysis: &FnAnalysis,
+        results: &mut ApiVec<FnPrePhase1>,
+    ) {
+        let first = match self
+            .config
+            .get_first_defaulted_param(&directive_name(&analysis.kind, name))
+        {
+            None => return,
+            Some(first) => first.clone(),
+        };
+        let param_names = fun
+            .inputs
+            .iter()
+            .map(|arg| match arg {
+                FnArg::Typed(pt) => match pt.pat.as_ref() {
+                    Pat::Ident(pp) => Some(pp.ident.clone()),
+                    _ => None,
+                },
+                FnArg::Receiver(_) => None,
+            })
+            .collect_vec();
+        let first_idx = match param_names.iter().position(|n| n.as_ref() == Some(&first)) {
+            None => return,
+            Some(first_idx) => first_idx,
+        };
+        for (idx, omitted) in param_names.iter().enumerate().skip(first_idx) {
+            let omitted = match omitted {
+                None => continue,
+                Some(omitted) => omitted,
+            };
+            let suffix = format!("_defaulting_{}", omitted);
+            let mut new_fun = fun.clone();
+            new_fun.inputs = fun.inputs.iter().take(idx).cloned().collect();
+            new_fun.original_name = Some(name.cpp_name());
+            new_fun.provenance = Provenance::SynthesizedDefaultArgs;
+            let variant_name = ApiName::new_with_cpp_name(
+                name.name.get_namespace(),
+                make_ident(format!("{}{}", name.name.get_final_item(), suffix)),
+                Some(name.cpp_name()),
+            );
+            let (variant_analysis, variant_name) = self.analyze_foreign_fn(
+                variant_name,
+                &new_fun,
+                TypeConversionSophistication::Regular,
+                Some(format!("{}{}", analysis.rust_name, suffix)),
+            );
+            results.push(Api::Function {
+                fun: Box::new(new_fun),
+                analysis: variant_analysis,
+                name: variant_name,
+            });
+        }
+    }
+
+    /// Take a C-style variadic function and synthesize a fixed-arity form
+    /// for each `variadic!` directive, e.g. for `log(fmt, ...)` and
+    /// `[i32, &CStr]` we add `log(fmt, vararg0, vararg1)`. These call through
+    /// a C++ wrapper, which passes the extra arguments on as `...`.
+    /// Returns whether there were any such directives.
+    fn create_variadic_variants(
+        &mut self,
+        name: &ApiName,
+        fun: &FuncToConvert,
+        analysis: &FnAnalysis,
+        results: &mut ApiVec<FnPrePhase1>,
+    ) -> bool {
+        let config = self.config;
+        let mut any_variants = false;
+        for (idx, call) in config
+            .get_variadic_calls(&directive_name(&analysis.kind, name))
+            .enumerate()
+        {
+            any_variants = true;
+            let mut new_fun = fun.clone();
+            let mut c_string_params = Vec::new();
+            for (param_idx, ty) in call.extra_params.iter().enumerate() {
+                let param = make_ident(format!("vararg{}", param_idx));
+                let ty: Type = if is_cstr_reference(ty) {
+                    c_string_params.push(param.clone());
+                    parse_quote! { *const ::std::os::raw::c_char }
+                } else {
+                    ty.clone()
+                };
+                new_fun.inputs.push(parse_quote! { #param: #ty });
+            }
+            new_fun.original_name = Some(name.cpp_name());
+            new_fun.provenance = Provenance::SynthesizedVariadic(c_string_params);
+            // The first form takes the function's own name; any others are
+            // numbered as if they were overloads.
+            let rust_name = if idx == 0 {
+                analysis.rust_name.clone()
+            } else {
+                format!("{}{}", analysis.rust_name, idx)
+            };
+            let variant_name = ApiName::new_with_cpp_name(
+                name.name.get_namespace(),
+                make_ident(format!("{}_variadic{}", name.name.get_final_item(), idx)),
+                Some(name.cpp_name()),
+            );
+            let (variant_analysis, variant_name) = self.analyze_foreign_fn(
+                variant_name,
+                &new_fun,
+                TypeConversionSophistication::Regular,
+                Some(rust_name),
+            );
+            results.push(Api::Function {
+                fun: Box::new(new_fun),
+                analysis: variant_analysis,
+                name: variant_name,
+            });
+        }
+        any_variants
+    }
+
     /// Take a constructor e.g. pub fn A_A(this: *mut root::A);
     /// and synthesize a make_unique e.g. pub fn make_unique() -> cxx::UniquePtr<A>
     fn create_make_unique(
@@ -961,7 +1228,8 @@ impl<'a> FnAnalyzer<'a> {
         } else {
             // Not a method.
             // What shall we call this function? It may be overloaded.
-            let rust_name = self.get_function_overload_name(ns, ideal_rust_name);
+            let rust_name = predetermined_rust_name
+                .unwrap_or_else(|| self.get_function_overload_name(ns, ideal_rust_name));
             (
                 FnKind::Function,
                 ErrorContext::new_for_item(make_ident(&rust_name)),
@@ -1070,7 +1338,62 @@ impl<'a> FnAnalyzer<'a> {
             _ => {}
         }
 
-        let requires_unsafe = self.should_be_unsafe(&param_details, &kind);
+        let directive_name = directive_name(&kind, &name);
+        self.apply_slice_params(&directive_name, &mut param_details)
+            .unwrap_or_else(&mut set_ignore_reason);
+        self.apply_pointer_params(&directive_name, &mut param_details)
+            .unwrap_or_else(&mut set_ignore_reason);
+        self.apply_const_spans(&directive_name, &mut param_details);
+
+        // Variants omitting default arguments, or filling in variadic
+        // arguments, are synthesized from the original function, so
+        // only it is checked against the directives.
+        if matches!(fun.provenance, Provenance::Bindgen) {
+            self.check_default_params(&directive_name, &kind, &param_details)
+                .unwrap_or_else(&mut set_ignore_reason);
+            if fun.is_variadic {
+                set_ignore_reason(ConvertError::VariadicFunction);
+            } else if self
+                .config
+                .get_variadic_calls(&directive_name)
+                .next()
+                .is_some()
+            {
+                set_ignore_reason(ConvertError::InvalidVariadicCall(
+                    "not a variadic function".into(),
+                ));
+            }
+        }
+        if let Provenance::SynthesizedVariadic(c_string_params) = &fun.provenance {
+            for pd in param_details.iter_mut().filter(
+                |pd| matches!(&pd.name, Pat::Ident(pp) if c_string_params.contains(&pp.ident)),
+            ) {
+                pd.conversion.rust_conversion = RustConversionType::FromCStrToPtr;
+                pd.requires_unsafe = UnsafetyNeeded::JustBridge;
+            }
+        }
+
+        // Only functions and methods which really exist in C++ can throw;
+        // constructors are handed to moveit, which has no way to fail.
+        let throws = matches!(
+            fun.provenance,
+            Provenance::Bindgen
+                | Provenance::SynthesizedDefaultArgs
+                | Provenance::SynthesizedVariadic(_)
+        ) && matches!(
+            kind,
+            FnKind::Function
+                | FnKind::Method {
+                    method_kind: MethodKind::Normal(_)
+                        | MethodKind::Static
+                        | MethodKind::Virtual(_)
+                        | MethodKind::PureVirtual(_),
+                    ..
+                }
+        ) && self.config.function_throws(
+            &directive_name,
+            self.config.throws_by_default() && self.clang_facts.is_noexcept(&directive_name),
+        );
 
         // Now we can add context to the error, check for a variety of error
         // cases. In each case, we continue to record the API, because it might
@@ -1192,13 +1515,71 @@ impl<'a> FnAnalyzer<'a> {
         deps.extend(return_analysis.deps.drain());
 
         let num_input_references = param_details.iter().filter(|pd| pd.was_reference).count();
-        if num_input_references != 1 && return_analysis.was_reference {
+        let lifetime_source = self.config.get_lifetime_param(&directive_name).cloned();
+        if let Some(ref lifetime_source) = lifetime_source {
+            // We've been told which parameter the returned reference borrows
+            // from, so we can give it an explicit lifetime.
+            let source_is_reference = param_details.iter().any(|pd| {
+                matches!(&pd.name, Pat::Ident(pp) if pp.ident == *lifetime_source)
+                    && pd.was_reference
+            });
+            if !return_analysis.was_reference {
+                set_ignore_reason(ConvertError::InvalidLifetimeParam(
+                    "it does not return a reference".into(),
+                ));
+            } else if !source_is_reference {
+                set_ignore_reason(ConvertError::InvalidLifetimeParam(format!(
+                    "{} is not a reference parameter",
+                    lifetime_source
+                )));
+            }
+        }
+        let lifetime_source = lifetime_source.or_else(|| {
+            // Failing that, use [[clang::lifetimebound]] if it names a
+            // reference parameter. If not, it's no use to us, but that's
+            // no reason to reject the function.
+            let clang_source = self.clang_facts.lifetime_source(&directive_name)?;
+            if !return_analysis.was_reference {
+                return None;
+            }
+            param_details.iter().find_map(|pd| match &pd.name {
+                Pat::Ident(pp) if pp.ident == clang_source && pd.was_reference => {
+                    Some(pp.ident.clone())
+                }
+                _ => None,
+            })
+        });
+        if lifetime_source.is_none() && num_input_references != 1 && return_analysis.was_reference {
             // cxx only allows functions to return a reference if they take exactly
             // one reference as a parameter. Let's see...
             set_ignore_reason(ConvertError::NotOneInputReference(rust_name.clone()));
         }
         let mut ret_type = return_analysis.rt;
-        let ret_type_conversion = return_analysis.conversion;
+        let mut ret_type_conversion = return_analysis.conversion;
+        if let (
+            FnKind::TraitMethod {
+                kind: TraitMethodKind::Downcast,
+                ..
+            },
+            Some(conversion),
+        ) = (&kind, &mut ret_type_conversion)
+        {
+            conversion.rust_conversion = RustConversionType::FromPtrToOptionalReference;
+        }
+        self.apply_ownership_transfers(
+            &directive_name,
+            &mut param_details,
+            &mut ret_type_conversion,
+        )
+        .unwrap_or_else(&mut set_ignore_reason);
+        self.apply_result_of(
+            &directive_name,
+            &mut param_details,
+            &mut ret_type_conversion,
+        )
+        .unwrap_or_else(&mut set_ignore_reason);
+
+        let requires_unsafe = self.should_be_unsafe(&param_details, &kind);
 
         // Do we need to convert either parameters or return type?
         let param_conversion_needed = param_details.iter().any(|b| b.conversion.cpp_work_needed());
@@ -1235,6 +1616,25 @@ impl<'a> FnAnalyzer<'a> {
             _ if ret_type_conversion_needed => true,
             _ if cpp_name_incompatible_with_cxx => true,
             _ if fun.synthetic_cpp.is_some() => true,
+            // Leaving out arguments, or filling in a variadic function's `...`,
+            // means cxx can't bind to the C++ function directly, since its
+            // signature doesn't match.
+            _ if matches!(
+                fun.provenance,
+                Provenance::SynthesizedDefaultArgs | Provenance::SynthesizedVariadic(_)
+            ) =>
+            {
+                true
+            }
+            // The C++ code generator only sees functions with wrappers, and
+            // it needs to see these to make the protected method public.
+            _ if matches!(
+                fun.provenance,
+                Provenance::SynthesizedProtectedAccess { .. }
+            ) =>
+            {
+                true
+            }
             _ => false,
         };
 
@@ -1350,10 +1750,17 @@ impl<'a> FnAnalyzer<'a> {
             .iter()
             .any(|pd| pd.conversion.rust_work_needed());
 
+        let ret_type_needs_rust_conversion =
+            ret_type_conversion.iter().any(|x| x.rust_work_needed());
+
         let rust_wrapper_needed = match kind {
             FnKind::TraitMethod { .. } => true,
-            FnKind::Method { .. } => any_param_needs_rust_conversion || cxxbridge_name != rust_name,
-            _ => any_param_needs_rust_conversion,
+            FnKind::Method { .. } => {
+                any_param_needs_rust_conversion
+                    || ret_type_needs_rust_conversion
+                    || cxxbridge_name != rust_name
+            }
+            _ => any_param_needs_rust_conversion || ret_type_needs_rust_conversion,
         };
 
         // Naming, part two.
@@ -1384,6 +1791,8 @@ impl<'a> FnAnalyzer<'a> {
             ignore_reason,
             externally_callable,
             rust_wrapper_needed,
+            throws,
+            lifetime_source,
         };
         let name = ApiName::new_with_cpp_name(ns, cxxbridge_name, cpp_name);
         (analysis, name)
@@ -1497,6 +1906,36 @@ impl<'a> FnAnalyzer<'a> {
                     rust_name,
                 ))
             }
+            TraitSynthesis::Downcast { to_type, mutable } => {
+                let rust_name = self.get_function_overload_name(ns, ideal_rust_name.to_string());
+                let from_type = self_ty.as_ref().unwrap();
+                let to_type = to_type.to_type_path();
+                let method_name = match mutable {
+                    CastMutability::MutToMut => "downcast_to_mut",
+                    _ => "downcast_to_ref",
+                };
+                Some((
+                    FnKind::TraitMethod {
+                        kind: TraitMethodKind::Downcast,
+                        impl_for: from_type.clone(),
+                        details: Box::new(TraitMethodDetails {
+                            trt: TraitImplSignature {
+                                ty: Type::Path(from_type.to_type_path()),
+                                trait_signature: parse_quote! {
+                                    autocxx::DowncastTo < #to_type >
+                                },
+                                unsafety: None,
+                            },
+                            avoid_self: false,
+                            method_name: make_ident(method_name),
+                            parameter_reordering: None,
+                            trait_call_is_unsafe: false,
+                        }),
+                    },
+                    ErrorContext::new_for_item(make_ident(&rust_name)),
+                    rust_name,
+                ))
+            }
             TraitSynthesis::AllocUninitialized(ty) => self.generate_alloc_or_deallocate(
                 ideal_rust_name,
                 ty,
@@ -1509,6 +1948,30 @@ impl<'a> FnAnalyzer<'a> {
                 "free_uninitialized_cpp_storage",
                 TraitMethodKind::Dealloc,
             ),
+            TraitSynthesis::VectorPush(ty) => {
+                let rust_name = self
+                    .get_function_overload_name(ty.get_namespace(), ideal_rust_name.to_string());
+                let typ = ty.to_type_path();
+                Some((
+                    FnKind::TraitMethod {
+                        impl_for: ty.clone(),
+                        details: Box::new(TraitMethodDetails {
+                            trt: TraitImplSignature {
+                                ty: Type::Path(typ),
+                                trait_signature: parse_quote! { autocxx::PushableVectorElement },
+                                unsafety: None,
+                            },
+                            avoid_self: true,
+                            method_name: make_ident("push_onto_vector"),
+                            parameter_reordering: None,
+                            trait_call_is_unsafe: false,
+                        }),
+                        kind: TraitMethodKind::VectorPush,
+                    },
+                    ErrorContext::new_for_item(make_ident(&rust_name)),
+                    rust_name,
+                ))
+            }
         })
     }
 
@@ -1555,6 +2018,16 @@ impl<'a> FnAnalyzer<'a> {
         }
     }
 
+    fn subclasses_by_primary_superclass(
+        &self,
+        sup: &QualifiedName,
+    ) -> impl Iterator<Item = SubclassName> {
+        match self.subclasses_by_primary_superclass.get(sup) {
+            Some(subs) => subs.clone().into_iter(),
+            None => Vec::new().into_iter(),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)] // currently reasonably clear
     fn convert_fn_arg(
         &mut self,
@@ -1628,6 +2101,17 @@ impl<'a> FnAnalyzer<'a> {
                 };
                 let annotated_type = self.convert_boxed_type(pt.ty, ns, treat_as_reference)?;
                 let new_ty = annotated_type.ty;
+                self.confirm_span_is_of_pod(&new_ty)?;
+                if matches!(new_ty.as_ref(), Type::BareFn(_))
+                    && matches!(
+                        sophistication,
+                        TypeConversionSophistication::SimpleForSubclasses
+                    )
+                {
+                    return Err(ConvertError::UnsupportedCallback(
+                        "function pointers can't yet be passed to subclass methods".into(),
+                    ));
+                }
                 let subclass_holder = match &annotated_type.kind {
                     type_converter::TypeKind::SubclassHolder(holder) => Some(holder),
                     _ => None,
@@ -1649,17 +2133,19 @@ impl<'a> FnAnalyzer<'a> {
                     } else {
                         UnsafetyNeeded::None
                     };
+                let was_reference = (matches!(
+                    annotated_type.kind,
+                    type_converter::TypeKind::Reference
+                        | type_converter::TypeKind::MutableReference
+                ) && conversion.callback_signature().is_none())
+                    || conversion.is_span();
                 (
                     FnArg::Typed(pt),
                     ArgumentAnalysis {
                         self_type,
                         name: new_pat,
                         conversion,
-                        was_reference: matches!(
-                            annotated_type.kind,
-                            type_converter::TypeKind::Reference
-                                | type_converter::TypeKind::MutableReference
-                        ),
+                        was_reference,
                         deps: annotated_type.types_encountered,
                         requires_unsafe,
                     },
@@ -1679,6 +2165,11 @@ impl<'a> FnAnalyzer<'a> {
     ) -> TypeConversionPolicy {
         if let Some(holder_id) = is_subclass_holder {
             let subclass = SubclassName::from_holder_name(holder_id);
+            let rust_conversion = if self.config.is_thread_safe_subclass(&subclass.id()) {
+                RustConversionType::ToBoxedUpThreadSafeHolder(subclass)
+            } else {
+                RustConversionType::ToBoxedUpHolder(subclass)
+            };
             return {
                 let ty = parse_quote! {
                     rust::Box<#holder_id>
@@ -1686,15 +2177,29 @@ impl<'a> FnAnalyzer<'a> {
                 TypeConversionPolicy {
                     unwrapped_type: ty,
                     cpp_conversion: CppConversionType::Move,
-                    rust_conversion: RustConversionType::ToBoxedUpHolder(subclass),
+                    rust_conversion,
                 }
             };
         }
+        if !matches!(
+            sophistication,
+            TypeConversionSophistication::SimpleForSubclasses
+        ) {
+            if let Some(conversion) = self.callback_conversion_details(ty) {
+                return conversion;
+            }
+        }
         match ty {
             Type::Path(p) => {
                 let ty = ty.clone();
                 let tn = QualifiedName::from_type_path(p);
-                if self.pod_safe_types.contains(&tn) {
+                if known_types().is_span(&tn) {
+                    TypeConversionPolicy {
+                        unwrapped_type: ty,
+                        cpp_conversion: CppConversionType::FromSliceToSpan,
+                        rust_conversion: RustConversionType::None,
+                    }
+                } else if self.pod_safe_types.contains(&tn) {
                     if known_types().lacks_copy_constructor(&tn) {
                         TypeConversionPolicy {
                             unwrapped_type: ty,
@@ -1745,11 +2250,410 @@ impl<'a> FnAnalyzer<'a> {
         }
     }
 
+    /// Function pointers are passed across the bridge as a `usize`, whilst
+    /// a `std::function` (by value or by const reference) is constructed
+    /// from a boxed Rust closure.
+    fn callback_conversion_details(&self, ty: &Type) -> Option<TypeConversionPolicy> {
+        let function_type = match ty {
+            Type::BareFn(_) => {
+                return Some(TypeConversionPolicy {
+                    unwrapped_type: ty.clone(),
+                    cpp_conversion: CppConversionType::FromUsizeToFunctionPointer,
+                    rust_conversion: RustConversionType::FromFunctionPointerToUsize,
+                })
+            }
+            Type::Reference(TypeReference {
+                elem,
+                mutability: None,
+                ..
+            }) => elem.as_ref(),
+            _ => ty,
+        };
+        match function_type {
+            Type::Path(p) => self
+                .type_converter
+                .get_callback_signature(&QualifiedName::from_type_path(p))
+                .map(|signature| TypeConversionPolicy {
+                    unwrapped_type: function_type.clone(),
+                    cpp_conversion: CppConversionType::FromBoxToFunction,
+                    rust_conversion: RustConversionType::FromClosureToBox(Box::new(
+                        signature.clone(),
+                    )),
+                }),
+            _ => None,
+        }
+    }
+
+    /// We can only represent spans as Rust slices if the elements are
+    /// something which Rust can hold by value.
+    fn confirm_span_is_of_pod(&self, ty: &Type) -> Result<(), ConvertError> {
+        if let Type::Path(p) = ty {
+            if known_types().is_span(&QualifiedName::from_type_path(p)) {
+                let element_type = TypeConversionPolicy::new_unconverted(ty.clone())
+                    .span_element_type()
+                    .clone();
+                return match element_type {
+                    Type::Path(ep) => {
+                        let element_tn = QualifiedName::from_type_path(&ep);
+                        if self.pod_safe_types.contains(&element_tn) {
+                            Ok(())
+                        } else {
+                            Err(ConvertError::SpanOfNonPodType(element_tn))
+                        }
+                    }
+                    _ => Err(ConvertError::UnsupportedType(
+                        ty.to_token_stream().to_string(),
+                    )),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that any `defaults!` directive for this function refers to
+    /// one of its parameters, and to a kind of function for which we can
+    /// synthesize variants omitting the defaulted arguments.
+    fn check_default_params(
+        &self,
+        directive_name: &str,
+        kind: &FnKind,
+        param_details: &[ArgumentAnalysis],
+    ) -> Result<(), ConvertError> {
+        let first = match self.config.get_first_defaulted_param(directive_name) {
+            None => return Ok(()),
+            Some(first) => first,
+        };
+        if !matches!(
+            kind,
+            FnKind::Function
+                | FnKind::Method {
+                    method_kind: MethodKind::Normal(_)
+                        | MethodKind::Static
+                        | MethodKind::Virtual(_)
+                        | MethodKind::PureVirtual(_),
+                    ..
+                }
+        ) {
+            return Err(ConvertError::InvalidDefaultParams(
+                "only functions and methods are supported".into(),
+            ));
+        }
+        if !param_details
+            .iter()
+            .any(|pd| matches!(&pd.name, Pat::Ident(pp) if pp.ident == *first))
+        {
+            return Err(ConvertError::InvalidDefaultParams(format!(
+                "no parameter called {}",
+                first
+            )));
+        }
+        Ok(())
+    }
+
+    /// Apply any `slice_param!` directives to this function, such that
+    /// a pointer and a length are presented to Rust as a single slice.
+    fn apply_slice_params(
+        &self,
+        directive_name: &str,
+        param_details: &mut [ArgumentAnalysis],
+    ) -> Result<(), ConvertError> {
+        for slice_param in self.config.get_slice_params(directive_name) {
+            let find_param = |id: &Ident| {
+                param_details
+                    .iter()
+                    .position(|pd| matches!(&pd.name, Pat::Ident(pp) if pp.ident == *id))
+                    .ok_or_else(|| {
+                        ConvertError::InvalidSliceParam(format!("no parameter called {}", id))
+                    })
+            };
+            let data_idx = find_param(&slice_param.data)?;
+            let len_idx = find_param(&slice_param.len)?;
+            if !matches!(
+                param_details[data_idx].conversion.unwrapped_type,
+                Type::Ptr(_)
+            ) {
+                return Err(ConvertError::InvalidSliceParam(format!(
+                    "{} is not a pointer",
+                    slice_param.data
+                )));
+            }
+            if !matches!(&param_details[len_idx].conversion.unwrapped_type, Type::Path(p) if p.path.is_ident("usize"))
+            {
+                return Err(ConvertError::InvalidSliceParam(format!(
+                    "{} is not a size_t",
+                    slice_param.len
+                )));
+            }
+            let data = &mut param_details[data_idx];
+            data.conversion.rust_conversion = RustConversionType::FromSliceToPtr;
+            // The cxx::bridge function still takes a raw pointer, but the
+            // wrapper is safe.
+            data.requires_unsafe = UnsafetyNeeded::JustBridge;
+            param_details[len_idx].conversion.rust_conversion =
+                RustConversionType::FromSliceToLength(slice_param.data.clone());
+        }
+        Ok(())
+    }
+
+    /// Takes spans of const elements as shared slices. bindgen doesn't
+    /// tell us about constness of template arguments, so we have to ask
+    /// libclang.
+    fn apply_const_spans(&self, directive_name: &str, param_details: &mut [ArgumentAnalysis]) {
+        for pd in param_details.iter_mut() {
+            if !matches!(
+                pd.conversion.cpp_conversion,
+                CppConversionType::FromSliceToSpan
+            ) {
+                continue;
+            }
+            if let Pat::Ident(pp) = &pd.name {
+                if self
+                    .clang_facts
+                    .first_template_arg_is_const(directive_name, &pp.ident.to_string())
+                {
+                    pd.conversion.cpp_conversion = CppConversionType::FromSharedSliceToSpan;
+                }
+            }
+        }
+    }
+
+    /// Applies any `nonnull!` or `nullable!` directives, such that the Rust
+    /// wrapper takes a reference (or an optional reference) in place of
+    /// a raw pointer. Then does the same for any other pointer parameters
+    /// which are annotated `_Nonnull` or `_Nullable` in C++.
+    fn apply_pointer_params(
+        &self,
+        directive_name: &str,
+        param_details: &mut [ArgumentAnalysis],
+    ) -> Result<(), ConvertError> {
+        for pointer_param in self.config.get_pointer_params(directive_name) {
+            let pd = param_details
+                .iter_mut()
+                .find(|pd| matches!(&pd.name, Pat::Ident(pp) if pp.ident == pointer_param.param))
+                .ok_or_else(|| {
+                    ConvertError::InvalidPointerParam(format!(
+                        "no parameter called {}",
+                        pointer_param.param
+                    ))
+                })?;
+            if !matches!(pd.conversion.unwrapped_type, Type::Ptr(_))
+                || pd.conversion.rust_work_needed()
+            {
+                return Err(ConvertError::InvalidPointerParam(format!(
+                    "{} is not a plain pointer",
+                    pointer_param.param
+                )));
+            }
+            Self::convert_pointer_param(pd, pointer_param.nullable);
+        }
+        // Annotations in the C++ are applied only where they make sense, since
+        // the user didn't explicitly ask for them.
+        for pd in param_details.iter_mut() {
+            let param = match &pd.name {
+                Pat::Ident(pp) => pp.ident.to_string(),
+                _ => continue,
+            };
+            if !matches!(pd.conversion.unwrapped_type, Type::Ptr(_))
+                || pd.conversion.rust_work_needed()
+                || pd.conversion.cpp_work_needed()
+            {
+                continue;
+            }
+            if let Some(nullable) = self.clang_facts.pointer_nullability(directive_name, &param) {
+                Self::convert_pointer_param(pd, nullable);
+            }
+        }
+        Ok(())
+    }
+
+    fn convert_pointer_param(pd: &mut ArgumentAnalysis, nullable: bool) {
+        pd.conversion.rust_conversion = if nullable {
+            RustConversionType::FromOptionalReferenceToPtr
+        } else {
+            RustConversionType::FromReferenceToPtr
+        };
+        // The cxx::bridge function still takes a raw pointer, but the
+        // wrapper is safe.
+        pd.requires_unsafe = UnsafetyNeeded::JustBridge;
+    }
+
+    /// Applies any `returns_owned!` or `takes_ownership!` directives, such
+    /// that raw pointers which transfer ownership become `UniquePtr`s.
+    fn apply_ownership_transfers(
+        &self,
+        directive_name: &str,
+        param_details: &mut [ArgumentAnalysis],
+        ret_type_conversion: &mut Option<TypeConversionPolicy>,
+    ) -> Result<(), ConvertError> {
+        if self.config.function_returns_owned(directive_name) {
+            match ret_type_conversion {
+                Some(conversion) if !conversion.cpp_work_needed() => {
+                    Self::confirm_owned_pointer(conversion, "the return type")?;
+                    conversion.cpp_conversion = CppConversionType::FromPtrToUniquePtr;
+                }
+                _ => {
+                    return Err(ConvertError::InvalidOwnershipTransfer(
+                        "it does not return a pointer".into(),
+                    ))
+                }
+            }
+        }
+        for param in self.config.get_ownership_params(directive_name) {
+            let pd = param_details
+                .iter_mut()
+                .find(|pd| matches!(&pd.name, Pat::Ident(pp) if pp.ident == *param))
+                .ok_or_else(|| {
+                    ConvertError::InvalidOwnershipTransfer(format!("no parameter called {}", param))
+                })?;
+            Self::confirm_owned_pointer(&pd.conversion, &param.to_string())?;
+            pd.conversion.cpp_conversion = CppConversionType::FromUniquePtrToPtr;
+            // A UniquePtr is entirely safe to pass.
+            pd.requires_unsafe = UnsafetyNeeded::None;
+        }
+        Ok(())
+    }
+
+    /// Checks that a type is a non-const pointer to a type which can
+    /// live in a `UniquePtr`, with no other conversion already applied.
+    fn confirm_owned_pointer(
+        conversion: &TypeConversionPolicy,
+        desc: &str,
+    ) -> Result<(), ConvertError> {
+        let ok = !conversion.cpp_work_needed()
+            && !conversion.rust_work_needed()
+            && match &conversion.unwrapped_type {
+                Type::Ptr(TypePtr {
+                    mutability: Some(_),
+                    elem,
+                    ..
+                }) => match elem.as_ref() {
+                    Type::Path(typ) => {
+                        !known_types().is_known_type(&QualifiedName::from_type_path(typ))
+                    }
+                    _ => false,
+                },
+                _ => false,
+            };
+        if ok {
+            Ok(())
+        } else {
+            Err(ConvertError::InvalidOwnershipTransfer(format!(
+                "{} is not a non-const pointer to a class",
+                desc
+            )))
+        }
+    }
+
+    /// Applies any `result_of!` directive, such that the Rust wrapper
+    /// returns a `Result` rather than a status code, and returns any
+    /// out-parameter within that `Result` instead of taking it as a parameter.
+    fn apply_result_of(
+        &self,
+        directive_name: &str,
+        param_details: &mut [ArgumentAnalysis],
+        ret_type_conversion: &mut Option<TypeConversionPolicy>,
+    ) -> Result<(), ConvertError> {
+        let result_of = match self.config.get_result_of(directive_name) {
+            Some(result_of) => result_of,
+            None => return Ok(()),
+        };
+        let status = match ret_type_conversion {
+            None => {
+                return Err(ConvertError::InvalidResultOf(
+                    "it does not return a status code".into(),
+                ))
+            }
+            Some(status) if status.rust_work_needed() => {
+                return Err(ConvertError::InvalidResultOf(
+                    "the status code is already converted in some other way".into(),
+                ))
+            }
+            Some(status) => status,
+        };
+        // Non-POD status types are returned to Rust within a UniquePtr,
+        // so can only be inspected by C++.
+        let status_is_non_pod = matches!(
+            status.cpp_conversion,
+            CppConversionType::FromValueToUniquePtr
+        );
+        let check = match &result_of.check {
+            StatusCheck::OkWhen(_) if status.cpp_work_needed() => {
+                return Err(ConvertError::InvalidResultOf(
+                    "ok_when needs a primitive, enum or POD status code; use ok_if for others"
+                        .into(),
+                ))
+            }
+            StatusCheck::OkIf(_) if !status_is_non_pod => {
+                return Err(ConvertError::InvalidResultOf(
+                    "ok_if needs a non-POD status type; use ok_when for others".into(),
+                ))
+            }
+            StatusCheck::OkWhen(ok_when) => {
+                SuccessCheck::OkWhen(Box::new(syn::parse_str(ok_when).map_err(|_| {
+                    ConvertError::InvalidResultOf("ok_when is not an expression".into())
+                })?))
+            }
+            StatusCheck::OkIf(ok_if) => SuccessCheck::OkIf(ok_if.clone()),
+        };
+        let out = match &result_of.out {
+            None => None,
+            Some(out) => {
+                let pd = param_details
+                    .iter_mut()
+                    .find(|pd| matches!(&pd.name, Pat::Ident(pp) if pp.ident == *out))
+                    .ok_or_else(|| {
+                        ConvertError::InvalidResultOf(format!("no parameter called {}", out))
+                    })?;
+                let pointee = match &pd.conversion.unwrapped_type {
+                    Type::Ptr(TypePtr {
+                        mutability: Some(_),
+                        elem,
+                        ..
+                    }) => elem.as_ref().clone(),
+                    _ => {
+                        return Err(ConvertError::InvalidResultOf(format!(
+                            "{} is not a non-const pointer",
+                            out
+                        )))
+                    }
+                };
+                if !matches!(&pointee, Type::Path(typ) if self.pod_safe_types.contains(&QualifiedName::from_type_path(typ)))
+                {
+                    return Err(ConvertError::InvalidResultOf(format!(
+                        "{} does not point to a primitive, enum or POD type",
+                        out
+                    )));
+                }
+                pd.conversion.rust_conversion = RustConversionType::FromOutParamToPtr;
+                // We trust C++ to have written the out-parameter whenever it
+                // reports success, and we can't check that, so the wrapper
+                // stays unsafe.
+                pd.requires_unsafe = UnsafetyNeeded::Always;
+                Some((out.clone(), pointee))
+            }
+        };
+        status.rust_conversion =
+            RustConversionType::FromStatusToResult(Box::new(StatusConversion { check, out }));
+        Ok(())
+    }
+
     fn return_type_conversion_details(&self, ty: &Type) -> TypeConversionPolicy {
         match ty {
             Type::Path(p) => {
                 let tn = QualifiedName::from_type_path(p);
-                if self.pod_safe_types.contains(&tn) {
+                if known_types().is_span(&tn) {
+                    TypeConversionPolicy {
+                        unwrapped_type: ty.clone(),
+                        cpp_conversion: CppConversionType::FromSpanToSlice,
+                        rust_conversion: RustConversionType::None,
+                    }
+                } else if let Some(elements) = self.get_tuple_elements(&tn) {
+                    TypeConversionPolicy {
+                        unwrapped_type: ty.clone(),
+                        cpp_conversion: CppConversionType::FromValueToUniquePtr,
+                        rust_conversion: RustConversionType::FromUniquePtrToTuple(elements),
+                    }
+                } else if self.pod_safe_types.contains(&tn) {
                     TypeConversionPolicy::new_unconverted(ty.clone())
                 } else {
                     TypeConversionPolicy::new_to_unique_ptr(ty.clone())
@@ -1759,6 +2663,25 @@ impl<'a> FnAnalyzer<'a> {
         }
     }
 
+    /// If this is a `std::pair` whose elements we can return individually,
+    /// work out how to return each of them: POD types by value, and
+    /// anything else within a `UniquePtr`.
+    fn get_tuple_elements(&self, tn: &QualifiedName) -> Option<Vec<TupleElement>> {
+        self.type_converter
+            .get_tuple_elements(tn)?
+            .iter()
+            .map(|ty| match ty {
+                Type::Path(typ) => Some(TupleElement {
+                    ty: ty.clone(),
+                    by_value: self
+                        .pod_safe_types
+                        .contains(&QualifiedName::from_type_path(typ)),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
     fn convert_return_type(
         &mut self,
         rt: &ReturnType,
@@ -1777,8 +2700,15 @@ impl<'a> FnAnalyzer<'a> {
                 let annotated_type =
                     self.convert_boxed_type(boxed_type.clone(), ns, references.ref_return)?;
                 let boxed_type = annotated_type.ty;
-                let was_reference = matches!(boxed_type.as_ref(), Type::Reference(_));
+                self.confirm_span_is_of_pod(&boxed_type)?;
+                if matches!(boxed_type.as_ref(), Type::BareFn(_)) {
+                    return Err(ConvertError::UnsupportedCallback(
+                        "function pointers can't yet be returned".into(),
+                    ));
+                }
                 let conversion = self.return_type_conversion_details(boxed_type.as_ref());
+                let was_reference =
+                    matches!(boxed_type.as_ref(), Type::Reference(_)) || conversion.is_span();
                 ReturnTypeAnalysis {
                     rt: ReturnType::Type(*rarrow, boxed_type),
                     conversion: Some(conversion),
@@ -1893,6 +2823,59 @@ impl<'a> FnAnalyzer<'a> {
         results
     }
 
+    /// Adds functions to push non-POD types onto `std::vector`s, for those
+    /// types which we've seen in vectors in the APIs analyzed so far.
+    fn add_vector_pushes(&mut self, apis: &mut ApiVec<FnPrePhase1>) {
+        let non_pod_types: HashSet<_> = apis
+            .iter()
+            .filter_map(|api| match api {
+                Api::Struct {
+                    name,
+                    analysis:
+                        PodAnalysis {
+                            kind: TypeKind::NonPod,
+                            is_generic: false,
+                            ..
+                        },
+                    ..
+                } => Some(&name.name),
+                _ => None,
+            })
+            .collect();
+        let mut element_types: Vec<_> = self
+            .type_converter
+            .vector_element_types()
+            .filter(|ty| non_pod_types.contains(ty))
+            .cloned()
+            .collect();
+        element_types.sort_by_key(|ty| ty.to_cpp_name());
+        for ty in element_types {
+            let ns = ty.get_namespace().clone();
+            let (name, fun) = create_vector_push(ty);
+            self.analyze_synthesized_fn(&ns, name, fun, apis);
+        }
+    }
+
+    /// Analyzes a function which we've made up, rather than found in
+    /// the bindgen output, adding it (or any error) to `apis`.
+    fn analyze_synthesized_fn(
+        &mut self,
+        ns: &Namespace,
+        name: ApiName,
+        fun: Box<FuncToConvert>,
+        apis: &mut ApiVec<FnPrePhase1>,
+    ) {
+        let mut any_errors = ApiVec::new();
+        apis.extend(
+            report_any_error(ns, &mut any_errors, || {
+                self.analyze_foreign_fn_and_subclasses(name, fun)
+            })
+            .into_iter()
+            .flatten(),
+        );
+        apis.append(&mut any_errors);
+    }
+
     #[allow(clippy::too_many_arguments)] // it's true, but sticking with it for now
     fn synthesize_special_member(
         &mut self,
@@ -1926,36 +2909,51 @@ impl<'a> FnAnalyzer<'a> {
             ApiName::new_with_cpp_name(self_ty.name.get_namespace(), ident.clone(), cpp_name);
         let self_ty = &self_ty.name;
         let ns = self_ty.get_namespace().clone();
-        let mut any_errors = ApiVec::new();
-        apis.extend(
-            report_any_error(&ns, &mut any_errors, || {
-                self.analyze_foreign_fn_and_subclasses(
-                    fake_api_name,
-                    Box::new(FuncToConvert {
-                        self_ty: Some(self_ty.clone()),
-                        ident,
-                        doc_attrs: make_doc_attrs(format!("Synthesized {}.", special_member)),
-                        inputs,
-                        output: ReturnType::Default,
-                        vis: parse_quote! { pub },
-                        virtualness: Virtualness::None,
-                        cpp_vis: CppVisibility::Public,
-                        special_member: Some(special_member),
-                        unused_template_param: false,
-                        references,
-                        original_name: None,
-                        synthesized_this_type: None,
-                        is_deleted: false,
-                        add_to_trait: None,
-                        synthetic_cpp: None,
-                        provenance: Provenance::SynthesizedOther,
-                    }),
-                )
-            })
-            .into_iter()
-            .flatten(),
-        );
-        apis.append(&mut any_errors);
+        let fun = Box::new(FuncToConvert {
+            self_ty: Some(self_ty.clone()),
+            ident,
+            doc_attrs: make_doc_attrs(format!("Synthesized {}.", special_member)),
+            inputs,
+            output: ReturnType::Default,
+            vis: parse_quote! { pub },
+            virtualness: Virtualness::None,
+            cpp_vis: CppVisibility::Public,
+            special_member: Some(special_member),
+            unused_template_param: false,
+            references,
+            original_name: None,
+            synthesized_this_type: None,
+            is_deleted: false,
+            is_variadic: false,
+            add_to_trait: None,
+            synthetic_cpp: None,
+            provenance: Provenance::SynthesizedOther,
+        });
+        self.analyze_synthesized_fn(&ns, fake_api_name, fun, apis);
+    }
+}
+
+/// Whether this is a `&CStr`, which `variadic!` allows for `const char*`
+/// arguments.
+fn is_cstr_reference(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(TypeReference {
+            mutability: None,
+            elem,
+            ..
+        }) => matches!(elem.as_ref(), Type::Path(typ)
+            if typ.path.segments.last().map(|seg| seg.ident == "CStr").unwrap_or_default()),
+        _ => false,
+    }
+}
+
+/// The name by which directives such as `slice_param!` refer to this function.
+fn directive_name(kind: &FnKind, name: &ApiName) -> String {
+    match kind {
+        FnKind::Method { impl_for, .. } => {
+            format!("{}::{}", impl_for.to_cpp_name(), name.cpp_name())
+        }
+        _ => name.qualified_cpp_name(),
     }
 }
 
@@ -1966,9 +2964,23 @@ fn error_context_for_method(self_ty: &QualifiedName, rust_name: &str) -> ErrorCo
 impl Api<FnPhase> {
     pub(crate) fn name_for_allowlist(&self) -> QualifiedName {
         match &self {
-            Api::Function { analysis, .. } => match analysis.kind {
+            Api::Function { fun, analysis, .. } => match analysis.kind {
                 FnKind::Method { ref impl_for, .. } => impl_for.clone(),
                 FnKind::TraitMethod { ref impl_for, .. } => impl_for.clone(),
+                // Variants omitting default arguments, or filling in variadic
+                // arguments, are allowlisted along with the C++ function from
+                // which they're made.
+                FnKind::Function
+                    if matches!(
+                        fun.provenance,
+                        Provenance::SynthesizedDefaultArgs | Provenance::SynthesizedVariadic(_)
+                    ) =>
+                {
+                    QualifiedName::new(
+                        self.name().get_namespace(),
+                        make_ident(fun.original_name.as_ref().unwrap()),
+                    )
+                }
                 FnKind::Function => {
                     QualifiedName::new(self.name().get_namespace(), make_ident(&analysis.rust_name))
                 }
@@ -2011,6 +3023,7 @@ impl Api<FnPhase> {
                 | Api::ConcreteType { .. }
                 | Api::CType { .. }
                 | Api::RustSubclassFn { .. }
+                | Api::RustTrait { .. }
                 | Api::Subclass { .. }
                 | Api::Struct {
                     analysis: PodAndDepAnalysis {
@@ -8,13 +8,14 @@
 
 use std::collections::HashMap;
 
-use syn::{parse_quote, FnArg, PatType, Type, TypePtr};
+use proc_macro2::Ident;
+use syn::{parse_quote, FnArg, GenericArgument, PatType, PathArguments, ReturnType, Type, TypePtr};
 
 use crate::conversion::analysis::fun::{FnKind, MethodKind, ReceiverMutability};
 use crate::conversion::analysis::pod::PodPhase;
 use crate::conversion::api::{
-    CppVisibility, FuncToConvert, Provenance, RustSubclassFnDetails, SubclassConstructorDetails,
-    SubclassName, SuperclassMethod, UnsafetyNeeded, Virtualness,
+    CppVisibility, FuncToConvert, Provenance, References, RustSubclassFnDetails,
+    SubclassConstructorDetails, SubclassName, SuperclassMethod, UnsafetyNeeded, Virtualness,
 };
 use crate::conversion::apivec::ApiVec;
 use crate::{
@@ -35,16 +36,62 @@ pub(super) fn subclasses_by_superclass(
     let mut subclasses_per_superclass: HashMap<QualifiedName, Vec<SubclassName>> = HashMap::new();
 
     for api in apis.iter() {
-        if let Api::Subclass { name, superclass } = api {
-            subclasses_per_superclass
-                .entry(superclass.clone())
-                .or_default()
-                .push(name.clone());
+        if let Api::Subclass { name, superclasses } = api {
+            for superclass in superclasses {
+                subclasses_per_superclass
+                    .entry(superclass.clone())
+                    .or_default()
+                    .push(name.clone());
+            }
         }
     }
     subclasses_per_superclass
 }
 
+/// As [`subclasses_by_superclass`] but only considering the first
+/// superclass of each subclass, whose constructors the subclass offers.
+/// Any other superclasses are default-constructed.
+pub(super) fn subclasses_by_primary_superclass(
+    apis: &ApiVec<PodPhase>,
+) -> HashMap<QualifiedName, Vec<SubclassName>> {
+    let mut subclasses_per_superclass: HashMap<QualifiedName, Vec<SubclassName>> = HashMap::new();
+
+    for api in apis.iter() {
+        if let Api::Subclass { name, superclasses } = api {
+            if let Some(superclass) = superclasses.first() {
+                subclasses_per_superclass
+                    .entry(superclass.clone())
+                    .or_default()
+                    .push(name.clone());
+            }
+        }
+    }
+    subclasses_per_superclass
+}
+
+/// Whether a method returns a reference, which we'll have represented as
+/// either `&T` or `Pin<&mut T>`.
+pub(super) fn returns_reference(ret: &ReturnType) -> bool {
+    let ty = match ret {
+        ReturnType::Default => return false,
+        ReturnType::Type(_, ty) => ty.as_ref(),
+    };
+    match ty {
+        Type::Reference(_) => true,
+        Type::Path(typ) => match typ.path.segments.last() {
+            Some(seg) if seg.ident == "Pin" => match &seg.arguments {
+                PathArguments::AngleBracketed(args) => matches!(
+                    args.args.first(),
+                    Some(GenericArgument::Type(Type::Reference(_)))
+                ),
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
 pub(super) fn create_subclass_fn_wrapper(
     sub: &SubclassName,
     super_fn_name: &QualifiedName,
@@ -67,11 +114,90 @@ pub(super) fn create_subclass_fn_wrapper(
         references: fun.references.clone(),
         add_to_trait: fun.add_to_trait.clone(),
         is_deleted: fun.is_deleted,
+        is_variadic: false,
         synthetic_cpp: None,
         provenance: Provenance::SynthesizedOther,
     })
 }
 
+/// Creates a method on the subclass's C++ peer which forwards to a
+/// protected, non-virtual method of the superclass. This is how a Rust
+/// subclass calls such methods, which aren't otherwise accessible.
+pub(super) fn create_protected_access_shim(
+    sub: &SubclassName,
+    superclass: &QualifiedName,
+    fun: &FuncToConvert,
+) -> Box<FuncToConvert> {
+    let mut shim = create_subclass_fn_wrapper(
+        sub,
+        &QualifiedName::new(&Namespace::new(), fun.ident.clone()),
+        fun,
+    );
+    shim.provenance = Provenance::SynthesizedProtectedAccess {
+        subclass: sub.clone(),
+        superclass: superclass.clone(),
+    };
+    shim
+}
+
+/// Creates methods on the subclass's C++ peer which return references to
+/// a protected field of the superclass: `field` and, unless the field is
+/// `const`, `field_mut`.
+pub(super) fn create_protected_field_accessors(
+    sub: &SubclassName,
+    superclass: &QualifiedName,
+    field: &Ident,
+    ty: &Type,
+    is_mutable: bool,
+) -> Vec<(ApiName, Box<FuncToConvert>)> {
+    let super_path = superclass.to_type_path();
+    let mut accessors = vec![(
+        field.clone(),
+        parse_quote! { this: *const #super_path },
+        parse_quote! { -> *const #ty },
+    )];
+    if is_mutable {
+        accessors.push((
+            make_ident(format!("{}_mut", field)),
+            parse_quote! { this: *mut #super_path },
+            parse_quote! { -> *mut #ty },
+        ));
+    }
+    accessors
+        .into_iter()
+        .map(|(ident, this, output): (Ident, FnArg, ReturnType)| {
+            let self_ty = Some(sub.cpp());
+            let fun = Box::new(FuncToConvert {
+                synthesized_this_type: self_ty.clone(),
+                self_ty,
+                ident: ident.clone(),
+                doc_attrs: Vec::new(),
+                inputs: [this].into_iter().collect(),
+                output,
+                vis: parse_quote! { pub },
+                virtualness: Virtualness::None,
+                cpp_vis: CppVisibility::Public,
+                special_member: None,
+                unused_template_param: false,
+                original_name: None,
+                references: References::new_with_this_and_return_as_reference(),
+                add_to_trait: None,
+                is_deleted: false,
+                is_variadic: false,
+                synthetic_cpp: Some((
+                    CppFunctionBody::FieldAccess(field.clone()),
+                    CppFunctionKind::Method,
+                )),
+                provenance: Provenance::SynthesizedProtectedAccess {
+                    subclass: sub.clone(),
+                    superclass: superclass.clone(),
+                },
+            });
+            (ApiName::new(&Namespace::new(), ident), fun)
+        })
+        .collect()
+}
+
 pub(super) fn create_subclass_trait_item(
     name: ApiName,
     analysis: &FnAnalysis,
@@ -235,6 +361,7 @@ pub(super) fn create_subclass_constructor(
         self_ty: Some(cpp),
         add_to_trait: None,
         is_deleted: fun.is_deleted,
+        is_variadic: false,
         synthetic_cpp: None,
         provenance: Provenance::SynthesizedSubclassConstructor(subclass_constructor_details),
     });
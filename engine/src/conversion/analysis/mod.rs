@@ -21,5 +21,6 @@ pub(crate) mod pod; // hey, that rhymes
 pub(crate) mod remove_ignored;
 pub(crate) mod tdef;
 mod type_converter;
+pub(crate) mod vectors;
 
 pub(crate) use name_check::check_names;
@@ -50,10 +50,12 @@ pub(crate) fn check_names(apis: ApiVec<FnPhase>) -> ApiVec<FnPhase> {
         }
         Api::Subclass {
             name: SubclassName(ref name),
-            ref superclass,
+            ref superclasses,
         } => {
             validate_all_segments_ok_for_cxx(name.name.segment_iter())?;
-            validate_all_segments_ok_for_cxx(superclass.segment_iter())?;
+            for superclass in superclasses {
+                validate_all_segments_ok_for_cxx(superclass.segment_iter())?;
+            }
             Ok(Box::new(std::iter::once(api)))
         }
         Api::Function { ref name, .. } => {
@@ -70,6 +72,7 @@ pub(crate) fn check_names(apis: ApiVec<FnPhase>) -> ApiVec<FnPhase> {
         | Api::RustType { .. }
         | Api::RustSubclassFn { .. }
         | Api::RustFn { .. }
+        | Api::RustTrait { .. }
         | Api::SubclassTraitItem { .. }
         | Api::IgnoredItem { .. } => Ok(Box::new(std::iter::once(api))),
     });
@@ -10,6 +10,7 @@ mod byvalue_checker;
 
 use std::collections::{HashMap, HashSet};
 
+use autocxx_clang_facts::ClangFacts;
 use autocxx_parser::IncludeCppConfig;
 use byvalue_checker::ByValueChecker;
 use syn::{ItemEnum, ItemStruct, Type, Visibility};
@@ -37,6 +38,8 @@ pub(crate) struct FieldInfo {
 pub(crate) struct PodAnalysis {
     pub(crate) kind: TypeKind,
     pub(crate) bases: HashSet<QualifiedName>,
+    /// Those of `bases` which are virtual.
+    pub(crate) virtual_bases: HashSet<QualifiedName>,
     /// Base classes for which we should create casts.
     /// That's just those which are on the allowlist,
     /// because otherwise we don't know whether they're
@@ -63,6 +66,7 @@ impl AnalysisPhase for PodPhase {
 pub(crate) fn analyze_pod_apis(
     apis: ApiVec<TypedefPhase>,
     config: &IncludeCppConfig,
+    clang_facts: &ClangFacts,
 ) -> Result<ApiVec<PodPhase>, ConvertError> {
     // This next line will return an error if any of the 'generate_pod'
     // directives from the user can't be met because, for instance,
@@ -84,6 +88,7 @@ pub(crate) fn analyze_pod_apis(
                 name,
                 details,
                 config,
+                clang_facts,
             )
         },
         analyze_enum,
@@ -105,6 +110,7 @@ pub(crate) fn analyze_pod_apis(
                 name,
                 details,
                 config,
+                clang_facts,
             )
         },
         analyze_enum,
@@ -130,6 +136,7 @@ fn analyze_struct(
     name: ApiName,
     mut details: Box<StructDetails>,
     config: &IncludeCppConfig,
+    clang_facts: &ClangFacts,
 ) -> Result<Box<dyn Iterator<Item = Api<PodPhase>>>, ConvertErrorWithContext> {
     let id = name.name.get_final_ident();
     if details.vis != CppVisibility::Public {
@@ -140,7 +147,17 @@ fn analyze_struct(
     }
     let metadata = BindgenSemanticAttributes::new_retaining_others(&mut details.item.attrs);
     metadata.check_for_fatal_attrs(&id)?;
-    let bases = get_bases(&details.item);
+    let mut bases = get_bases(&details.item);
+    let mut virtual_bases = HashSet::new();
+    // bindgen leaves out virtual bases, though it gives the class its
+    // own vtable, so only then do we need to ask libclang.
+    if has_own_vtable(&details.item) {
+        for (base, is_public) in clang_facts.virtual_bases(&name.qualified_cpp_name()) {
+            let base = QualifiedName::new_from_cpp_name(base);
+            bases.insert(base.clone(), is_public);
+            virtual_bases.insert(base);
+        }
+    }
     let mut field_deps = HashSet::new();
     let mut field_info = Vec::new();
     let field_conversion_errors = get_struct_field_types(
@@ -184,6 +201,7 @@ fn analyze_struct(
         analysis: PodAnalysis {
             kind: type_kind,
             bases: bases.into_keys().collect(),
+            virtual_bases,
             castable_bases,
             field_deps,
             field_info,
@@ -228,6 +246,12 @@ fn get_struct_field_types(
     convert_errors
 }
 
+pub(crate) fn has_own_vtable(item: &ItemStruct) -> bool {
+    item.fields
+        .iter()
+        .any(|f| f.ident.as_ref().map(|id| id == "vtable_").unwrap_or(false))
+}
+
 /// Map to whether the bases are public.
 fn get_bases(item: &ItemStruct) -> HashMap<QualifiedName, bool> {
     item.fields
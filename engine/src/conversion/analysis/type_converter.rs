@@ -13,7 +13,7 @@ use crate::{
         codegen_cpp::type_to_cpp::type_to_cpp,
         ConvertError,
     },
-    known_types::{known_types, CxxGenericType},
+    known_types::{known_types, CallbackSignature, CxxGenericType},
     types::{make_ident, Namespace, QualifiedName},
 };
 use autocxx_parser::IncludeCppConfig;
@@ -22,8 +22,8 @@ use proc_macro2::Ident;
 use quote::ToTokens;
 use std::collections::{HashMap, HashSet};
 use syn::{
-    parse_quote, punctuated::Punctuated, GenericArgument, PathArguments, PathSegment, Type,
-    TypePath, TypePtr,
+    parse_quote, punctuated::Punctuated, GenericArgument, PathArguments, PathSegment, ReturnType,
+    Type, TypeBareFn, TypePath, TypePtr,
 };
 
 use super::tdef::TypedefAnalysis;
@@ -79,9 +79,16 @@ impl<T> Annotated<T> {
 /// a type will only ever be used in a bindgen context,
 /// we could be more liberal. At the moment though, all outputs
 /// from [TypeConverter] _might_ be used in the [cxx::bridge].
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum TypeConversionContext {
     CxxInnerType,
-    CxxOuterType { convert_ptrs_to_references: bool },
+    CxxOuterType {
+        convert_ptrs_to_references: bool,
+    },
+    /// The target of a typedef. This is treated like an inner type,
+    /// except that function pointers are retained as-is so that
+    /// they can later be resolved as parameters.
+    CxxTypedefTarget,
 }
 
 impl TypeConversionContext {
@@ -95,7 +102,10 @@ impl TypeConversionContext {
         )
     }
     fn allow_instantiation_of_forward_declaration(&self) -> bool {
-        matches!(self, TypeConversionContext::CxxInnerType)
+        matches!(
+            self,
+            TypeConversionContext::CxxInnerType | TypeConversionContext::CxxTypedefTarget
+        )
     }
 }
 
@@ -111,6 +121,9 @@ pub(crate) struct TypeConverter<'a> {
     types_found: HashSet<QualifiedName>,
     typedefs: HashMap<QualifiedName, Type>,
     concrete_templates: HashMap<String, QualifiedName>,
+    tuple_elements: HashMap<QualifiedName, Vec<Type>>,
+    vector_elements: HashSet<QualifiedName>,
+    callback_signatures: HashMap<QualifiedName, CallbackSignature>,
     forward_declarations: HashSet<QualifiedName>,
     config: &'a IncludeCppConfig,
 }
@@ -123,7 +136,10 @@ impl<'a> TypeConverter<'a> {
         Self {
             types_found: find_types(apis),
             typedefs: Self::find_typedefs(apis),
-            concrete_templates: Self::find_concrete_templates(apis),
+            concrete_templates: Self::find_concrete_templates(config, apis),
+            tuple_elements: HashMap::new(),
+            vector_elements: HashSet::new(),
+            callback_signatures: Self::find_callback_signatures(apis),
             forward_declarations: Self::find_incomplete_types(apis),
             config,
         }
@@ -211,6 +227,10 @@ impl<'a> TypeConverter<'a> {
         ns: &Namespace,
         ctx: &TypeConversionContext,
     ) -> Result<Annotated<Type>, ConvertError> {
+        if let Some(bare_fn) = Self::as_function_pointer(&typ) {
+            let bare_fn = bare_fn.clone();
+            return self.convert_function_pointer(typ, bare_fn, HashSet::new(), ns, ctx);
+        }
         // First, qualify any unqualified paths.
         if typ.path.segments.iter().next().unwrap().ident != "root" {
             let ty = QualifiedName::from_type_path(&typ);
@@ -246,16 +266,20 @@ impl<'a> TypeConverter<'a> {
         // Now convert this type itself.
         deps.insert(original_tn.clone());
         // First let's see if this is a typedef.
-        let (typ, tn) = match self.resolve_typedef(&original_tn)? {
+        let (typ, tn) = match self.resolve_typedef(&original_tn)?.cloned() {
             None => (typ, original_tn),
             Some(Type::Path(resolved_tp)) => {
-                let resolved_tn = QualifiedName::from_type_path(resolved_tp);
+                if let Some(bare_fn) = Self::as_function_pointer(&resolved_tp) {
+                    let bare_fn = bare_fn.clone();
+                    return self.convert_function_pointer(resolved_tp, bare_fn, deps, ns, ctx);
+                }
+                let resolved_tn = QualifiedName::from_type_path(&resolved_tp);
                 deps.insert(resolved_tn.clone());
-                (resolved_tp.clone(), resolved_tn)
+                (resolved_tp, resolved_tn)
             }
             Some(Type::Ptr(resolved_tp)) => {
                 return Ok(Annotated::new(
-                    Type::Ptr(resolved_tp.clone()),
+                    Type::Ptr(resolved_tp),
                     deps,
                     ApiVec::new(),
                     TypeKind::Pointer,
@@ -263,7 +287,7 @@ impl<'a> TypeConverter<'a> {
             }
             Some(other) => {
                 return Ok(Annotated::new(
-                    other.clone(),
+                    other,
                     deps,
                     ApiVec::new(),
                     TypeKind::Regular,
@@ -307,21 +331,201 @@ impl<'a> TypeConverter<'a> {
                     let mut innerty = self.convert_punctuated(ab.args.clone(), ns)?;
                     ab.args = innerty.ty;
                     deps.extend(innerty.types_encountered.drain());
+                    if known_types().is_vector(&tn) {
+                        if let Some(GenericArgument::Type(Type::Path(element))) = ab.args.first() {
+                            self.vector_elements
+                                .insert(QualifiedName::from_type_path(element));
+                        }
+                    }
                 }
             } else {
                 // Oh poop. It's a generic type which cxx won't be able to handle.
                 // We'll have to come up with a concrete type in both the cxx::bridge (in Rust)
                 // and a corresponding typedef in C++.
-                let (new_tn, api) = self.get_templated_typename(&Type::Path(typ))?;
+                let is_tuple = known_types().is_tuple(&tn);
+                let mut tuple_elements = None;
+                if known_types().is_function(&tn) {
+                    // bindgen gives us the signature as a function pointer,
+                    // which we'll use to generate an adapter from a Rust closure.
+                    if let PathArguments::AngleBracketed(ref mut ab) = last_seg.arguments {
+                        for arg in ab.args.iter_mut() {
+                            if let GenericArgument::Type(ty) = arg {
+                                let mut signature = self.convert_callback_signature(ty, ns)?;
+                                deps.extend(signature.types_encountered.drain());
+                                extra_apis.append(&mut signature.extra_apis);
+                                *ty = Type::BareFn(signature.ty);
+                            }
+                        }
+                    }
+                } else if known_types().is_associative_container(&tn) || is_tuple {
+                    // We'll generate shims to get at the contents of these, so we need
+                    // to know the cxx names of the key and value types.
+                    if let PathArguments::AngleBracketed(ref mut ab) = last_seg.arguments {
+                        if known_types().is_associative_container(&tn) {
+                            // bindgen may give us defaulted comparator, hasher or
+                            // allocator arguments, which we neither need nor can
+                            // necessarily convert.
+                            ab.args = known_types()
+                                .associative_container_args(&tn, &ab.args)
+                                .ok_or_else(|| {
+                                    ConvertError::UnsupportedType(format!(
+                                        "{} with a custom comparator, hasher or allocator",
+                                        tn.to_cpp_name()
+                                    ))
+                                })?;
+                        }
+                        let mut innerty = self.convert_punctuated(ab.args.clone(), ns)?;
+                        ab.args = innerty.ty;
+                        deps.extend(innerty.types_encountered.drain());
+                        if is_tuple {
+                            tuple_elements = Some(
+                                ab.args
+                                    .iter()
+                                    .filter_map(|arg| match arg {
+                                        GenericArgument::Type(ty) => Some(ty.clone()),
+                                        _ => None,
+                                    })
+                                    .collect::<Vec<_>>(),
+                            );
+                        }
+                    }
+                }
+                let rs_definition = Type::Path(typ);
+                let (new_tn, api) = self.get_templated_typename(&rs_definition)?;
                 extra_apis.extend(api.into_iter());
+                if let Some(signature) = known_types().callback_signature(&rs_definition) {
+                    self.callback_signatures.insert(new_tn.clone(), signature);
+                }
                 deps.remove(&tn);
                 typ = new_tn.to_type_path();
+                if let Some(tuple_elements) = tuple_elements {
+                    self.tuple_elements.insert(new_tn.clone(), tuple_elements);
+                }
                 deps.insert(new_tn);
             }
         }
         Ok(Annotated::new(Type::Path(typ), deps, extra_apis, kind))
     }
 
+    /// If this type is a concrete instantiation of a `std::pair` or similar,
+    /// returns the (converted) types of its elements.
+    pub(crate) fn get_tuple_elements(&self, tn: &QualifiedName) -> Option<&[Type]> {
+        self.tuple_elements
+            .get(tn)
+            .map(|elements| elements.as_slice())
+    }
+
+    /// If this type is a concrete instantiation of a `std::function`
+    /// which we can construct from a Rust closure, returns its signature.
+    pub(crate) fn get_callback_signature(&self, tn: &QualifiedName) -> Option<&CallbackSignature> {
+        self.callback_signatures.get(tn)
+    }
+
+    /// bindgen represents function pointers as
+    /// `::std::option::Option<unsafe extern "C" fn(..)>`.
+    fn as_function_pointer(typ: &TypePath) -> Option<&TypeBareFn> {
+        let segs: Vec<_> = typ
+            .path
+            .segments
+            .iter()
+            .map(|seg| seg.ident.to_string())
+            .collect();
+        if segs != ["std", "option", "Option"] {
+            return None;
+        }
+        match &typ.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(ab) if ab.args.len() == 1 => match ab.args.first() {
+                Some(GenericArgument::Type(Type::BareFn(bare_fn))) => Some(bare_fn),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Function pointers are only acceptable as parameters, where we can
+    /// convert them to something cxx can cope with. Elsewhere, we leave
+    /// them untouched in typedefs, and reject them otherwise.
+    fn convert_function_pointer(
+        &mut self,
+        typ: TypePath,
+        bare_fn: TypeBareFn,
+        mut deps: HashSet<QualifiedName>,
+        ns: &Namespace,
+        ctx: &TypeConversionContext,
+    ) -> Result<Annotated<Type>, ConvertError> {
+        match ctx {
+            TypeConversionContext::CxxTypedefTarget => Ok(Annotated::new(
+                Type::Path(typ),
+                deps,
+                ApiVec::new(),
+                TypeKind::Regular,
+            )),
+            TypeConversionContext::CxxOuterType { .. } => {
+                let mut signature = self.convert_callback_signature(&Type::BareFn(bare_fn), ns)?;
+                deps.extend(signature.types_encountered.drain());
+                Ok(Annotated::new(
+                    Type::BareFn(signature.ty),
+                    deps,
+                    signature.extra_apis,
+                    TypeKind::Regular,
+                ))
+            }
+            TypeConversionContext::CxxInnerType => Err(ConvertError::UnsupportedCallback(
+                "function pointers are only supported as function parameters".into(),
+            )),
+        }
+    }
+
+    /// Converts the signature of a function pointer or `std::function`,
+    /// checking it's something we can call across the language boundary.
+    fn convert_callback_signature(
+        &mut self,
+        ty: &Type,
+        ns: &Namespace,
+    ) -> Result<Annotated<TypeBareFn>, ConvertError> {
+        let mut bare_fn = match ty {
+            Type::BareFn(bare_fn) => bare_fn.clone(),
+            Type::Path(typ) => Self::as_function_pointer(typ)
+                .ok_or_else(|| {
+                    ConvertError::UnsupportedCallback(typ.to_token_stream().to_string())
+                })?
+                .clone(),
+            _ => {
+                return Err(ConvertError::UnsupportedCallback(
+                    ty.to_token_stream().to_string(),
+                ))
+            }
+        };
+        let mut types_encountered = HashSet::new();
+        let mut extra_apis = ApiVec::new();
+        for arg in bare_fn.inputs.iter_mut() {
+            let mut innerty =
+                self.convert_type(arg.ty.clone(), ns, &TypeConversionContext::CxxInnerType)?;
+            types_encountered.extend(innerty.types_encountered.drain());
+            extra_apis.append(&mut innerty.extra_apis);
+            arg.name = None;
+            arg.ty = innerty.ty;
+        }
+        if let ReturnType::Type(_, ref mut ret) = bare_fn.output {
+            let mut innerty =
+                self.convert_boxed_type(ret.clone(), ns, &TypeConversionContext::CxxInnerType)?;
+            types_encountered.extend(innerty.types_encountered.drain());
+            extra_apis.append(&mut innerty.extra_apis);
+            *ret = innerty.ty;
+        }
+        if !known_types().is_callback_signature(&bare_fn) {
+            return Err(ConvertError::UnsupportedCallback(
+                "callbacks may only take and return simple numeric types".into(),
+            ));
+        }
+        Ok(Annotated::new(
+            bare_fn,
+            types_encountered,
+            extra_apis,
+            TypeKind::Regular,
+        ))
+    }
+
     fn get_generic_args(typ: &mut TypePath) -> Option<&mut PathSegment> {
         match typ.path.segments.last_mut() {
             Some(s) if !s.arguments.is_empty() => Some(s),
@@ -520,6 +724,11 @@ impl<'a> TypeConverter<'a> {
         }
     }
 
+    /// The element types of any `std::vector`s we've come across.
+    pub(crate) fn vector_element_types(&self) -> impl Iterator<Item = &QualifiedName> {
+        self.vector_elements.iter()
+    }
+
     fn find_typedefs<A: AnalysisPhase>(apis: &ApiVec<A>) -> HashMap<QualifiedName, Type>
     where
         A::TypedefAnalysis: TypedefTarget,
@@ -536,8 +745,19 @@ impl<'a> TypeConverter<'a> {
     }
 
     fn find_concrete_templates<A: AnalysisPhase>(
+        config: &IncludeCppConfig,
         apis: &ApiVec<A>,
     ) -> HashMap<String, QualifiedName> {
+        // Templated superclasses are structs, not concrete types, but
+        // other uses of the same template instantiation should refer to them.
+        let superclasses = config
+            .templated_superclasses()
+            .map(|(cpp_definition, name)| {
+                (
+                    cpp_definition.clone(),
+                    QualifiedName::new_from_cpp_name(&name),
+                )
+            });
         apis.iter()
             .filter_map(|api| match &api {
                 Api::ConcreteType { cpp_definition, .. } => {
@@ -545,6 +765,23 @@ impl<'a> TypeConverter<'a> {
                 }
                 _ => None,
             })
+            .chain(superclasses)
+            .collect()
+    }
+
+    fn find_callback_signatures<A: AnalysisPhase>(
+        apis: &ApiVec<A>,
+    ) -> HashMap<QualifiedName, CallbackSignature> {
+        apis.iter()
+            .filter_map(|api| match &api {
+                Api::ConcreteType {
+                    rs_definition: Some(rs_definition),
+                    ..
+                } => known_types()
+                    .callback_signature(rs_definition)
+                    .map(|signature| (api.name().clone(), signature)),
+                _ => None,
+            })
             .collect()
     }
 
@@ -607,7 +844,8 @@ pub(crate) fn find_types<A: AnalysisPhase>(apis: &ApiVec<A>) -> HashSet<Qualifie
             | Api::Enum { .. }
             | Api::Struct { .. }
             | Api::Subclass { .. }
-            | Api::RustType { .. } => Some(api.name()),
+            | Api::RustType { .. }
+            | Api::RustTrait { .. } => Some(api.name()),
             Api::StringConstructor { .. }
             | Api::Function { .. }
             | Api::Const { .. }
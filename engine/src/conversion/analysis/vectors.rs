@@ -0,0 +1,127 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Code to create functions to push non-POD types onto `std::vector`s.
+//! `cxx` can only push trivial types (which for us means POD types)
+//! so for anything else we need a C++ shim which can call the type's
+//! move constructor.
+
+use std::collections::HashSet;
+
+use syn::{parse_quote, punctuated::Punctuated, token::Comma, FnArg, ReturnType};
+
+use crate::{
+    conversion::{
+        api::{
+            Api, ApiName, CppVisibility, FuncToConvert, Provenance, References, TraitSynthesis,
+            TypeKind,
+        },
+        apivec::ApiVec,
+    },
+    types::{make_ident, Namespace, QualifiedName},
+};
+
+use super::{
+    fun::{
+        function_wrapper::{CppFunctionBody, CppFunctionKind},
+        FnAnalysis, FnKind, FnPrePhase2, PodAndConstructorAnalysis, PublicConstructors,
+        TraitMethodKind,
+    },
+    pod::PodAnalysis,
+};
+
+/// Creates the function which pushes a non-POD type onto a `std::vector`.
+/// We only do this for types which we find in vectors in some API, since
+/// for any other type it would be useless.
+pub(crate) fn create_vector_push(ty_name: QualifiedName) -> (ApiName, Box<FuncToConvert>) {
+    let typ = ty_name.to_type_path();
+    let vector_typ = QualifiedName::new_from_cpp_name("std::vector").to_type_path();
+    let inputs: Punctuated<FnArg, Comma> = parse_quote! {
+        vec: *mut #vector_typ<#typ>,
+        value: #typ
+    };
+    let name = get_vector_push_name(&ty_name);
+    let ident = name.get_final_ident();
+    (
+        ApiName::new_from_qualified_name(name),
+        Box::new(FuncToConvert {
+            ident,
+            doc_attrs: Vec::new(),
+            inputs,
+            output: ReturnType::Default,
+            vis: parse_quote! { pub },
+            virtualness: crate::conversion::api::Virtualness::None,
+            cpp_vis: CppVisibility::Public,
+            special_member: None,
+            unused_template_param: false,
+            references: References {
+                ref_params: [make_ident("vec")].into_iter().collect(),
+                ..Default::default()
+            },
+            original_name: None,
+            self_ty: None,
+            synthesized_this_type: None,
+            synthetic_cpp: Some((
+                CppFunctionBody::FunctionCall(Namespace::new(), make_ident("push_back")),
+                CppFunctionKind::Method,
+            )),
+            add_to_trait: Some(TraitSynthesis::VectorPush(ty_name)),
+            is_deleted: false,
+            is_variadic: false,
+            provenance: Provenance::SynthesizedOther,
+        }),
+    )
+}
+
+fn get_vector_push_name(ty_name: &QualifiedName) -> QualifiedName {
+    let name = format!("{}_vector_push", ty_name.get_final_item());
+    QualifiedName::new(ty_name.get_namespace(), make_ident(name))
+}
+
+/// Pushing onto a vector requires a move constructor, which we don't know
+/// about until we've analyzed all the functions. Discard any push functions
+/// for types which turned out to be abstract or immovable.
+pub(crate) fn discard_unpushable_vector_elements(
+    mut apis: ApiVec<FnPrePhase2>,
+) -> ApiVec<FnPrePhase2> {
+    let pushable_types: HashSet<_> = apis
+        .iter()
+        .filter_map(|api| match api {
+            Api::Struct {
+                name,
+                analysis:
+                    PodAndConstructorAnalysis {
+                        pod:
+                            PodAnalysis {
+                                kind: TypeKind::NonPod,
+                                ..
+                            },
+                        constructors:
+                            PublicConstructors {
+                                move_constructor: true,
+                                ..
+                            },
+                    },
+                ..
+            } => Some(name.name.clone()),
+            _ => None,
+        })
+        .collect();
+    apis.retain(|api| {
+        !matches!(api,
+            Api::Function {
+                analysis:
+                    FnAnalysis {
+                        kind: FnKind::TraitMethod { kind: TraitMethodKind::VectorPush, impl_for, .. },
+                        ..
+                    },
+                ..
+            } if !pushable_types.contains(impl_for))
+    });
+    apis
+}
@@ -104,8 +104,14 @@ pub(crate) enum TraitSynthesis {
         to_type: QualifiedName,
         mutable: CastMutability,
     },
+    /// A checked downcast to a subclass using `dynamic_cast`.
+    Downcast {
+        to_type: QualifiedName,
+        mutable: CastMutability,
+    },
     AllocUninitialized(QualifiedName),
     FreeUninitialized(QualifiedName),
+    VectorPush(QualifiedName),
 }
 
 /// Details of a subclass constructor.
@@ -148,6 +154,12 @@ impl References {
     pub(crate) fn new_with_this_and_return_as_reference() -> Self {
         Self {
             ref_return: true,
+            ..Self::new_with_this_as_reference()
+        }
+    }
+
+    pub(crate) fn new_with_this_as_reference() -> Self {
+        Self {
             ref_params: [make_ident("this")].into_iter().collect(),
             ..Default::default()
         }
@@ -223,7 +235,17 @@ pub(crate) enum Provenance {
     Bindgen,
     SynthesizedOther,
     SynthesizedMakeUnique,
+    SynthesizedDefaultArgs,
+    /// A fixed-arity form of a variadic function, listing any parameters
+    /// which Rust passes as a `&CStr`.
+    SynthesizedVariadic(Vec<Ident>),
     SynthesizedSubclassConstructor(Box<SubclassConstructorDetails>),
+    /// A public shim on a Rust subclass's C++ peer, forwarding to a
+    /// protected method or field of its superclass.
+    SynthesizedProtectedAccess {
+        subclass: SubclassName,
+        superclass: QualifiedName,
+    },
 }
 
 /// A C++ function for which we need to generate bindings, but haven't
@@ -263,6 +285,8 @@ pub(crate) struct FuncToConvert {
     /// C++ and instead we're synthesizing it.
     pub(crate) synthetic_cpp: Option<(CppFunctionBody, CppFunctionKind)>,
     pub(crate) is_deleted: bool,
+    /// Whether this is a C-style variadic function, i.e. ends in `...`.
+    pub(crate) is_variadic: bool,
 }
 
 /// Layers of analysis which may be applied to decorate each API.
@@ -382,6 +406,12 @@ impl SubclassName {
     pub(crate) fn remove_ownership(&self) -> Ident {
         self.with_suffix("_remove_ownership")
     }
+    pub(crate) fn on_cpp_destroy(&self) -> Ident {
+        self.with_suffix("_on_cpp_destroy")
+    }
+    pub(crate) fn make_default(&self) -> Ident {
+        self.with_suffix("_make_default")
+    }
     fn with_suffix(&self, suffix: &str) -> Ident {
         make_ident(format!("{}{}", self.0.name.get_final_item(), suffix))
     }
@@ -493,16 +523,24 @@ pub(crate) enum Api<T: AnalysisPhase> {
         details: RustFun,
         receiver: Option<QualifiedName>,
     },
+    /// A Rust trait which C++ sees as an abstract class of the same name,
+    /// implemented by forwarding each method to a `Box<dyn Trait>`.
+    RustTrait {
+        name: ApiName,
+        path: RustPath,
+        methods: Vec<RustTraitMethod>,
+    },
     /// Some function for the extern "Rust" block.
     RustSubclassFn {
         name: ApiName,
         subclass: SubclassName,
         details: Box<RustSubclassFnDetails>,
     },
-    /// A Rust subclass of a C++ class.
+    /// A Rust subclass of one or more C++ classes. The first superclass
+    /// is the one whose constructors we offer.
     Subclass {
         name: SubclassName,
-        superclass: QualifiedName,
+        superclasses: Vec<QualifiedName>,
     },
     /// Contributions to the traits representing superclass methods that we might
     /// subclass in Rust.
@@ -524,6 +562,51 @@ pub(crate) struct RustSubclassFnDetails {
     pub(crate) is_pure_virtual: bool,
 }
 
+/// A method of a Rust trait which C++ can call as a pure virtual
+/// function of the corresponding abstract class.
+#[derive(Clone)]
+pub(crate) struct RustTraitMethod {
+    pub(crate) name: Ident,
+    pub(crate) receiver_mutability: ReceiverMutability,
+    pub(crate) params: Vec<(Ident, Type)>,
+    pub(crate) ret: ReturnType,
+    pub(crate) cpp_params: Vec<String>,
+    pub(crate) cpp_ret: String,
+}
+
+/// Names of the items which we generate in order to expose a Rust
+/// trait to C++.
+pub(crate) struct RustTraitNames(String);
+
+impl RustTraitNames {
+    pub(crate) fn new(rust_trait: &Ident) -> Self {
+        Self(rust_trait.to_string())
+    }
+
+    /// The Rust type which owns the `Box<dyn Trait>`. C++ owns this in
+    /// turn via a `rust::Box`.
+    pub(crate) fn holder(&self) -> Ident {
+        make_ident(format!("{}RustHolder", self.0))
+    }
+
+    /// The C++ class which implements the abstract class by calling
+    /// into Rust.
+    pub(crate) fn cpp_impl(&self) -> Ident {
+        make_ident(format!("{}Rust", self.0))
+    }
+
+    /// The C++ function which wraps a holder in a new instance of
+    /// [`RustTraitNames::cpp_impl`].
+    pub(crate) fn wrapper_fn(&self) -> Ident {
+        make_ident(format!("{}_autocxx_from_rust", self.0))
+    }
+
+    /// The `extern "Rust"` function which C++ calls to invoke a method.
+    pub(crate) fn trampoline(&self, method: &Ident) -> Ident {
+        make_ident(format!("{}_autocxx_{}", self.0, method))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) enum UnsafetyNeeded {
     None,
@@ -546,6 +629,7 @@ impl<T: AnalysisPhase> Api<T> {
             Api::IgnoredItem { name, .. } => name,
             Api::RustType { name, .. } => name,
             Api::RustFn { name, .. } => name,
+            Api::RustTrait { name, .. } => name,
             Api::RustSubclassFn { name, .. } => name,
             Api::Subclass { name, .. } => &name.0,
             Api::SubclassTraitItem { name, .. } => name,
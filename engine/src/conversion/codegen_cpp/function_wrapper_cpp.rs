@@ -6,9 +6,14 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::conversion::{
-    analysis::fun::function_wrapper::{CppConversionType, TypeConversionPolicy},
-    ConvertError,
+use itertools::Itertools;
+
+use crate::{
+    conversion::{
+        analysis::fun::function_wrapper::{CppConversionType, TypeConversionPolicy},
+        ConvertError,
+    },
+    known_types::CallbackSignature,
 };
 
 use super::type_to_cpp::{type_to_cpp, CppNameMap};
@@ -23,6 +28,14 @@ impl TypeConversionPolicy {
             CppConversionType::FromPtrToValue => {
                 Ok(format!("{}*", self.unwrapped_type_as_string(cpp_name_map)?))
             }
+            CppConversionType::FromSliceToSpan => self.slice_type(cpp_name_map, ""),
+            CppConversionType::FromSharedSliceToSpan => self.slice_type(cpp_name_map, "const "),
+            CppConversionType::FromUniquePtrToPtr => self.owning_unique_ptr_type(cpp_name_map),
+            CppConversionType::FromUsizeToFunctionPointer => Ok("size_t".into()),
+            CppConversionType::FromBoxToFunction => Ok(format!(
+                "rust::Box<{}>",
+                CallbackSignature::closure_name(&self.unwrapped_type_as_string(cpp_name_map)?)
+            )),
             _ => self.unwrapped_type_as_string(cpp_name_map),
         }
     }
@@ -30,10 +43,24 @@ impl TypeConversionPolicy {
     pub(super) fn converted_type(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
         match self.cpp_conversion {
             CppConversionType::FromValueToUniquePtr => self.unique_ptr_wrapped_type(cpp_name_map),
+            CppConversionType::FromSpanToSlice => self.slice_type(cpp_name_map, "const "),
+            CppConversionType::FromPtrToUniquePtr => self.owning_unique_ptr_type(cpp_name_map),
             _ => self.unwrapped_type_as_string(cpp_name_map),
         }
     }
 
+    fn slice_type(
+        &self,
+        cpp_name_map: &CppNameMap,
+        constness: &str,
+    ) -> Result<String, ConvertError> {
+        Ok(format!(
+            "rust::Slice<{}{}>",
+            constness,
+            type_to_cpp(self.span_element_type(), cpp_name_map)?
+        ))
+    }
+
     fn unwrapped_type_as_string(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
         type_to_cpp(&self.unwrapped_type, cpp_name_map)
     }
@@ -48,6 +75,14 @@ impl TypeConversionPolicy {
         ))
     }
 
+    /// A `std::unique_ptr` owning the object pointed to by a raw pointer.
+    fn owning_unique_ptr_type(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
+        Ok(format!(
+            "std::unique_ptr<{}>",
+            type_to_cpp(self.pointee_type(), cpp_name_map)?
+        ))
+    }
+
     pub(super) fn cpp_conversion(
         &self,
         var_name: &str,
@@ -77,6 +112,79 @@ impl TypeConversionPolicy {
                     format!("std::move({})", dereference)
                 }
             }
+            CppConversionType::FromSliceToSpan => format!(
+                "{}({}.data(), {}.size())",
+                self.unwrapped_type_as_string(cpp_name_map)?,
+                var_name,
+                var_name
+            ),
+            // bindgen told us the span's type without the constness of
+            // its elements, so put that back.
+            CppConversionType::FromSharedSliceToSpan => format!(
+                "{}({}.data(), {}.size())",
+                self.unwrapped_type_as_string(cpp_name_map)?
+                    .replacen('<', "<const ", 1),
+                var_name,
+                var_name
+            ),
+            CppConversionType::FromSpanToSlice => format!(
+                "[&] {{ auto autocxx_span = {}; return {}(autocxx_span.data(), autocxx_span.size()); }}()",
+                var_name,
+                self.converted_type(cpp_name_map)?
+            ),
+            CppConversionType::FromUsizeToFunctionPointer => format!(
+                "reinterpret_cast<std::add_pointer_t<{}>>({})",
+                self.unwrapped_type_as_string(cpp_name_map)?,
+                var_name
+            ),
+            CppConversionType::FromBoxToFunction => {
+                self.closure_to_function(var_name, cpp_name_map)?
+            }
+            CppConversionType::FromPtrToUniquePtr => format!(
+                "{}({})",
+                self.owning_unique_ptr_type(cpp_name_map)?,
+                var_name
+            ),
+            CppConversionType::FromUniquePtrToPtr => format!("{}.release()", var_name),
         })
     }
+
+    /// Wraps a `rust::Box` holding a Rust closure into a `std::function`.
+    /// `std::function` must be copyable, so we share ownership of the box
+    /// between any copies. That's sound only because the Rust side insists
+    /// on a `Fn + Send + Sync` closure, which is only ever called by
+    /// shared reference.
+    fn closure_to_function(
+        &self,
+        var_name: &str,
+        cpp_name_map: &CppNameMap,
+    ) -> Result<String, ConvertError> {
+        let signature = self
+            .callback_signature()
+            .expect("std::function conversion without a signature");
+        let function = self.unwrapped_type_as_string(cpp_name_map)?;
+        let params: Vec<_> = signature
+            .inputs()
+            .enumerate()
+            .map(|(i, ty)| {
+                Ok(format!(
+                    "{} autocxx_arg{}",
+                    type_to_cpp(ty, cpp_name_map)?,
+                    i
+                ))
+            })
+            .collect::<Result<_, ConvertError>>()?;
+        let args = std::iter::once("**autocxx_closure".to_string())
+            .chain((0..params.len()).map(|i| format!("autocxx_arg{}", i)))
+            .join(", ");
+        Ok(format!(
+            "{}([autocxx_closure = std::make_shared<const rust::Box<{}>>(std::move({}))]({}) {{ return {}({}); }})",
+            function,
+            CallbackSignature::closure_name(&function),
+            var_name,
+            params.join(", "),
+            CallbackSignature::trampoline_name(&function),
+            args
+        ))
+    }
 }
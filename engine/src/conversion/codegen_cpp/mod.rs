@@ -11,7 +11,14 @@ mod new_and_delete_prelude;
 pub(crate) mod type_to_cpp;
 
 use crate::{
-    conversion::analysis::fun::{function_wrapper::CppFunctionKind, FnAnalysis},
+    conversion::analysis::fun::{
+        function_wrapper::{
+            CppConversionType, CppFunctionKind, StatusConversion, SuccessCheck, TupleElement,
+            TypeConversionPolicy,
+        },
+        FnAnalysis, ReceiverMutability,
+    },
+    known_types::{known_types, AssociativeContainerDetails, CallbackSignature},
     types::{make_ident, QualifiedName},
     CppCodegenOptions, CppFilePair,
 };
@@ -19,8 +26,9 @@ use autocxx_parser::IncludeCppConfig;
 use itertools::Itertools;
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
 };
+use syn::{Ident, Type};
 use type_to_cpp::{original_name_map_from_apis, type_to_cpp, CppNameMap};
 
 use self::type_to_cpp::{
@@ -35,7 +43,7 @@ use super::{
         },
         pod::PodAnalysis,
     },
-    api::{Api, Provenance, SubclassName, TypeKind},
+    api::{Api, Provenance, RustTraitMethod, RustTraitNames, SubclassName, TypeKind},
     apivec::ApiVec,
     ConvertError,
 };
@@ -103,6 +111,7 @@ pub(crate) struct CppCodeGenerator<'a> {
 
 struct SubclassFunction<'a> {
     fun: &'a CppFunction,
+    superclass: &'a QualifiedName,
     is_pure_virtual: bool,
 }
 
@@ -147,6 +156,7 @@ impl<'a> CppCodeGenerator<'a> {
     ) -> Result<(), ConvertError> {
         let mut constructors_by_subclass: HashMap<SubclassName, Vec<&CppFunction>> = HashMap::new();
         let mut methods_by_subclass: HashMap<SubclassName, Vec<SubclassFunction>> = HashMap::new();
+        let mut protected_by_subclass: HashMap<SubclassName, Vec<String>> = HashMap::new();
         let mut deferred_apis = Vec::new();
         for api in apis {
             match &api {
@@ -168,6 +178,24 @@ impl<'a> CppCodeGenerator<'a> {
                             .or_default()
                             .push(&details.cpp_impl);
                     }
+                    if let Provenance::SynthesizedProtectedAccess {
+                        subclass,
+                        superclass,
+                    } = &fun.provenance
+                    {
+                        let member = match &cpp_wrapper.payload {
+                            CppFunctionBody::FieldAccess(field) => field.to_string(),
+                            _ => cpp_wrapper.original_cpp_name.clone(),
+                        };
+                        let using_decl = format!("using {}::{};", superclass.to_cpp_name(), member);
+                        let using_decls =
+                            protected_by_subclass.entry(subclass.clone()).or_default();
+                        // Overloads, and both accessors for a field, need only one
+                        // using-declaration.
+                        if !using_decls.contains(&using_decl) {
+                            using_decls.push(using_decl);
+                        }
+                    }
                     self.generate_cpp_function(cpp_wrapper)?
                 }
                 Api::ConcreteType {
@@ -182,9 +210,30 @@ impl<'a> CppCodeGenerator<'a> {
                         None => Cow::Borrowed(cpp_definition),
                     };
 
-                    self.generate_typedef(api.name(), &effective_cpp_definition)
+                    self.generate_typedef(api.name(), &effective_cpp_definition);
+                    if let Some(details) = rs_definition
+                        .as_ref()
+                        .and_then(|ty| known_types().associative_container_details(ty))
+                    {
+                        self.generate_associative_container_shims(api.name(), &details)?;
+                    }
+                    if rs_definition
+                        .as_ref()
+                        .and_then(|ty| known_types().callback_signature(ty))
+                        .is_some()
+                    {
+                        self.generate_closure_declaration(api.name());
+                    }
+                }
+                Api::CType { typename, .. } => {
+                    match known_types().utf_string_code_unit_bits(typename) {
+                        Some(bits) => self.generate_utf_string_helpers(typename, bits),
+                        None => self.generate_ctype_typedef(typename),
+                    }
+                }
+                Api::RustTrait { name, methods, .. } => {
+                    self.generate_rust_trait(&name.name.get_final_ident(), methods)
                 }
-                Api::CType { typename, .. } => self.generate_ctype_typedef(typename),
                 Api::Subclass { .. } => deferred_apis.push(api),
                 Api::RustSubclassFn {
                     subclass, details, ..
@@ -194,6 +243,7 @@ impl<'a> CppCodeGenerator<'a> {
                         .or_default()
                         .push(SubclassFunction {
                             fun: &details.cpp_impl,
+                            superclass: &details.superclass,
                             is_pure_virtual: details.is_pure_virtual,
                         });
                 }
@@ -216,17 +266,36 @@ impl<'a> CppCodeGenerator<'a> {
             }
         }
 
+        // Subclasses which C++ may construct by name, keyed by superclass.
+        let mut factories: BTreeMap<(String, String), Vec<&SubclassName>> = BTreeMap::new();
         for api in deferred_apis.into_iter() {
             match api {
-                Api::Subclass { name, superclass } => self.generate_subclass(
-                    superclass,
-                    name,
-                    constructors_by_subclass.remove(name).unwrap_or_default(),
-                    methods_by_subclass.remove(name).unwrap_or_default(),
-                )?,
+                Api::Subclass { name, superclasses } => {
+                    self.generate_subclass(
+                        superclasses,
+                        name,
+                        constructors_by_subclass.remove(name).unwrap_or_default(),
+                        methods_by_subclass.remove(name).unwrap_or_default(),
+                        protected_by_subclass.remove(name).unwrap_or_default(),
+                    )?;
+                    if self.config.is_factory_subclass(&name.id()) {
+                        for superclass in superclasses {
+                            factories
+                                .entry((
+                                    superclass.to_cpp_name(),
+                                    superclass.get_final_item().to_string(),
+                                ))
+                                .or_default()
+                                .push(name);
+                        }
+                    }
+                }
                 _ => panic!("Unexpected deferred API"),
             }
         }
+        if !factories.is_empty() {
+            self.generate_subclass_factories(factories);
+        }
         Ok(())
     }
 
@@ -334,14 +403,99 @@ impl<'a> CppCodeGenerator<'a> {
     }
 
     fn generate_cpp_function(&mut self, details: &CppFunction) -> Result<(), ConvertError> {
-        self.additional_functions
-            .push(self.generate_cpp_function_inner(
-                details,
-                false,
-                ConversionDirection::RustCallsCpp,
-                false,
-                None,
-            )?);
+        // Functions which take Rust closures call into Rust, so need to see
+        // the declarations generated by cxx.
+        let takes_closures = details
+            .argument_conversion
+            .iter()
+            .any(|conv| conv.callback_signature().is_some());
+        let mut function = self.generate_cpp_function_inner(
+            details,
+            false,
+            ConversionDirection::RustCallsCpp,
+            takes_closures,
+            None,
+        )?;
+        if takes_closures {
+            function.cpp_headers.push(Header::CxxgenH);
+        }
+        self.additional_functions.push(function);
+        if let Some(ret) = &details.return_conversion {
+            self.generate_tuple_accessors(&details.wrapper_function_name, ret)?;
+            self.generate_status_checker(&details.wrapper_function_name, ret)?;
+        }
+        Ok(())
+    }
+
+    /// If a function returns a `std::pair` or `std::tuple` which Rust will
+    /// receive as a tuple, generate functions to move each element out of it.
+    fn generate_tuple_accessors(
+        &mut self,
+        wrapper_function_name: &Ident,
+        ret: &TypeConversionPolicy,
+    ) -> Result<(), ConvertError> {
+        let elements = match ret.tuple_elements() {
+            Some(elements) => elements,
+            None => return Ok(()),
+        };
+        let tuple_type = type_to_cpp(&ret.unwrapped_type, &self.original_name_map)?;
+        let definitions: Result<Vec<_>, ConvertError> = elements
+            .iter()
+            .enumerate()
+            .map(|(idx, element)| {
+                let element_type = type_to_cpp(&element.ty, &self.original_name_map)?;
+                let accessor_name = TupleElement::accessor_name(wrapper_function_name, idx);
+                Ok(if element.by_value {
+                    format!(
+                        "inline {} {}({}& tuple) {{ return std::move(std::get<{}>(tuple)); }}",
+                        element_type, accessor_name, tuple_type, idx
+                    )
+                } else {
+                    format!(
+                        "inline std::unique_ptr<{}> {}({}& tuple) {{ return std::make_unique<{}>(std::move(std::get<{}>(tuple))); }}",
+                        element_type, accessor_name, tuple_type, element_type, idx
+                    )
+                })
+            })
+            .collect();
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration: Some(definitions?.join("\n")),
+            definition: None,
+            headers: vec![
+                Header::System("memory"),
+                Header::System("utility"),
+                Header::System("tuple"),
+            ],
+            cpp_headers: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// If a function returns a non-POD status type which Rust will turn into
+    /// a `Result`, generate a function to check it for success.
+    fn generate_status_checker(
+        &mut self,
+        wrapper_function_name: &Ident,
+        ret: &TypeConversionPolicy,
+    ) -> Result<(), ConvertError> {
+        let predicate = match ret.status_conversion().map(|status| &status.check) {
+            Some(SuccessCheck::OkIf(predicate)) => predicate,
+            _ => return Ok(()),
+        };
+        let status_type = type_to_cpp(&ret.unwrapped_type, &self.original_name_map)?;
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration: Some(format!(
+                "inline bool {}(const {}& status) {{ return {}; }}",
+                StatusConversion::checker_name(wrapper_function_name),
+                status_type,
+                predicate
+            )),
+            definition: None,
+            headers: Vec::new(),
+            cpp_headers: Vec::new(),
+        });
         Ok(())
     }
 
@@ -468,6 +622,11 @@ impl<'a> CppCodeGenerator<'a> {
             CppFunctionBody::MakeUnique | CppFunctionBody::Cast => {
                 (arg_list, "".to_string(), false)
             }
+            CppFunctionBody::Downcast => (
+                format!("dynamic_cast<{}>(&{})", ret_type, arg_list),
+                "".to_string(),
+                false,
+            ),
             CppFunctionBody::PlacementNew(ns, id) => {
                 let ty_id = QualifiedName::new(ns, id.clone());
                 let ty_id = self.namespaced_name(&ty_id);
@@ -527,6 +686,11 @@ impl<'a> CppCodeGenerator<'a> {
                 "".to_string(),
                 true,
             ),
+            CppFunctionBody::FieldAccess(field) => (
+                format!("{}.{}", receiver.unwrap(), field),
+                "".to_string(),
+                false,
+            ),
         };
         if let Some(ret) = &details.return_conversion {
             underlying_function_call = format!(
@@ -580,6 +744,25 @@ impl<'a> CppCodeGenerator<'a> {
         if need_allocators {
             headers.push(Header::NewDeletePrelude);
         }
+        if details
+            .argument_conversion
+            .iter()
+            .chain(details.return_conversion.iter())
+            .any(|conv| conv.is_span())
+        {
+            headers.push(Header::CxxH);
+        }
+        for conv in &details.argument_conversion {
+            match conv.cpp_conversion {
+                CppConversionType::FromUsizeToFunctionPointer => {
+                    headers.push(Header::System("type_traits"))
+                }
+                CppConversionType::FromBoxToFunction => {
+                    headers.extend([Header::CxxH, Header::System("functional")])
+                }
+                _ => {}
+            }
+        }
         Ok(AdditionalFunction {
             type_definition: None,
             declaration,
@@ -598,6 +781,33 @@ impl<'a> CppCodeGenerator<'a> {
         self.generate_typedef(tn, &cpp_name)
     }
 
+    /// cxx doesn't know about `std::u16string` or `std::u32string`, so we
+    /// give it an opaque typedef of each, plus functions to get the code
+    /// units in and out.
+    fn generate_utf_string_helpers(&mut self, tn: &QualifiedName, bits: usize) {
+        let our_name = tn.get_final_item();
+        let cpp_name = tn.to_cpp_name();
+        let make_name = self.config.get_utf_string_helper_name("make", our_name);
+        let code_units_name = self
+            .config
+            .get_utf_string_helper_name("code_units", our_name);
+        let make = format!("inline std::unique_ptr<{}> {}(::rust::Slice<const uint{}_t> code_units) {{ return std::make_unique<{}>(code_units.begin(), code_units.end()); }}", cpp_name, make_name, bits, cpp_name);
+        let code_units = format!("inline ::rust::Vec<uint{}_t> {}(const {}& str) {{ ::rust::Vec<uint{}_t> code_units; code_units.reserve(str.size()); for (auto code_unit : str) {{ code_units.push_back(code_unit); }} return code_units; }}", bits, code_units_name, cpp_name, bits);
+        let declaration = Some(format!("{}\n{}", make, code_units));
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(format!("typedef {} {};", cpp_name, our_name)),
+            declaration,
+            definition: None,
+            headers: vec![
+                Header::System("cstdint"),
+                Header::System("memory"),
+                Header::System("string"),
+                Header::CxxH,
+            ],
+            cpp_headers: Vec::new(),
+        })
+    }
+
     fn generate_typedef(&mut self, tn: &QualifiedName, definition: &str) {
         let our_name = tn.get_final_item();
         self.additional_functions.push(AdditionalFunction {
@@ -609,12 +819,258 @@ impl<'a> CppCodeGenerator<'a> {
         })
     }
 
+    /// A `std::function` which can be built from a Rust closure needs a
+    /// declaration of the Rust type holding that closure, such that
+    /// we can declare functions which take it in a `rust::Box`.
+    fn generate_closure_declaration(&mut self, tn: &QualifiedName) {
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(format!(
+                "struct {};",
+                CallbackSignature::closure_name(tn.get_final_item())
+            )),
+            declaration: None,
+            definition: None,
+            headers: vec![
+                Header::System("functional"),
+                Header::System("memory"),
+                Header::CxxH,
+            ],
+            cpp_headers: Vec::new(),
+        })
+    }
+
+    /// A Rust trait is exposed to C++ as an abstract class, plus a concrete
+    /// subclass which owns the Rust object and calls into Rust for each method.
+    fn generate_rust_trait(&mut self, id: &Ident, methods: &[RustTraitMethod]) {
+        let names = RustTraitNames::new(id);
+        let holder = names.holder();
+        let cpp_impl = names.cpp_impl();
+        let wrapper_fn = names.wrapper_fn();
+        let mut pure_virtuals = Vec::new();
+        let mut overrides = Vec::new();
+        let mut definitions = vec![
+            format!(
+                "{}::{}(rust::Box<{}> holder) : holder(std::move(holder)) {{}}",
+                cpp_impl, cpp_impl, holder
+            ),
+            format!("{}::~{}() {{}}", cpp_impl, cpp_impl),
+        ];
+        for method in methods {
+            let constness = match method.receiver_mutability {
+                ReceiverMutability::Const => " const",
+                ReceiverMutability::Mutable => "",
+            };
+            let params = method.cpp_params.join(", ");
+            pure_virtuals.push(format!(
+                "virtual {} {}({}){} = 0;",
+                method.cpp_ret, method.name, params, constness
+            ));
+            overrides.push(format!(
+                "{} {}({}){} override;",
+                method.cpp_ret, method.name, params, constness
+            ));
+            let args = std::iter::once("*holder".to_string())
+                .chain(method.params.iter().map(|(id, _)| id.to_string()))
+                .join(", ");
+            definitions.push(format!(
+                "{} {}::{}({}){} {{ return {}({}); }}",
+                method.cpp_ret,
+                cpp_impl,
+                method.name,
+                params,
+                constness,
+                names.trampoline(&method.name),
+                args
+            ));
+        }
+        definitions.push(format!(
+            "std::unique_ptr<{}> {}(rust::Box<{}> holder) {{ return std::make_unique<{}>(std::move(holder)); }}",
+            id, wrapper_fn, holder, cpp_impl
+        ));
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(format!(
+                "struct {};\nclass {} {{\npublic:\nvirtual ~{}() {{}}\n{}\n}};\nclass {} : public {} {{\npublic:\n{}(rust::Box<{}> holder);\n~{}();\n{}\nprivate:\nrust::Box<{}> holder;\n}};",
+                holder,
+                id,
+                id,
+                pure_virtuals.join("\n"),
+                cpp_impl,
+                id,
+                cpp_impl,
+                holder,
+                cpp_impl,
+                overrides.join("\n"),
+                holder
+            )),
+            declaration: Some(format!(
+                "std::unique_ptr<{}> {}(rust::Box<{}> holder);",
+                id, wrapper_fn, holder
+            )),
+            definition: Some(definitions.join("\n")),
+            headers: vec![Header::System("memory"), Header::CxxH],
+            cpp_headers: vec![Header::CxxgenH],
+        })
+    }
+
+    /// Generate functions to allow Rust to inspect and modify a `std::map`
+    /// or similar, since cxx doesn't know about these containers.
+    fn generate_associative_container_shims(
+        &mut self,
+        tn: &QualifiedName,
+        details: &AssociativeContainerDetails,
+    ) -> Result<(), ConvertError> {
+        let container = tn.get_final_item();
+        let shim_name = |op| AssociativeContainerDetails::shim_name(container, op);
+        let param_type = |ty: &Type| -> Result<String, ConvertError> {
+            let cpp_type = type_to_cpp(ty, &self.original_name_map)?;
+            Ok(if AssociativeContainerDetails::passed_by_reference(ty) {
+                format!("const {}&", cpp_type)
+            } else {
+                cpp_type
+            })
+        };
+        let key_type = type_to_cpp(&details.key, &self.original_name_map)?;
+        let key_param = param_type(&details.key)?;
+        let iter_type = shim_name("iter");
+        let mut definitions = vec![
+            format!(
+                "inline size_t {}(const {}& container) {{ return container.size(); }}",
+                shim_name("len"),
+                container
+            ),
+            format!(
+                "inline bool {}(const {}& container, {} key) {{ return container.count(key) != 0; }}",
+                shim_name("contains"),
+                container,
+                key_param
+            ),
+            format!(
+                "inline std::unique_ptr<{}> {}(const {}& container) {{ return std::make_unique<{}>(container.begin()); }}",
+                iter_type,
+                shim_name("begin"),
+                container,
+                iter_type
+            ),
+            format!(
+                "inline void {}({}& iter) {{ ++iter; }}",
+                shim_name("iter_advance"),
+                iter_type
+            ),
+        ];
+        match &details.value {
+            Some(value) => {
+                let value_type = type_to_cpp(value, &self.original_name_map)?;
+                definitions.extend([
+                    format!(
+                        "inline const {}* {}(const {}& container, {} key) {{ auto it = container.find(key); return it == container.end() ? nullptr : &it->second; }}",
+                        value_type,
+                        shim_name("get"),
+                        container,
+                        key_param
+                    ),
+                    format!(
+                        "inline void {}({}& container, {} key, {} value) {{ container.erase(key); container.emplace(key, std::move(value)); }}",
+                        shim_name("insert"),
+                        container,
+                        key_param,
+                        param_type(value)?
+                    ),
+                    format!(
+                        "inline const {}* {}(const {}& container, const {}& iter) {{ return iter == container.end() ? nullptr : &iter->first; }}",
+                        key_type,
+                        shim_name("iter_key"),
+                        container,
+                        iter_type
+                    ),
+                    format!(
+                        "inline const {}* {}(const {}& iter) {{ return &iter->second; }}",
+                        value_type,
+                        shim_name("iter_value"),
+                        iter_type
+                    ),
+                ]);
+            }
+            None => {
+                definitions.extend([
+                    format!(
+                        "inline void {}({}& container, {} key) {{ container.insert(key); }}",
+                        shim_name("insert"),
+                        container,
+                        key_param
+                    ),
+                    format!(
+                        "inline const {}* {}(const {}& container, const {}& iter) {{ return iter == container.end() ? nullptr : &*iter; }}",
+                        key_type,
+                        shim_name("iter_key"),
+                        container,
+                        iter_type
+                    ),
+                ]);
+            }
+        }
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(format!(
+                "typedef {}::const_iterator {};",
+                container, iter_type
+            )),
+            declaration: Some(definitions.join("\n")),
+            definition: None,
+            headers: vec![Header::System("memory"), Header::System("utility")],
+            cpp_headers: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Generates `make_rust_subclass<Base>(name)`, which constructs a
+    /// default instance of whichever Rust subclass of `Base` is called
+    /// `name`, or returns `nullptr` if there's no such subclass.
+    fn generate_subclass_factories(
+        &mut self,
+        factories: BTreeMap<(String, String), Vec<&SubclassName>>,
+    ) {
+        let mut declarations = vec!["template <typename Base> std::unique_ptr<Base> make_rust_subclass(const std::string& name);".to_string()];
+        let mut definitions = Vec::new();
+        for ((superclass, super_name), subclasses) in factories {
+            let signature = format!(
+                "template <> std::unique_ptr<{}> make_rust_subclass<{}>(const std::string& name)",
+                superclass, superclass
+            );
+            declarations.push(format!("{};", signature));
+            let branches = subclasses
+                .iter()
+                .map(|sub| {
+                    // Our subclasses inherit privately, so we can't rely on
+                    // implicit conversion from the subclass pointer.
+                    format!(
+                        "if (name == \"{}\") {{\nreturn std::unique_ptr<{}>(&{}().release()->As_{}_mut());\n}}",
+                        sub.id(),
+                        superclass,
+                        sub.make_default(),
+                        super_name
+                    )
+                })
+                .join("\n");
+            definitions.push(format!(
+                "{} {{\n{}\nreturn nullptr;\n}}",
+                signature, branches
+            ));
+        }
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration: Some(declarations.join("\n")),
+            definition: Some(definitions.join("\n")),
+            headers: vec![Header::System("memory"), Header::System("string")],
+            cpp_headers: vec![Header::CxxgenH],
+        });
+    }
+
     fn generate_subclass(
         &mut self,
-        superclass: &QualifiedName,
+        superclasses: &[QualifiedName],
         subclass: &SubclassName,
         constructors: Vec<&CppFunction>,
         methods: Vec<SubclassFunction>,
+        protected_using_decls: Vec<String>,
     ) -> Result<(), ConvertError> {
         let holder = subclass.holder();
         self.additional_functions.push(AdditionalFunction {
@@ -624,7 +1080,8 @@ impl<'a> CppCodeGenerator<'a> {
             headers: Vec::new(),
             cpp_headers: Vec::new(),
         });
-        let mut method_decls = Vec::new();
+        // Protected superclass methods which Rust may call through the peer.
+        let mut method_decls = protected_using_decls;
         for method in methods {
             // First the method which calls from C++ to Rust
             let mut fn_impl = self.generate_cpp_function_inner(
@@ -638,6 +1095,7 @@ impl<'a> CppCodeGenerator<'a> {
             self.additional_functions.push(fn_impl);
             // And now the function to be called from Rust for default implementation (calls superclass in C++)
             if !method.is_pure_virtual {
+                let superclass = method.superclass;
                 let mut super_method = method.fun.clone();
                 super_method.pass_obs_field = false;
                 super_method.wrapper_function_name = SubclassName::get_super_fn_name(
@@ -661,16 +1119,17 @@ impl<'a> CppCodeGenerator<'a> {
                 self.additional_functions.push(super_fn_impl);
             }
         }
-        // In future, for each superclass..
-        let super_name = superclass.get_final_item();
-        method_decls.push(format!(
-            "const {}& As_{}() const {{ return *this; }}",
-            super_name, super_name,
-        ));
-        method_decls.push(format!(
-            "{}& As_{}_mut() {{ return *this; }}",
-            super_name, super_name
-        ));
+        for superclass in superclasses {
+            let super_name = superclass.get_final_item();
+            method_decls.push(format!(
+                "const {}& As_{}() const {{ return *this; }}",
+                super_name, super_name,
+            ));
+            method_decls.push(format!(
+                "{}& As_{}_mut() {{ return *this; }}",
+                super_name, super_name
+            ));
+        }
         // And now constructors
         let mut constructor_decls: Vec<String> = Vec::new();
         for constructor in constructors {
@@ -687,16 +1146,21 @@ impl<'a> CppCodeGenerator<'a> {
         }
         self.additional_functions.push(AdditionalFunction {
             type_definition: Some(format!(
-                "class {} : {}\n{{\npublic:\n{}\n{}\nvoid {}() const;\nprivate:rust::Box<{}> obs;\nvoid really_remove_ownership();\n\n}};",
+                "class {} : {}\n{{\npublic:\n{}\n~{}();\n{}\nvoid {}() const;\nprivate:rust::Box<{}> obs;\nvoid really_remove_ownership();\n\n}};",
                 subclass.cpp(),
-                superclass.to_cpp_name(),
+                superclasses.iter().map(|sup| sup.to_cpp_name()).join(", "),
                 constructor_decls.join("\n"),
+                subclass.cpp().get_final_item(),
                 method_decls.join("\n"),
                 subclass.cpp_remove_ownership(),
                 holder
             )),
+            // The destructor tells the Rust side that this peer is gone.
             definition: Some(format!(
-                "void {}::{}() const {{\nconst_cast<{}*>(this)->really_remove_ownership();\n}}\nvoid {}::really_remove_ownership() {{\nauto new_obs = {}(std::move(obs));\nobs = std::move(new_obs);\n}}\n",
+                "{}::~{}() {{\n{}(*obs);\n}}\nvoid {}::{}() const {{\nconst_cast<{}*>(this)->really_remove_ownership();\n}}\nvoid {}::really_remove_ownership() {{\nauto new_obs = {}(std::move(obs));\nobs = std::move(new_obs);\n}}\n",
+                subclass.cpp(),
+                subclass.cpp().get_final_item(),
+                subclass.on_cpp_destroy(),
                 subclass.cpp(),
                 subclass.cpp_remove_ownership(),
                 subclass.cpp(),
@@ -111,8 +111,21 @@ pub(crate) fn type_to_cpp(ty: &Type, cpp_name_map: &CppNameMap) -> Result<String
             get_mut_string(&typp.mutability),
             type_to_cpp(typp.elem.as_ref(), cpp_name_map)?
         )),
+        // This gives the function type, as used in `std::function`,
+        // rather than a function pointer.
+        Type::BareFn(bare_fn) => {
+            let ret = match &bare_fn.output {
+                syn::ReturnType::Default => "void".to_string(),
+                syn::ReturnType::Type(_, ty) => type_to_cpp(ty, cpp_name_map)?,
+            };
+            let args: Result<Vec<_>, _> = bare_fn
+                .inputs
+                .iter()
+                .map(|arg| type_to_cpp(&arg.ty, cpp_name_map))
+                .collect();
+            Ok(format!("{}({})", ret, args?.join(", ")))
+        }
         Type::Array(_)
-        | Type::BareFn(_)
         | Type::Group(_)
         | Type::ImplTrait(_)
         | Type::Infer(_)
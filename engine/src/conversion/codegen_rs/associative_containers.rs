@@ -0,0 +1,155 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::{format_ident, quote};
+use syn::{parse_quote, ForeignItem, ForeignItemFn, Ident, Item, Type};
+
+use crate::known_types::AssociativeContainerDetails;
+
+use super::unqualify::{unqualify_params, unqualify_ret_type};
+
+/// Generates the Rust side of the shims which allow access to the contents
+/// of a `std::map` or similar. The C++ side is generated by `codegen_cpp`.
+/// Returns items for the `extern "C++"` section of the bridge, and items
+/// for the bindgen mod.
+pub(super) fn generate_associative_container_shims(
+    id: &Ident,
+    details: &AssociativeContainerDetails,
+) -> (Vec<ForeignItem>, Vec<Item>) {
+    let container = id.to_string();
+    let shim_name =
+        |op| format_ident!("{}", AssociativeContainerDetails::shim_name(&container, op));
+    let param_type = |ty: &Type| -> Type {
+        if AssociativeContainerDetails::passed_by_reference(ty) {
+            parse_quote! { & #ty }
+        } else {
+            ty.clone()
+        }
+    };
+    let key = &details.key;
+    let key_param = param_type(key);
+    let iter_type = shim_name("iter");
+    let len = shim_name("len");
+    let contains = shim_name("contains");
+    let begin = shim_name("begin");
+    let iter_advance = shim_name("iter_advance");
+    let iter_key = shim_name("iter_key");
+    let insert = shim_name("insert");
+    let iterator_struct = format_ident!("{}Iter", id);
+
+    let mut bridge_fns: Vec<ForeignItemFn> = vec![
+        parse_quote! { fn #len(container: &#id) -> usize; },
+        parse_quote! { fn #contains(container: &#id, key: #key_param) -> bool; },
+        parse_quote! { fn #begin(container: &#id) -> UniquePtr<#iter_type>; },
+        parse_quote! { fn #iter_advance(iter: ::std::pin::Pin<&mut #iter_type>); },
+        parse_quote! { fn #iter_key(container: &#id, iter: &#iter_type) -> *const #key; },
+    ];
+    let (extra_methods, item_type, make_item): (_, Type, _) = match &details.value {
+        Some(value) => {
+            let value_param = param_type(value);
+            let get = shim_name("get");
+            let iter_value = shim_name("iter_value");
+            bridge_fns.extend([
+                parse_quote! { fn #get(container: &#id, key: #key_param) -> *const #value; },
+                parse_quote! { fn #insert(container: ::std::pin::Pin<&mut #id>, key: #key_param, value: #value_param); },
+                parse_quote! { fn #iter_value(iter: &#iter_type) -> *const #value; },
+            ]);
+            (
+                quote! {
+                    /// Returns a reference to the value for this key, if any.
+                    pub fn get(&self, key: #key_param) -> Option<&#value> {
+                        unsafe { cxxbridge::#get(self, key).as_ref() }
+                    }
+                    /// Inserts a value, replacing any existing value for this key.
+                    pub fn insert(self: ::std::pin::Pin<&mut Self>, key: #key_param, value: #value_param) {
+                        cxxbridge::#insert(self, key, value)
+                    }
+                },
+                parse_quote! { (&'a #key, &'a #value) },
+                quote! {
+                    let value = unsafe { cxxbridge::#iter_value(&self.iter).as_ref() }.unwrap();
+                    let item = (key, value);
+                },
+            )
+        }
+        None => {
+            bridge_fns.push(
+                parse_quote! { fn #insert(container: ::std::pin::Pin<&mut #id>, key: #key_param); },
+            );
+            (
+                quote! {
+                    /// Adds a value to the set, if it's not already present.
+                    pub fn insert(self: ::std::pin::Pin<&mut Self>, key: #key_param) {
+                        cxxbridge::#insert(self, key)
+                    }
+                },
+                parse_quote! { &'a #key },
+                quote! {
+                    let item = key;
+                },
+            )
+        }
+    };
+
+    let mut extern_c_mod_items = vec![ForeignItem::Verbatim(quote! {
+        type #iter_type;
+    })];
+    extern_c_mod_items.extend(bridge_fns.into_iter().map(|mut f| {
+        f.sig.inputs = unqualify_params(f.sig.inputs);
+        f.sig.output = unqualify_ret_type(f.sig.output);
+        ForeignItem::Fn(f)
+    }));
+
+    let bindgen_mod_items = vec![
+        Item::Impl(parse_quote! {
+            impl #id {
+                /// Returns the number of elements in the container.
+                pub fn len(&self) -> usize {
+                    cxxbridge::#len(self)
+                }
+                /// Whether the container is empty.
+                pub fn is_empty(&self) -> bool {
+                    self.len() == 0
+                }
+                /// Whether the container contains this key.
+                pub fn contains(&self, key: #key_param) -> bool {
+                    cxxbridge::#contains(self, key)
+                }
+                /// Iterates over the contents of the container, in the order
+                /// that C++ stores them.
+                pub fn iter(&self) -> #iterator_struct<'_> {
+                    #iterator_struct {
+                        container: self,
+                        iter: cxxbridge::#begin(self),
+                    }
+                }
+                #extra_methods
+            }
+        }),
+        Item::Struct(parse_quote! {
+            /// Iterator over the contents of a C++ associative container.
+            #[allow(non_camel_case_types)]
+            pub struct #iterator_struct<'a> {
+                container: &'a #id,
+                iter: cxx::UniquePtr<cxxbridge::#iter_type>,
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl<'a> Iterator for #iterator_struct<'a> {
+                type Item = #item_type;
+                fn next(&mut self) -> Option<Self::Item> {
+                    let key = unsafe { cxxbridge::#iter_key(self.container, &self.iter).as_ref() }?;
+                    #make_item
+                    cxxbridge::#iter_advance(self.iter.pin_mut());
+                    Some(item)
+                }
+            }
+        }),
+    ];
+    (extern_c_mod_items, bindgen_mod_items)
+}
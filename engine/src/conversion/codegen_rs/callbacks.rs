@@ -0,0 +1,66 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::format_ident;
+use syn::{parse_quote, ForeignItem, ForeignItemFn, Ident, Item};
+
+use crate::known_types::CallbackSignature;
+
+use super::unqualify::{unqualify_params, unqualify_ret_type};
+
+/// Generates the Rust side of a `std::function` which can be constructed
+/// from a Rust closure. The closure is held in a Rust type which C++
+/// receives in a `rust::Box`, and C++ calls it via an `extern "Rust"`
+/// trampoline function. The C++ side is generated by `codegen_cpp`
+/// and `function_wrapper_cpp`. Copies of a `std::function` share the
+/// closure and may be called from any thread, so it must be `Fn`,
+/// `Send` and `Sync`.
+/// Returns items for the `extern "Rust"` section of the bridge, global
+/// items, and items for the bindgen mod.
+pub(super) fn generate_closure_adapter(
+    id: &Ident,
+    signature: &CallbackSignature,
+) -> (Vec<ForeignItem>, Vec<Item>, Vec<Item>) {
+    let function = id.to_string();
+    let closure = format_ident!("{}", CallbackSignature::closure_name(&function));
+    let trampoline = format_ident!("{}", CallbackSignature::trampoline_name(&function));
+    let inputs: Vec<_> = signature.inputs().collect();
+    let output = &signature.0.output;
+    let args: Vec<_> = (0..inputs.len())
+        .map(|i| format_ident!("autocxx_arg{}", i))
+        .collect();
+    let trampoline_decl: ForeignItemFn = parse_quote! {
+        fn #trampoline(closure: &#closure, #(#args: #inputs),*) #output;
+    };
+    let mut trampoline_decl_unqualified = trampoline_decl.clone();
+    trampoline_decl_unqualified.sig.inputs = unqualify_params(trampoline_decl.sig.inputs);
+    trampoline_decl_unqualified.sig.output = unqualify_ret_type(trampoline_decl.sig.output);
+    let extern_rust_mod_items = vec![
+        parse_quote! {
+            type #closure;
+        },
+        ForeignItem::Fn(trampoline_decl_unqualified),
+    ];
+    let global_items = vec![
+        parse_quote! {
+            pub use bindgen::root::#closure;
+        },
+        parse_quote! {
+            #[allow(non_snake_case)]
+            fn #trampoline(closure: &#closure, #(#args: #inputs),*) #output {
+                (closure.0)(#(#args),*)
+            }
+        },
+    ];
+    let bindgen_mod_items = vec![parse_quote! {
+        /// Holds a Rust closure such that C++ can call it as a `std::function`.
+        #[allow(non_camel_case_types)]
+        pub struct #closure(pub Box<dyn Fn(#(#inputs),*) #output + Send + Sync>);
+    }];
+    (extern_rust_mod_items, global_items, bindgen_mod_items)
+}
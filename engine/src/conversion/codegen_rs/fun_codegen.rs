@@ -6,7 +6,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::collections::HashSet;
+use std::{borrow::Cow, collections::HashSet};
 
 use proc_macro2::TokenStream;
 use quote::quote;
@@ -15,7 +15,7 @@ use syn::{
     parse_quote,
     punctuated::Punctuated,
     token::{Comma, Unsafe},
-    Attribute, FnArg, ForeignItem, Ident, ImplItem, Item, ReturnType,
+    Attribute, FnArg, ForeignItem, ForeignItemFn, Ident, ImplItem, Item, ReturnType, Type,
 };
 
 use super::{
@@ -25,6 +25,9 @@ use super::{
 use crate::{
     conversion::{
         analysis::fun::{
+            function_wrapper::{
+                StatusConversion, SuccessCheck, TupleElement, TypeConversionPolicy,
+            },
             ArgumentAnalysis, FnAnalysis, FnKind, MethodKind, RustRenameStrategy,
             TraitMethodDetails,
         },
@@ -92,6 +95,7 @@ pub(super) fn gen_function(
     let cxxbridge_name = analysis.cxxbridge_name;
     let rust_name = &analysis.rust_name;
     let ret_type = analysis.ret_type;
+    let ret_conversion = analysis.ret_conversion;
     let param_details = analysis.param_details;
     let wrapper_function_needed = analysis.cpp_wrapper.is_some();
     let params = analysis.params;
@@ -115,10 +119,25 @@ pub(super) fn gen_function(
         always_unsafe_due_to_trait_definition,
         doc_attrs: &doc_attrs,
         non_pod_types,
+        ret_conversion: &ret_conversion,
+        throws: analysis.throws,
+        lifetime_source: analysis.lifetime_source.as_ref(),
     };
     // In rare occasions, we might need to give an explicit lifetime.
-    let (lifetime_tokens, params, ret_type) =
-        add_explicit_lifetime_if_necessary(&param_details, params, &ret_type, non_pod_types);
+    let (lifetime_tokens, params, ret_type) = add_explicit_lifetime_if_necessary(
+        &param_details,
+        params,
+        &ret_type,
+        non_pod_types,
+        analysis.lifetime_source.as_ref(),
+    );
+    let wrapper_ret_type = match ret_conversion
+        .as_ref()
+        .and_then(|conversion| conversion.rust_wrapper_return_type())
+    {
+        Some(ty) => Cow::Owned(parse_quote! { -> #ty }),
+        None => ret_type.clone(),
+    };
 
     if analysis.rust_wrapper_needed {
         match kind {
@@ -142,15 +161,16 @@ pub(super) fn gen_function(
                         MethodKind::MakeUnique | MethodKind::Constructor { .. }
                     ),
                     impl_for,
-                    &ret_type,
+                    &wrapper_ret_type,
                 ));
             }
             FnKind::TraitMethod { ref details, .. } => {
-                trait_impl_entry = Some(fn_generator.generate_trait_impl(details, &ret_type));
+                trait_impl_entry =
+                    Some(fn_generator.generate_trait_impl(details, &wrapper_ret_type));
             }
             _ => {
                 // Generate plain old function
-                bindgen_mod_items.push(fn_generator.generate_function_impl(&ret_type));
+                bindgen_mod_items.push(fn_generator.generate_function_impl(&wrapper_ret_type));
             }
         }
     }
@@ -184,7 +204,14 @@ pub(super) fn gen_function(
     // and the following code will act to unqualify only those types
     // which the user has declared.
     let params = unqualify_params(params);
-    let ret_type = unqualify_ret_type(ret_type.into_owned());
+    let ret_type = if analysis.throws {
+        // cxx catches any exception in its own C++ shim, and returns it
+        // to us as a `cxx::Exception`.
+        ret_type_as_result(&ret_type, quote! {})
+    } else {
+        ret_type.into_owned()
+    };
+    let ret_type = unqualify_ret_type(ret_type);
     // And we need to make an attribute for the namespace that the function
     // itself is in.
     let namespace_attr = if ns.is_empty() || wrapper_function_needed {
@@ -205,8 +232,13 @@ pub(super) fn gen_function(
         #(#doc_attrs)*
         #vis #bridge_unsafety fn #cxxbridge_name #lifetime_tokens ( #params ) #ret_type;
     ));
+    let mut extern_c_mod_items = vec![extern_c_mod_item];
+    if let Some(ref conversion) = ret_conversion {
+        extern_c_mod_items.extend(generate_tuple_accessors(&cxxbridge_name, conversion));
+        extern_c_mod_items.extend(generate_status_checker(&cxxbridge_name, conversion));
+    }
     RsCodegenResult {
-        extern_c_mod_items: vec![extern_c_mod_item],
+        extern_c_mod_items,
         bindgen_mod_items,
         impl_entry,
         trait_impl_entry,
@@ -215,6 +247,70 @@ pub(super) fn gen_function(
     }
 }
 
+/// Turns `-> T` into `-> Result<T>` for a function which may throw a C++
+/// exception. `error_type` is appended to the generic arguments of `Result`.
+fn ret_type_as_result(ret_type: &ReturnType, error_type: TokenStream) -> ReturnType {
+    let ty: Type = match ret_type {
+        ReturnType::Default => parse_quote! { () },
+        ReturnType::Type(_, ty) => ty.as_ref().clone(),
+    };
+    parse_quote! {
+        -> Result< #ty #error_type >
+    }
+}
+
+/// If a function returns a `std::pair` or `std::tuple` which we're turning
+/// into a Rust tuple, generates the cxx::bridge declarations of the C++ functions
+/// which extract each element.
+fn generate_tuple_accessors(
+    cxxbridge_name: &Ident,
+    conversion: &TypeConversionPolicy,
+) -> Vec<ForeignItem> {
+    let tuple_type = &conversion.unwrapped_type;
+    conversion
+        .tuple_elements()
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(idx, element)| {
+            let accessor_name = TupleElement::accessor_name(cxxbridge_name, idx);
+            let ty = &element.ty;
+            let ret_type: ReturnType = if element.by_value {
+                parse_quote! { -> #ty }
+            } else {
+                parse_quote! { -> cxx::UniquePtr<#ty> }
+            };
+            let mut accessor: ForeignItemFn = parse_quote! {
+                fn #accessor_name(tuple: ::std::pin::Pin<&mut #tuple_type>) #ret_type;
+            };
+            accessor.sig.inputs = unqualify_params(accessor.sig.inputs);
+            accessor.sig.output = unqualify_ret_type(accessor.sig.output);
+            ForeignItem::Fn(accessor)
+        })
+        .collect()
+}
+
+/// If a function returns a non-POD status type which we're turning into a
+/// `Result`, generates the cxx::bridge declaration of the C++ function which
+/// checks it for success.
+fn generate_status_checker(
+    cxxbridge_name: &Ident,
+    conversion: &TypeConversionPolicy,
+) -> Option<ForeignItem> {
+    match conversion.status_conversion()?.check {
+        SuccessCheck::OkIf(_) => {
+            let checker_name = StatusConversion::checker_name(cxxbridge_name);
+            let status_type = &conversion.unwrapped_type;
+            let mut checker: ForeignItemFn = parse_quote! {
+                fn #checker_name(status: &#status_type) -> bool;
+            };
+            checker.sig.inputs = unqualify_params(checker.sig.inputs);
+            Some(ForeignItem::Fn(checker))
+        }
+        SuccessCheck::OkWhen(_) => None,
+    }
+}
+
 /// Knows how to generate a given function.
 #[derive(Clone)]
 struct FnGenerator<'a> {
@@ -225,6 +321,9 @@ struct FnGenerator<'a> {
     always_unsafe_due_to_trait_definition: bool,
     doc_attrs: &'a Vec<Attribute>,
     non_pod_types: &'a HashSet<QualifiedName>,
+    ret_conversion: &'a Option<TypeConversionPolicy>,
+    throws: bool,
+    lifetime_source: Option<&'a Ident>,
 }
 
 impl<'a> FnGenerator<'a> {
@@ -237,16 +336,18 @@ impl<'a> FnGenerator<'a> {
         let mut arg_list = Vec::new();
         let wrap_unsafe_calls = self.should_wrap_unsafe_calls();
         for pd in self.param_details {
-            let type_name = pd.conversion.rust_wrapper_unconverted_type();
             let wrapper_arg_name = if pd.self_type.is_some() && !avoid_self {
                 parse_quote!(self)
             } else {
                 pd.name.clone()
             };
-            let param_mutability = pd.conversion.rust_conversion.requires_mutability();
-            wrapper_params.push(parse_quote!(
-                #param_mutability #wrapper_arg_name: #type_name
-            ));
+            if pd.conversion.rust_conversion.is_wrapper_param() {
+                let type_name = pd.conversion.rust_wrapper_unconverted_type();
+                let param_mutability = pd.conversion.rust_conversion.requires_mutability();
+                wrapper_params.push(parse_quote!(
+                    #param_mutability #wrapper_arg_name: #type_name
+                ));
+            }
             let (local_variable, actual_arg) = pd
                 .conversion
                 .rust_conversion(wrapper_arg_name, wrap_unsafe_calls);
@@ -270,14 +371,16 @@ impl<'a> FnGenerator<'a> {
             wrapper_params,
             ret_type,
             self.non_pod_types,
+            self.lifetime_source,
         );
+        let ret_type = self.wrapper_ret_type(ret_type);
         let rust_name = make_ident(self.rust_name);
         let unsafety = self.unsafety.wrapper_token();
         let doc_attrs = self.doc_attrs;
         let cxxbridge_name = self.cxxbridge_name;
-        let call_body = self.wrap_call_with_unsafe(quote! {
+        let call_body = self.wrap_call_with_unsafe(self.convert_return(quote! {
             cxxbridge::#cxxbridge_name ( #(#arg_list),* )
-        });
+        }));
         Box::new(ImplBlockDetails {
             item: ImplItem::Method(parse_quote! {
                 #(#doc_attrs)*
@@ -306,15 +409,17 @@ impl<'a> FnGenerator<'a> {
             wrapper_params,
             ret_type,
             self.non_pod_types,
+            self.lifetime_source,
         );
+        let ret_type = self.wrapper_ret_type(ret_type);
         let doc_attrs = self.doc_attrs;
         let unsafety = self.unsafety.wrapper_token();
         let cxxbridge_name = self.cxxbridge_name;
         let key = details.trt.clone();
         let method_name = &details.method_name;
-        let call_body = self.wrap_call_with_unsafe(quote! {
+        let call_body = self.wrap_call_with_unsafe(self.convert_return(quote! {
             cxxbridge::#cxxbridge_name ( #(#arg_list),* )
-        });
+        }));
         let item = parse_quote! {
             #(#doc_attrs)*
             #unsafety fn #method_name #lifetime_tokens ( #wrapper_params ) #ret_type {
@@ -325,6 +430,33 @@ impl<'a> FnGenerator<'a> {
         Box::new(TraitImplBlockDetails { item, key })
     }
 
+    fn convert_return(&self, call: TokenStream) -> TokenStream {
+        match self.ret_conversion {
+            Some(conversion) if self.throws && conversion.rust_work_needed() => {
+                let converted =
+                    conversion.rust_return_conversion(self.cxxbridge_name, quote! { #call? });
+                quote! {
+                    Ok({ #converted })
+                }
+            }
+            Some(conversion) => conversion.rust_return_conversion(self.cxxbridge_name, call),
+            None => call,
+        }
+    }
+
+    /// The return type of a Rust wrapper function, which is a `Result`
+    /// if the C++ function may throw.
+    fn wrapper_ret_type<'r>(&self, ret_type: Cow<'r, ReturnType>) -> Cow<'r, ReturnType> {
+        if self.throws {
+            Cow::Owned(ret_type_as_result(
+                &ret_type,
+                quote! { , autocxx::CppException },
+            ))
+        } else {
+            ret_type
+        }
+    }
+
     fn should_wrap_unsafe_calls(&self) -> bool {
         matches!(self.unsafety, UnsafetyNeeded::JustBridge)
             || self.always_unsafe_due_to_trait_definition
@@ -385,13 +517,14 @@ impl<'a> FnGenerator<'a> {
     /// Generate a function call wrapper
     fn generate_function_impl(&self, ret_type: &ReturnType) -> Item {
         let (wrapper_params, local_variables, arg_list) = self.generate_arg_lists(false);
+        let ret_type = self.wrapper_ret_type(Cow::Borrowed(ret_type));
         let rust_name = make_ident(self.rust_name);
         let doc_attrs = self.doc_attrs;
         let unsafety = self.unsafety.wrapper_token();
         let cxxbridge_name = self.cxxbridge_name;
-        let body = self.wrap_call_with_unsafe(quote! {
+        let body = self.wrap_call_with_unsafe(self.convert_return(quote! {
             cxxbridge::#cxxbridge_name ( #(#arg_list),* )
-        });
+        }));
         Item::Fn(parse_quote! {
             #(#doc_attrs)*
             pub #unsafety fn #rust_name ( #wrapper_params ) #ret_type {
@@ -7,10 +7,12 @@
 // except according to those terms.
 
 use proc_macro2::TokenStream;
-use syn::{Pat, Type, TypePtr};
+use syn::{Ident, Pat, Type, TypePtr};
 
 use crate::{
-    conversion::analysis::fun::function_wrapper::{RustConversionType, TypeConversionPolicy},
+    conversion::analysis::fun::function_wrapper::{
+        RustConversionType, StatusConversion, SuccessCheck, TupleElement, TypeConversionPolicy,
+    },
     types::make_ident,
 };
 use quote::quote;
@@ -26,6 +28,12 @@ impl TypeConversionPolicy {
                     super::super::super:: #id>
                 }
             }
+            RustConversionType::ToBoxedUpThreadSafeHolder(ref sub) => {
+                let id = sub.id();
+                parse_quote! { autocxx::subclass::CppSubclassRustPeerHolderThreadSafe<
+                    super::super::super:: #id>
+                }
+            }
             RustConversionType::FromStr => parse_quote! { impl ToCppString },
             RustConversionType::FromPinMaybeUninitToPtr => {
                 let ty = match &self.unwrapped_type {
@@ -56,6 +64,151 @@ impl TypeConversionPolicy {
                 let ty = &self.unwrapped_type;
                 parse_quote! { impl autocxx::ValueParam<#ty> }
             }
+            RustConversionType::FromSliceToPtr => match &self.unwrapped_type {
+                Type::Ptr(TypePtr {
+                    elem, mutability, ..
+                }) => parse_quote! { & #mutability [#elem] },
+                _ => panic!("Not a ptr"),
+            },
+            RustConversionType::FromSliceToLength(_) => {
+                panic!("Slice lengths are not parameters of the wrapper function")
+            }
+            RustConversionType::FromReferenceToPtr => self.pointee_reference_type(),
+            RustConversionType::FromOptionalReferenceToPtr => {
+                let ty = self.pointee_reference_type();
+                parse_quote! { Option< #ty > }
+            }
+            RustConversionType::FromCStrToPtr => parse_quote! { &::std::ffi::CStr },
+            RustConversionType::FromUniquePtrToTuple(_)
+            | RustConversionType::FromStatusToResult(_)
+            | RustConversionType::FromPtrToOptionalReference => {
+                panic!("Tuples, status codes and downcasts are only supported as return types")
+            }
+            RustConversionType::FromOutParamToPtr => {
+                panic!("Out-parameters are not parameters of the wrapper function")
+            }
+            RustConversionType::FromFunctionPointerToUsize => self.unwrapped_type.clone(),
+            RustConversionType::FromClosureToBox(ref signature) => {
+                let inputs = signature.inputs();
+                let output = &signature.0.output;
+                parse_quote! {
+                    impl Fn( #(#inputs),* ) #output + Send + Sync + 'static
+                }
+            }
+        }
+    }
+
+    /// The reference type which we accept in place of a pointer
+    /// which is known not to be null.
+    fn pointee_reference_type(&self) -> Type {
+        match &self.unwrapped_type {
+            Type::Ptr(TypePtr {
+                elem,
+                mutability: Some(_),
+                ..
+            }) => parse_quote! { ::std::pin::Pin<&mut #elem> },
+            Type::Ptr(TypePtr { elem, .. }) => parse_quote! { & #elem },
+            _ => panic!("Not a ptr"),
+        }
+    }
+
+    /// The type returned by the Rust wrapper function, if it differs from
+    /// that returned by the cxx::bridge function.
+    pub(super) fn rust_wrapper_return_type(&self) -> Option<Type> {
+        if matches!(
+            self.rust_conversion,
+            RustConversionType::FromPtrToOptionalReference
+        ) {
+            let ty = self.pointee_reference_type();
+            return Some(parse_quote! { Option< #ty > });
+        }
+        if let Some(status) = self.status_conversion() {
+            let status_type = self.unconverted_rust_type();
+            let ok_type = match &status.out {
+                Some((_, ty)) => quote! { #ty },
+                None => quote! { () },
+            };
+            return Some(parse_quote! {
+                Result< #ok_type, #status_type >
+            });
+        }
+        self.tuple_elements().map(|elements| {
+            let element_types = elements.iter().map(|element| {
+                let ty = &element.ty;
+                if element.by_value {
+                    quote! { #ty }
+                } else {
+                    quote! { cxx::UniquePtr<#ty> }
+                }
+            });
+            parse_quote! {
+                ( #(#element_types),* )
+            }
+        })
+    }
+
+    /// Converts the return value of the cxx::bridge function into that
+    /// returned by the Rust wrapper function.
+    pub(super) fn rust_return_conversion(
+        &self,
+        wrapper_function_name: &Ident,
+        call: TokenStream,
+    ) -> TokenStream {
+        if matches!(
+            self.rust_conversion,
+            RustConversionType::FromPtrToOptionalReference
+        ) {
+            // C++ returns a pointer to the same object, or null.
+            return match &self.unwrapped_type {
+                Type::Ptr(TypePtr {
+                    mutability: Some(_),
+                    ..
+                }) => quote! {
+                    unsafe { #call.as_mut().map(|r| ::std::pin::Pin::new_unchecked(r)) }
+                },
+                _ => quote! {
+                    unsafe { #call.as_ref() }
+                },
+            };
+        }
+        if let Some(status) = self.status_conversion() {
+            let is_ok = match &status.check {
+                SuccessCheck::OkWhen(ok_when) => quote! { autocxx_status == #ok_when },
+                SuccessCheck::OkIf(_) => {
+                    let checker = StatusConversion::checker_name(wrapper_function_name);
+                    // A null status can't tell us we succeeded.
+                    quote! {
+                        matches!(autocxx_status.as_ref(), Some(status) if cxxbridge::#checker(status))
+                    }
+                }
+            };
+            // The out-parameter was passed as a pointer to uninitialized
+            // memory, which the C++ function promises to fill on success.
+            // That's why a wrapper with an out-parameter is an unsafe
+            // function.
+            let ok_value = match &status.out {
+                Some((out, _)) => quote! { #out.assume_init() },
+                None => quote! { () },
+            };
+            return quote! {
+                let autocxx_status = #call;
+                if #is_ok {
+                    Ok(#ok_value)
+                } else {
+                    Err(autocxx_status)
+                }
+            };
+        }
+        match self.tuple_elements() {
+            Some(elements) => {
+                let accessors = (0..elements.len())
+                    .map(|idx| TupleElement::accessor_name(wrapper_function_name, idx));
+                quote! {
+                    let mut autocxx_tuple = #call;
+                    ( #(cxxbridge::#accessors(autocxx_tuple.pin_mut())),* )
+                }
+            }
+            None => call,
         }
     }
 
@@ -67,7 +220,8 @@ impl TypeConversionPolicy {
         match self.rust_conversion {
             RustConversionType::None => (None, quote! { #var }),
             RustConversionType::FromStr => (None, quote! ( #var .into_cpp() )),
-            RustConversionType::ToBoxedUpHolder(ref sub) => {
+            RustConversionType::ToBoxedUpHolder(ref sub)
+            | RustConversionType::ToBoxedUpThreadSafeHolder(ref sub) => {
                 let holder_type = sub.holder();
                 (
                     None,
@@ -128,6 +282,62 @@ impl TypeConversionPolicy {
                     },
                 )
             }
+            RustConversionType::FromSliceToPtr => match &self.unwrapped_type {
+                Type::Ptr(TypePtr {
+                    mutability: Some(_),
+                    ..
+                }) => (None, quote! { #var.as_mut_ptr() }),
+                _ => (None, quote! { #var.as_ptr() }),
+            },
+            RustConversionType::FromSliceToLength(ref slice_var) => {
+                (None, quote! { #slice_var.len() })
+            }
+            RustConversionType::FromReferenceToPtr => match &self.unwrapped_type {
+                Type::Ptr(TypePtr {
+                    mutability: Some(_),
+                    ..
+                }) => (None, quote! { ::std::pin::Pin::into_inner_unchecked(#var) }),
+                _ => (None, quote! { #var }),
+            },
+            RustConversionType::FromOptionalReferenceToPtr => match &self.unwrapped_type {
+                Type::Ptr(TypePtr {
+                    mutability: Some(_),
+                    ..
+                }) => (
+                    None,
+                    quote! {
+                        #var.map_or(::std::ptr::null_mut(), |r| ::std::pin::Pin::into_inner_unchecked(r) as *mut _)
+                    },
+                ),
+                _ => (
+                    None,
+                    quote! {
+                        #var.map_or(::std::ptr::null(), |r| r as *const _)
+                    },
+                ),
+            },
+            RustConversionType::FromCStrToPtr => (None, quote! { #var.as_ptr() }),
+            RustConversionType::FromUniquePtrToTuple(_)
+            | RustConversionType::FromStatusToResult(_)
+            | RustConversionType::FromPtrToOptionalReference => {
+                panic!("Tuples, status codes and downcasts are only supported as return types")
+            }
+            RustConversionType::FromOutParamToPtr => (
+                Some(quote! {
+                    let mut #var = ::std::mem::MaybeUninit::uninit();
+                }),
+                quote! { #var.as_mut_ptr() },
+            ),
+            RustConversionType::FromFunctionPointerToUsize => (None, quote! { #var as usize }),
+            RustConversionType::FromClosureToBox(_) => {
+                let closure = self.callback_closure_type();
+                (
+                    None,
+                    quote! {
+                        Box::new(#closure(Box::new(#var)))
+                    },
+                )
+            }
         }
     }
 }
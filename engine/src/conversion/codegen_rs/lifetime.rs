@@ -13,8 +13,8 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use std::{borrow::Cow, collections::HashSet};
 use syn::{
-    parse_quote, punctuated::Punctuated, token::Comma, FnArg, GenericArgument, PatType, Path,
-    PathSegment, ReturnType, Type, TypePath, TypeReference,
+    parse_quote, punctuated::Punctuated, token::Comma, FnArg, GenericArgument, Ident, Pat, PatType,
+    Path, PathSegment, ReturnType, Type, TypePath, TypeReference,
 };
 
 /// Function which can add explicit lifetime parameters to function signatures
@@ -26,11 +26,16 @@ use syn::{
 /// 2) as a workaround for https://github.com/dtolnay/cxx/issues/1024, where the
 ///    input parameter is a non-POD type but the output reference is a POD or
 ///    built-in type
+///
+/// Additionally, where a `lifetime!` directive has told us which parameter
+/// the return value borrows from, we tie the lifetimes of just that parameter
+/// and the return type together.
 pub(crate) fn add_explicit_lifetime_if_necessary<'r>(
     param_details: &[ArgumentAnalysis],
     mut params: Punctuated<FnArg, Comma>,
     ret_type: &'r ReturnType,
     non_pod_types: &HashSet<QualifiedName>,
+    lifetime_source: Option<&Ident>,
 ) -> (
     Option<TokenStream>,
     Punctuated<FnArg, Comma>,
@@ -43,7 +48,7 @@ pub(crate) fn add_explicit_lifetime_if_necessary<'r>(
     let non_pod_ref_param = reference_parameter_is_non_pod_reference(&params, non_pod_types);
     let ret_type_pod = return_type_is_pod_or_known_type_reference(ret_type, non_pod_types);
     let hits_1024_bug = non_pod_ref_param && ret_type_pod;
-    if !(has_mutable_receiver || hits_1024_bug) {
+    if !(has_mutable_receiver || hits_1024_bug || lifetime_source.is_some()) {
         return (None, params, Cow::Borrowed(ret_type));
     }
     let new_return_type = match ret_type {
@@ -59,6 +64,7 @@ pub(crate) fn add_explicit_lifetime_if_necessary<'r>(
             Type::Path(typ) => {
                 let mut new_path = typ.clone();
                 add_lifetime_to_pinned_reference(&mut new_path.path.segments)
+                    .or_else(|_| add_lifetime_to_optional_reference(&mut new_path.path.segments))
                     .ok()
                     .map(|_| ReturnType::Type(*rarrow, Box::new(Type::Path(new_path))))
             }
@@ -70,7 +76,10 @@ pub(crate) fn add_explicit_lifetime_if_necessary<'r>(
     match new_return_type {
         None => (None, params, Cow::Borrowed(ret_type)),
         Some(new_return_type) => {
-            for mut param in params.iter_mut() {
+            for mut param in params
+                .iter_mut()
+                .filter(|param| shares_return_lifetime(param, lifetime_source))
+            {
                 match &mut param {
                     FnArg::Typed(PatType { ty, .. }) => match ty.as_mut() {
                         Type::Path(TypePath {
@@ -89,6 +98,18 @@ pub(crate) fn add_explicit_lifetime_if_necessary<'r>(
     }
 }
 
+/// Whether this parameter should share the lifetime of the return type.
+/// Without a `lifetime!` directive, that's all of them.
+fn shares_return_lifetime(param: &FnArg, lifetime_source: Option<&Ident>) -> bool {
+    match (param, lifetime_source) {
+        (_, None) => true,
+        (FnArg::Typed(PatType { pat, .. }), Some(source)) => {
+            matches!(pat.as_ref(), Pat::Ident(pp) if pp.ident == *source)
+        }
+        _ => false,
+    }
+}
+
 fn reference_parameter_is_non_pod_reference(
     params: &Punctuated<FnArg, Comma>,
     non_pod_types: &HashSet<QualifiedName>,
@@ -160,6 +181,29 @@ fn add_lifetime_to_pinned_reference(
     Ok(())
 }
 
+/// Adds a lifetime to an `Option<&T>` or `Option<Pin<&mut T>>`.
+fn add_lifetime_to_optional_reference(
+    segments: &mut Punctuated<PathSegment, syn::token::Colon2>,
+) -> Result<(), AddLifetimeError> {
+    let last_seg = segments.last_mut().unwrap();
+    if last_seg.ident != "Option" {
+        return Err(AddLifetimeError::WasNotPin);
+    }
+    match &mut last_seg.arguments {
+        syn::PathArguments::AngleBracketed(aba) => match aba.args.iter_mut().next() {
+            Some(GenericArgument::Type(Type::Reference(tyr))) => {
+                add_lifetime_to_reference(tyr);
+                Ok(())
+            }
+            Some(GenericArgument::Type(Type::Path(typ))) => {
+                add_lifetime_to_pinned_reference(&mut typ.path.segments)
+            }
+            _ => Err(AddLifetimeError::WasNotPin),
+        },
+        _ => Err(AddLifetimeError::WasNotPin),
+    }
+}
+
 fn add_lifetime_to_reference(tyr: &mut syn::TypeReference) {
     tyr.lifetime = Some(parse_quote! { 'a })
 }
@@ -0,0 +1,168 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, FnArg, Item};
+
+use crate::{
+    conversion::{
+        analysis::fun::ReceiverMutability,
+        api::{SubclassName, SuperclassMethod},
+    },
+    types::{make_ident, QualifiedName},
+};
+
+/// Generates the Rust side of a mock subclass requested by `mock!`. This
+/// plays the part of the struct which the user would otherwise write and
+/// annotate with `#[subclass]`, and implements each pure virtual method
+/// by calling a closure supplied by the test, counting the calls.
+/// We don't get here for superclasses with pure virtual methods which
+/// return references, which closures can't implement.
+pub(super) fn generate_mock(
+    sub: &SubclassName,
+    superclass: &QualifiedName,
+    methods: &[SuperclassMethod],
+) -> Vec<Item> {
+    let id = sub.id();
+    let cpp_id = sub.cpp().get_final_ident();
+    let super_id = superclass.get_final_ident();
+    let methods_trait = SubclassName::get_methods_trait_name(superclass).to_type_path();
+    let mut fields: Vec<TokenStream> = Vec::new();
+    let mut inherent_fns: Vec<TokenStream> = Vec::new();
+    let mut trait_fns: Vec<TokenStream> = Vec::new();
+    for method in methods.iter().filter(|m| m.is_pure_virtual) {
+        let name = &method.name;
+        let mut params = method.params.clone();
+        *(params.iter_mut().next().unwrap()) = match method.receiver_mutability {
+            ReceiverMutability::Const => parse_quote!(&self),
+            ReceiverMutability::Mutable => parse_quote!(&mut self),
+        };
+        let (arg_names, arg_types): (Vec<_>, Vec<_>) = method
+            .params
+            .iter()
+            .skip(1)
+            .filter_map(|param| match param {
+                FnArg::Typed(pt) => Some((pt.pat.clone(), pt.ty.clone())),
+                FnArg::Receiver(_) => None,
+            })
+            .unzip();
+        let ret = &method.ret_type;
+        let unsafe_token = method.requires_unsafe.wrapper_token();
+        let expectation = make_ident(format!("{}_expectation", name));
+        let calls = make_ident(format!("{}_calls", name));
+        let expect_fn = make_ident(format!("expect_{}", name));
+        fields.push(quote! {
+            #expectation: ::std::cell::RefCell<Option<Box<dyn FnMut(#(#arg_types),*) #ret>>>
+        });
+        fields.push(quote! {
+            #calls: ::std::cell::Cell<usize>
+        });
+        let expect_doc = format!(
+            "Sets what happens when C++ calls `{}`. Without this, such calls panic.",
+            name
+        );
+        let calls_doc = format!("The number of times C++ has called `{}`.", name);
+        inherent_fns.push(quote! {
+            #[doc = #expect_doc]
+            pub fn #expect_fn(&mut self, f: impl FnMut(#(#arg_types),*) #ret + 'static) {
+                *self.#expectation.borrow_mut() = Some(Box::new(f));
+            }
+            #[doc = #calls_doc]
+            pub fn #calls(&self) -> usize {
+                self.#calls.get()
+            }
+        });
+        let unexpected_msg = format!("{}: unexpected call to {}", id, name);
+        trait_fns.push(quote! {
+            #unsafe_token fn #name(#params) #ret {
+                self.#calls.set(self.#calls.get() + 1);
+                match self.#expectation.borrow_mut().as_mut() {
+                    Some(f) => f(#(#arg_names),*),
+                    None => panic!(#unexpected_msg),
+                }
+            }
+        });
+    }
+    let struct_doc = format!(
+        "A mock implementation of the C++ class `{}`, generated by `mock!`.",
+        superclass.to_cpp_name()
+    );
+    let with_cpp_doc = format!(
+        "Calls `f` with this mock as a C++ `{}`, for example to pass to the C++ code under \
+        test. Unlike `pin_mut`, this doesn't keep the mock borrowed while `f` runs, so C++ \
+        can call into the mock meanwhile. Panics if the mock is borrowed, or if called again \
+        from within `f`.\n\n\
+        # Safety\n\n\
+        While `f` runs, C++ holds a mutable reference to the peer. Neither `f` nor \
+        anything it calls may obtain another reference to the same C++ object, for \
+        instance through `pin_mut`, `as_ref`, `peer` or `peer_mut` on this mock.",
+        superclass.to_cpp_name()
+    );
+    let lent_msg = format!("{} is already in use by C++", id);
+    vec![
+        parse_quote! {
+            #[doc = #struct_doc]
+            #[derive(Default)]
+            #[allow(non_snake_case)]
+            pub struct #id {
+                cpp_peer: autocxx::subclass::CppSubclassCppPeerHolder<#cpp_id>,
+                cpp_lent: ::std::cell::Cell<bool>,
+                #(#fields,)*
+            }
+        },
+        parse_quote! {
+            impl autocxx::subclass::CppSubclass<#cpp_id> for #id {
+                fn peer_holder_mut(&mut self) -> &mut autocxx::subclass::CppSubclassCppPeerHolder<#cpp_id> {
+                    &mut self.cpp_peer
+                }
+                fn peer_holder(&self) -> &autocxx::subclass::CppSubclassCppPeerHolder<#cpp_id> {
+                    &self.cpp_peer
+                }
+            }
+        },
+        parse_quote! {
+            #[allow(non_snake_case)]
+            impl #id {
+                #(#inherent_fns)*
+                #[doc = #with_cpp_doc]
+                pub unsafe fn with_cpp<R>(
+                    this: &::std::rc::Rc<::std::cell::RefCell<Self>>,
+                    f: impl FnOnce(::std::pin::Pin<&mut cxxbridge::#super_id>) -> R,
+                ) -> R {
+                    // Gives the mock back even if `f` panics.
+                    struct Lent<'a>(&'a ::std::rc::Rc<::std::cell::RefCell<#id>>);
+                    impl Drop for Lent<'_> {
+                        fn drop(&mut self) {
+                            self.0.borrow().cpp_lent.set(false);
+                        }
+                    }
+                    let peer: *mut cxxbridge::#super_id = {
+                        let mut me = this.borrow_mut();
+                        assert!(!me.cpp_lent.replace(true), #lent_msg);
+                        // We don't move the C++ peer out of this pointer.
+                        me.pin_mut().get_unchecked_mut()
+                    };
+                    let _lent = Lent(this);
+                    // `this` keeps the mock, and so its C++ peer, alive until
+                    // `f` returns, and `cpp_lent` stops `with_cpp` handing out
+                    // another such reference meanwhile. Our caller promises
+                    // not to make any other reference. C++ never moves the
+                    // peer in memory.
+                    f(::std::pin::Pin::new_unchecked(&mut *peer))
+                }
+            }
+        },
+        parse_quote! {
+            #[allow(non_snake_case)]
+            impl #methods_trait for #id {
+                #(#trait_fns)*
+            }
+        },
+    ]
+}
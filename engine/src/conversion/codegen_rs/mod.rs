@@ -6,23 +6,27 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod associative_containers;
+mod callbacks;
 mod fun_codegen;
 mod function_wrapper_rs;
 mod impl_item_creator;
 mod lifetime;
+mod mocks;
 mod namespace_organizer;
 mod non_pod_struct;
+mod rust_traits;
 pub(crate) mod unqualify;
 
 use std::collections::{HashMap, HashSet};
 
-use autocxx_parser::{IncludeCppConfig, RustFun};
+use autocxx_parser::{IncludeCppConfig, RustFun, SubclassPanicPolicy};
 
 use itertools::Itertools;
 use proc_macro2::{Span, TokenStream};
 use syn::{
     parse_quote, punctuated::Punctuated, token::Comma, Attribute, Expr, FnArg, ForeignItem,
-    ForeignItemFn, Ident, ImplItem, Item, ItemForeignMod, ItemMod, TraitItem,
+    ForeignItemFn, Ident, ImplItem, Item, ItemForeignMod, ItemMod, ReturnType, TraitItem, TypePath,
 };
 
 use crate::{
@@ -33,13 +37,17 @@ use crate::{
         },
         doc_attr::get_doc_attrs,
     },
+    known_types::known_types,
     types::{make_ident, Namespace, QualifiedName},
 };
 use impl_item_creator::create_impl_items;
 
 use self::{
+    associative_containers::generate_associative_container_shims,
+    callbacks::generate_closure_adapter,
     fun_codegen::gen_function,
     namespace_organizer::{HasNs, NamespaceEntries},
+    rust_traits::generate_rust_trait_adapter,
 };
 
 use super::{
@@ -129,6 +137,73 @@ fn get_string_items() -> Vec<Item> {
     .to_vec()
 }
 
+fn get_utf_string_items(
+    id: &Ident,
+    code_unit: &Ident,
+    make_name: &Ident,
+    code_units_name: &Ident,
+) -> Vec<Item> {
+    let trait_name = make_ident(format!("ToCpp{}", id.to_string().trim_start_matches("Cxx")));
+    let method_name = make_ident(format!(
+        "into_cpp_{}",
+        id.to_string().trim_start_matches("Cxx").to_lowercase()
+    ));
+    let decode: Expr = if code_unit == "u16" {
+        parse_quote! { String::from_utf16_lossy(&code_units) }
+    } else {
+        parse_quote! {
+            code_units
+                .into_iter()
+                .map(|c| char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+    };
+    let encode: Expr = if code_unit == "u16" {
+        parse_quote! { self.encode_utf16().collect::<Vec<_>>() }
+    } else {
+        parse_quote! { self.chars().map(|c| c as u32).collect::<Vec<_>>() }
+    };
+    [
+        Item::Trait(parse_quote! {
+            pub trait #trait_name {
+                fn #method_name(self) -> cxx::UniquePtr<#id>;
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl #trait_name for &str {
+                fn #method_name(self) -> cxx::UniquePtr<#id> {
+                    cxxbridge::#make_name(&#encode)
+                }
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl #trait_name for String {
+                fn #method_name(self) -> cxx::UniquePtr<#id> {
+                    self.as_str().#method_name()
+                }
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl #trait_name for &String {
+                fn #method_name(self) -> cxx::UniquePtr<#id> {
+                    self.as_str().#method_name()
+                }
+            }
+        }),
+        Item::Impl(parse_quote! {
+            impl #id {
+                /// Converts to a Rust string, replacing any invalid
+                /// code units with the Unicode replacement character.
+                pub fn to_string_lossy(&self) -> String {
+                    let code_units = cxxbridge::#code_units_name(self);
+                    #decode
+                }
+            }
+        }),
+    ]
+    .to_vec()
+}
+
 /// Type which handles generation of Rust code.
 /// In practice, much of the "generation" involves connecting together
 /// existing lumps of code within the Api structures.
@@ -267,11 +342,12 @@ impl<'a> RsCodeGenerator<'a> {
         apis: &ApiVec<FnPhase>,
     ) -> HashMap<QualifiedName, Vec<SuperclassMethod>> {
         let mut results = HashMap::new();
-        results.extend(
-            self.config
-                .superclasses()
-                .map(|sc| (QualifiedName::new_from_cpp_name(sc), Vec::new())),
-        );
+        results.extend(self.config.superclasses().map(|sc| {
+            (
+                QualifiedName::new_from_cpp_name(&self.config.superclass_name(sc)),
+                Vec::new(),
+            )
+        }));
         for api in apis.iter() {
             if let Api::SubclassTraitItem { details, .. } = api {
                 let list = results.get_mut(&details.receiver);
@@ -456,6 +532,32 @@ impl<'a> RsCodeGenerator<'a> {
         output_items
     }
 
+    fn generate_utf_string(&self, id: &Ident, bits: usize) -> RsCodegenResult {
+        let code_unit = make_ident(format!("u{}", bits));
+        let id_str = id.to_string();
+        let make_name = make_ident(self.config.get_utf_string_helper_name("make", &id_str));
+        let code_units_name = make_ident(
+            self.config
+                .get_utf_string_helper_name("code_units", &id_str),
+        );
+        RsCodegenResult {
+            extern_c_mod_items: vec![
+                ForeignItem::Verbatim(quote! {
+                    type #id;
+                }),
+                ForeignItem::Fn(parse_quote! {
+                    fn #make_name(code_units: &[#code_unit]) -> UniquePtr<#id>;
+                }),
+                ForeignItem::Fn(parse_quote! {
+                    fn #code_units_name(str: &#id) -> Vec<#code_unit>;
+                }),
+            ],
+            global_items: get_utf_string_items(id, &code_unit, &make_name, &code_units_name),
+            materializations: vec![Use::UsedFromCxxBridge],
+            ..Default::default()
+        }
+    }
+
     fn generate_rs_for_api(
         &self,
         api: Api<FnPhase>,
@@ -529,7 +631,7 @@ impl<'a> RsCodeGenerator<'a> {
                     None,
                 )
             }
-            Api::ForwardDeclaration { .. } | Api::ConcreteType { .. } => self.generate_type(
+            Api::ForwardDeclaration { .. } => self.generate_type(
                 &name,
                 id,
                 TypeKind::Abstract,
@@ -539,12 +641,49 @@ impl<'a> RsCodeGenerator<'a> {
                 associated_methods,
                 None,
             ),
-            Api::CType { .. } => RsCodegenResult {
-                extern_c_mod_items: vec![ForeignItem::Verbatim(quote! {
-                    type #id = autocxx::#id;
-                })],
-                ..Default::default()
-            },
+            Api::ConcreteType { rs_definition, .. } => {
+                let container_details = rs_definition
+                    .as_ref()
+                    .and_then(|ty| known_types().associative_container_details(ty));
+                let mut result = self.generate_type(
+                    &name,
+                    id.clone(),
+                    TypeKind::Abstract,
+                    false, // assume for now that these types can't be kept in a Vector
+                    true,  // assume for now that these types can be put in a smart pointer
+                    || None,
+                    associated_methods,
+                    None,
+                );
+                if let Some(details) = container_details {
+                    let (extern_c_mod_items, bindgen_mod_items) =
+                        generate_associative_container_shims(&id, &details);
+                    result.extern_c_mod_items.extend(extern_c_mod_items);
+                    result.bindgen_mod_items.extend(bindgen_mod_items);
+                }
+                if let Some(signature) = rs_definition
+                    .as_ref()
+                    .and_then(|ty| known_types().callback_signature(ty))
+                {
+                    let (extern_rust_mod_items, global_items, bindgen_mod_items) =
+                        generate_closure_adapter(&id, &signature);
+                    result.extern_rust_mod_items.extend(extern_rust_mod_items);
+                    result.global_items.extend(global_items);
+                    result.bindgen_mod_items.extend(bindgen_mod_items);
+                }
+                result
+            }
+            Api::CType { typename, .. } => {
+                match known_types().utf_string_code_unit_bits(&typename) {
+                    Some(bits) => self.generate_utf_string(&id, bits),
+                    None => RsCodegenResult {
+                        extern_c_mod_items: vec![ForeignItem::Verbatim(quote! {
+                            type #id = autocxx::#id;
+                        })],
+                        ..Default::default()
+                    },
+                }
+            }
             Api::RustType { path, .. } => RsCodegenResult {
                 global_items: vec![parse_quote! {
                     use super::#path;
@@ -586,16 +725,43 @@ impl<'a> RsCodeGenerator<'a> {
                 }],
                 ..Default::default()
             },
+            Api::RustTrait { path, methods, .. } => {
+                let mut result = self.generate_type(
+                    &name,
+                    id.clone(),
+                    TypeKind::Abstract,
+                    false, // C++ owns the Rust object, so this can't be moved
+                    true,
+                    || None,
+                    associated_methods,
+                    None,
+                );
+                let (extern_rust_mod_items, extern_c_mod_items, global_items, bindgen_mod_items) =
+                    generate_rust_trait_adapter(&id, &path, &methods);
+                result.extern_rust_mod_items.extend(extern_rust_mod_items);
+                result.extern_c_mod_items.extend(extern_c_mod_items);
+                result.global_items.extend(global_items);
+                result.bindgen_mod_items.extend(bindgen_mod_items);
+                result
+            }
             Api::RustSubclassFn {
                 details, subclass, ..
-            } => Self::generate_subclass_fn(id, *details, subclass),
+            } => {
+                let thread_safe = self.config.is_thread_safe_subclass(&subclass.id());
+                let panic_policy = self.config.get_subclass_panic_policy(&subclass.id());
+                Self::generate_subclass_fn(id, *details, subclass, thread_safe, panic_policy)
+            }
             Api::Subclass {
-                name, superclass, ..
+                name, superclasses, ..
             } => {
-                let methods = associated_methods.get(&superclass);
                 let generate_peer_constructor =
                     subclasses_with_a_single_trivial_constructor.contains(&name.0.name);
-                self.generate_subclass(name, &superclass, methods, generate_peer_constructor)
+                self.generate_subclass(
+                    name,
+                    &superclasses,
+                    associated_methods,
+                    generate_peer_constructor,
+                )
             }
             Api::IgnoredItem {
                 err,
@@ -609,13 +775,10 @@ impl<'a> RsCodeGenerator<'a> {
     fn generate_subclass(
         &self,
         sub: SubclassName,
-        superclass: &QualifiedName,
-        methods: Option<&Vec<SuperclassMethod>>,
+        superclasses: &[QualifiedName],
+        associated_methods: &HashMap<QualifiedName, Vec<SuperclassMethod>>,
         generate_peer_constructor: bool,
     ) -> RsCodegenResult {
-        let super_name = superclass.get_final_item();
-        let super_path = superclass.to_type_path();
-        let super_cxxxbridge_id = superclass.get_final_ident();
         let id = sub.id();
         let holder = sub.holder();
         let full_cpp = sub.cpp();
@@ -626,12 +789,41 @@ impl<'a> RsCodeGenerator<'a> {
             pub use bindgen::root::#holder;
         });
         let relinquish_ownership_call = sub.cpp_remove_ownership();
+        let is_mock = self.config.is_mock(&id);
+        // The user's subclass is alongside the include_cpp! macro, but
+        // we generate mocks alongside the holder.
+        let rust_subclass: TypePath = if is_mock {
+            parse_quote! { #id }
+        } else {
+            parse_quote! { super::super::super::#id }
+        };
+        let thread_safe = self.config.is_thread_safe_subclass(&id);
+        let (rust_peer_holder, subclass_trait, peer_constructor_trait, default_trait) =
+            if thread_safe {
+                (
+                    "CppSubclassRustPeerHolderThreadSafe",
+                    "CppSubclassThreadSafe",
+                    "CppPeerConstructorThreadSafe",
+                    "CppSubclassThreadSafeDefault",
+                )
+            } else {
+                (
+                    "CppSubclassRustPeerHolder",
+                    "CppSubclass",
+                    "CppPeerConstructor",
+                    "CppSubclassDefault",
+                )
+            };
+        let rust_peer_holder = make_ident(rust_peer_holder);
+        let subclass_trait = make_ident(subclass_trait);
+        let peer_constructor_trait = make_ident(peer_constructor_trait);
+        let default_trait = make_ident(default_trait);
         let mut bindgen_mod_items = vec![
             parse_quote! {
                 pub use cxxbridge::#cpp_id;
             },
             parse_quote! {
-                pub struct #holder(pub autocxx::subclass::CppSubclassRustPeerHolder<super::super::super::#id>);
+                pub struct #holder(pub autocxx::subclass::#rust_peer_holder<#rust_subclass>);
             },
             parse_quote! {
                 impl autocxx::subclass::CppSubclassCppPeer for #cpp_id {
@@ -641,84 +833,118 @@ impl<'a> RsCodeGenerator<'a> {
                 }
             },
         ];
+        if thread_safe {
+            bindgen_mod_items.push(parse_quote! {
+                unsafe impl autocxx::subclass::CppSubclassCppPeerThreadSafe for #cpp_id {}
+            });
+        }
         let mut extern_c_mod_items = vec![
             self.generate_cxxbridge_type(&full_cpp, false, Vec::new()),
             parse_quote! {
                 fn #relinquish_ownership_call(self: &#cpp_id);
             },
         ];
-        if let Some(methods) = methods {
-            let supers = SubclassName::get_supers_trait_name(superclass).to_type_path();
-            let methods_impls: Vec<ImplItem> = methods
-                .iter()
-                .filter(|m| !m.is_pure_virtual)
-                .map(|m| {
-                    let cpp_super_method_name =
-                        SubclassName::get_super_fn_name(&Namespace::new(), &m.name.to_string())
-                            .get_final_ident();
-                    let mut params = m.params.clone();
-                    let ret = &m.ret_type.clone();
-                    let (peer_fn, first_param) = match m.receiver_mutability {
-                        ReceiverMutability::Const => ("peer", parse_quote!(&self)),
-                        ReceiverMutability::Mutable => ("peer_mut", parse_quote!(&mut self)),
-                    };
-                    let peer_fn = make_ident(peer_fn);
-                    *(params.iter_mut().next().unwrap()) = first_param;
-                    let param_names = m.param_names.iter().skip(1);
-                    let unsafe_token = m.requires_unsafe.wrapper_token();
-                    parse_quote! {
-                        #unsafe_token fn #cpp_super_method_name(#params) #ret {
-                            use autocxx::subclass::CppSubclass;
-                            self.#peer_fn().#cpp_super_method_name(#(#param_names),*)
+        for superclass in superclasses {
+            if let Some(methods) = associated_methods.get(superclass) {
+                let supers = SubclassName::get_supers_trait_name(superclass).to_type_path();
+                let methods_impls: Vec<ImplItem> = methods
+                    .iter()
+                    .filter(|m| !m.is_pure_virtual)
+                    .map(|m| {
+                        let cpp_super_method_name =
+                            SubclassName::get_super_fn_name(&Namespace::new(), &m.name.to_string())
+                                .get_final_ident();
+                        let mut params = m.params.clone();
+                        let ret = &m.ret_type.clone();
+                        let (peer_fn, first_param) = match m.receiver_mutability {
+                            ReceiverMutability::Const => ("peer", parse_quote!(&self)),
+                            ReceiverMutability::Mutable => ("peer_mut", parse_quote!(&mut self)),
+                        };
+                        let peer_fn = make_ident(peer_fn);
+                        *(params.iter_mut().next().unwrap()) = first_param;
+                        let param_names = m.param_names.iter().skip(1);
+                        let unsafe_token = m.requires_unsafe.wrapper_token();
+                        parse_quote! {
+                            #unsafe_token fn #cpp_super_method_name(#params) #ret {
+                                use autocxx::subclass::#subclass_trait;
+                                self.#peer_fn().#cpp_super_method_name(#(#param_names),*)
+                            }
                         }
-                    }
-                })
-                .collect();
-            if !methods_impls.is_empty() {
-                bindgen_mod_items.push(parse_quote! {
-                    #[allow(non_snake_case)]
-                    impl #supers for super::super::super::#id {
-                        #(#methods_impls)*
-                    }
-                });
+                    })
+                    .collect();
+                if !methods_impls.is_empty() {
+                    bindgen_mod_items.push(parse_quote! {
+                        #[allow(non_snake_case)]
+                        impl #supers for #rust_subclass {
+                            #(#methods_impls)*
+                        }
+                    });
+                }
             }
         }
         if generate_peer_constructor {
             bindgen_mod_items.push(parse_quote! {
-                impl autocxx::subclass::CppPeerConstructor<#cpp_id> for super::super::super::#id {
-                    fn make_peer(&mut self, peer_holder: autocxx::subclass::CppSubclassRustPeerHolder<Self>) -> cxx::UniquePtr<#cpp_path> {
+                impl autocxx::subclass::#peer_constructor_trait<#cpp_id> for #rust_subclass {
+                    fn make_peer(&mut self, peer_holder: autocxx::subclass::#rust_peer_holder<Self>) -> cxx::UniquePtr<#cpp_path> {
                         #cpp_id :: make_unique(peer_holder)
                     }
                 }
             })
         };
 
-        // Once for each superclass, in future...
-        let as_id = make_ident(format!("As_{}", super_name));
-        extern_c_mod_items.push(parse_quote! {
-            fn #as_id(self: &#cpp_id) -> &#super_cxxxbridge_id;
-        });
-        let as_mut_id = make_ident(format!("As_{}_mut", super_name));
-        extern_c_mod_items.push(parse_quote! {
-            fn #as_mut_id(self: Pin<&mut #cpp_id>) -> Pin<&mut #super_cxxxbridge_id>;
-        });
-        bindgen_mod_items.push(parse_quote! {
-            impl AsRef<#super_path> for super::super::super::#id {
-                fn as_ref(&self) -> &cxxbridge::#super_cxxxbridge_id {
-                    use autocxx::subclass::CppSubclass;
-                    self.peer().#as_id()
+        for (idx, superclass) in superclasses.iter().enumerate() {
+            let super_name = superclass.get_final_item();
+            let super_path = superclass.to_type_path();
+            let super_cxxxbridge_id = superclass.get_final_ident();
+            let as_id = make_ident(format!("As_{}", super_name));
+            extern_c_mod_items.push(parse_quote! {
+                fn #as_id(self: &#cpp_id) -> &#super_cxxxbridge_id;
+            });
+            let as_mut_id = make_ident(format!("As_{}_mut", super_name));
+            extern_c_mod_items.push(parse_quote! {
+                fn #as_mut_id(self: Pin<&mut #cpp_id>) -> Pin<&mut #super_cxxxbridge_id>;
+            });
+            bindgen_mod_items.push(parse_quote! {
+                impl AsRef<#super_path> for #rust_subclass {
+                    fn as_ref(&self) -> &cxxbridge::#super_cxxxbridge_id {
+                        use autocxx::subclass::#subclass_trait;
+                        self.peer().#as_id()
+                    }
                 }
-            }
-        });
-        // TODO it would be nice to impl AsMut here but pin prevents us
-        bindgen_mod_items.push(parse_quote! {
-            impl super::super::super::#id {
-                pub fn pin_mut(&mut self) -> ::std::pin::Pin<&mut cxxbridge::#super_cxxxbridge_id> {
-                    use autocxx::subclass::CppSubclass;
-                    self.peer_mut().#as_mut_id()
+            });
+            bindgen_mod_items.push(parse_quote! {
+                impl autocxx::PinMut<#super_path> for #rust_subclass {
+                    fn pin_mut(&mut self) -> ::std::pin::Pin<&mut cxxbridge::#super_cxxxbridge_id> {
+                        use autocxx::subclass::#subclass_trait;
+                        self.peer_mut().#as_mut_id()
+                    }
                 }
+            });
+            // For the first superclass, we also offer an inherent method
+            // so callers needn't say which superclass they mean.
+            if idx == 0 {
+                bindgen_mod_items.push(parse_quote! {
+                    impl #rust_subclass {
+                        pub fn pin_mut(&mut self) -> ::std::pin::Pin<&mut cxxbridge::#super_cxxxbridge_id> {
+                            use autocxx::subclass::#subclass_trait;
+                            self.peer_mut().#as_mut_id()
+                        }
+                    }
+                });
             }
-        });
+        }
+        let mut materializations = vec![Use::Custom(Box::new(parse_quote! {
+            pub use cxxbridge::#cpp_id;
+        }))];
+        if is_mock {
+            let superclass = &superclasses[0];
+            let methods = associated_methods
+                .get(superclass)
+                .map(|methods| methods.as_slice())
+                .unwrap_or_default();
+            bindgen_mod_items.extend(mocks::generate_mock(&sub, superclass, methods));
+            materializations.push(Use::SpecificNameFromBindgen(id.clone()));
+        }
         let remove_ownership = sub.remove_ownership();
         global_items.push(parse_quote! {
             #[allow(non_snake_case)]
@@ -726,6 +952,46 @@ impl<'a> RsCodeGenerator<'a> {
                 Box::new(#holder(me.0.relinquish_ownership()))
             }
         });
+        let on_cpp_destroy = sub.on_cpp_destroy();
+        let notify_call = if self.config.subclass_has_cpp_destroy_hook(&id) {
+            quote! { me.0.notify_cpp_peer_destroyed() }
+        } else {
+            quote! { me.0.cpp_peer_destroyed() }
+        };
+        global_items.push(parse_quote! {
+            #[allow(non_snake_case)]
+            pub fn #on_cpp_destroy(me: &#holder) {
+                #notify_call
+            }
+        });
+        let mut extern_rust_mod_items = vec![
+            parse_quote! {
+                pub type #holder;
+            },
+            parse_quote! {
+                fn #remove_ownership(me: Box<#holder>) -> Box<#holder>;
+            },
+            parse_quote! {
+                fn #on_cpp_destroy(me: &#holder);
+            },
+        ];
+        if self.config.is_factory_subclass(&id) {
+            // Called by the C++ make_rust_subclass registry.
+            let make_default = sub.make_default();
+            bindgen_mod_items.push(parse_quote! {
+                #[allow(non_snake_case)]
+                pub fn #make_default() -> cxx::UniquePtr<#cpp_id> {
+                    use autocxx::subclass::#default_trait;
+                    <#rust_subclass as #default_trait<#cpp_id>>::default_cpp_owned()
+                }
+            });
+            global_items.push(parse_quote! {
+                use bindgen::root::#make_default;
+            });
+            extern_rust_mod_items.push(parse_quote! {
+                fn #make_default() -> UniquePtr<#cpp_id>;
+            });
+        }
         RsCodegenResult {
             extern_c_mod_items,
             // For now we just assume we can't keep subclasses in vectors, but we can put them in
@@ -733,18 +999,9 @@ impl<'a> RsCodeGenerator<'a> {
             // That's the reason for the 'false' and 'true'
             bridge_items: create_impl_items(&cpp_id, false, true, self.config),
             bindgen_mod_items,
-            materializations: vec![Use::Custom(Box::new(parse_quote! {
-                pub use cxxbridge::#cpp_id;
-            }))],
+            materializations,
             global_items,
-            extern_rust_mod_items: vec![
-                parse_quote! {
-                    pub type #holder;
-                },
-                parse_quote! {
-                    fn #remove_ownership(me: Box<#holder>) -> Box<#holder>;
-                },
-            ],
+            extern_rust_mod_items,
             ..Default::default()
         }
     }
@@ -753,13 +1010,27 @@ impl<'a> RsCodeGenerator<'a> {
         api_name: Ident,
         details: RustSubclassFnDetails,
         subclass: SubclassName,
+        thread_safe: bool,
+        panic_policy: SubclassPanicPolicy,
     ) -> RsCodegenResult {
         let params = details.params;
-        let ret = details.ret;
+        let mut ret = details.ret;
+        let mut bridge_ret = unqualify_ret_type(ret.clone());
+        if matches!(panic_policy, SubclassPanicPolicy::Exception) {
+            // cxx turns an Err into a C++ exception.
+            let (ret_ty, bridge_ret_ty) = match (&ret, &bridge_ret) {
+                (ReturnType::Type(_, ret_ty), ReturnType::Type(_, bridge_ret_ty)) => {
+                    (quote! { #ret_ty }, quote! { #bridge_ret_ty })
+                }
+                _ => (quote! { () }, quote! { () }),
+            };
+            ret = parse_quote! { -> ::std::result::Result<#ret_ty, String> };
+            bridge_ret = parse_quote! { -> Result<#bridge_ret_ty> };
+        }
         let unsafe_token = details.requires_unsafe.wrapper_token();
         let global_def = quote! { #unsafe_token fn #api_name(#params) #ret };
         let params = unqualify_params(params);
-        let ret = unqualify_ret_type(ret);
+        let ret = bridge_ret;
         let method_name = details.method_name;
         let cxxbridge_decl: ForeignItemFn =
             parse_quote! { #unsafe_token fn #api_name(#params) #ret; };
@@ -769,11 +1040,20 @@ impl<'a> RsCodeGenerator<'a> {
         let methods_trait = SubclassName::get_methods_trait_name(&details.superclass);
         let methods_trait = methods_trait.to_type_path();
         let (deref_ty, deref_call, borrow, mut_token) = match details.receiver_mutability {
-            ReceiverMutability::Const => ("Deref", "deref", "try_borrow", None),
+            ReceiverMutability::Const => (
+                "Deref",
+                "deref",
+                if thread_safe { "read" } else { "try_borrow" },
+                None,
+            ),
             ReceiverMutability::Mutable => (
                 "DerefMut",
                 "deref_mut",
-                "try_borrow_mut",
+                if thread_safe {
+                    "write"
+                } else {
+                    "try_borrow_mut"
+                },
                 Some(syn::token::Mut(Span::call_site())),
             ),
         };
@@ -781,21 +1061,51 @@ impl<'a> RsCodeGenerator<'a> {
         let deref_call = make_ident(deref_call);
         let borrow = make_ident(borrow);
         let destroy_panic_msg = format!("Rust subclass API (method {} of subclass {} of superclass {}) called after subclass destroyed", method_name, subclass.0.name, superclass_id);
-        let reentrancy_panic_msg = format!("Rust subclass API (method {} of subclass {} of superclass {}) called whilst subclass already borrowed - likely a re-entrant call",  method_name, subclass.0.name, superclass_id);
+        let borrow_panic_msg = if thread_safe {
+            format!("Rust subclass API (method {} of subclass {} of superclass {}) called after an earlier call panicked", method_name, subclass.0.name, superclass_id)
+        } else {
+            format!("Rust subclass API (method {} of subclass {} of superclass {}) called whilst subclass already borrowed - likely a re-entrant call",  method_name, subclass.0.name, superclass_id)
+        };
+        // Panics mustn't unwind into C++, so we catch them and do whatever
+        // the subclass asked.
+        let panicking_method = format!(
+            "Rust subclass API (method {} of subclass {} of superclass {})",
+            method_name, subclass.0.name, superclass_id
+        );
+        let (on_success, on_panic) = match panic_policy {
+            SubclassPanicPolicy::Abort => (
+                quote! { r },
+                quote! { autocxx::subclass::abort_after_subclass_panic(#panicking_method, payload) },
+            ),
+            SubclassPanicPolicy::Exception => (
+                quote! { Ok(r) },
+                quote! { Err(autocxx::subclass::describe_subclass_panic(#panicking_method, payload)) },
+            ),
+            SubclassPanicPolicy::ReturnDefault => (
+                quote! { r },
+                quote! { { drop(payload); Default::default() } },
+            ),
+        };
         RsCodegenResult {
             global_items: vec![parse_quote! {
                 #global_def {
-                    let rc = me.0
-                        .get()
-                        .expect(#destroy_panic_msg);
-                    let #mut_token b = rc
-                        .as_ref()
-                        .#borrow()
-                        .expect(#reentrancy_panic_msg);
-                    let r = std::ops::#deref_ty::#deref_call(& #mut_token b);
-                    #methods_trait :: #method_name
-                        (r,
-                        #args)
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        let rc = me.0
+                            .get()
+                            .expect(#destroy_panic_msg);
+                        let #mut_token b = rc
+                            .as_ref()
+                            .#borrow()
+                            .expect(#borrow_panic_msg);
+                        let r = std::ops::#deref_ty::#deref_call(& #mut_token b);
+                        #methods_trait :: #method_name
+                            (r,
+                            #args)
+                    }));
+                    match result {
+                        Ok(r) => #on_success,
+                        Err(payload) => #on_panic,
+                    }
                 }
             }],
             extern_rust_mod_items: vec![ForeignItem::Fn(cxxbridge_decl)],
@@ -901,6 +1211,7 @@ impl<'a> RsCodeGenerator<'a> {
         methods: Option<&Vec<SuperclassMethod>>,
     ) {
         if let Some(methods) = methods {
+            let cpp_name = name.to_cpp_name();
             let (supers, mains): (Vec<_>, Vec<_>) = methods
                 .iter()
                 .map(|method| {
@@ -918,17 +1229,32 @@ impl<'a> RsCodeGenerator<'a> {
                     let ret_type = &method.ret_type;
                     let unsafe_token = method.requires_unsafe.wrapper_token();
                     if method.is_pure_virtual {
+                        let doc = format!(
+                            "Implements the pure virtual C++ method `{}::{}`. Every Rust subclass must implement this.",
+                            cpp_name, id
+                        );
                         (
                             None,
                             parse_quote!(
+                                #[doc = #doc]
                                 #unsafe_token fn #id(#params) #ret_type;
                             ),
                         )
                     } else {
+                        let super_doc = format!(
+                            "Calls the superclass implementation of the C++ method `{}::{}`.",
+                            cpp_name, id
+                        );
+                        let doc = format!(
+                            "Overrides the virtual C++ method `{}::{}`. If you don't implement this, it calls `{}`, the superclass implementation.",
+                            cpp_name, id, super_id
+                        );
                         let a: Option<TraitItem> = Some(parse_quote!(
+                            #[doc = #super_doc]
                             #unsafe_token fn #super_id(#params) #ret_type;
                         ));
                         let b: TraitItem = parse_quote!(
+                            #[doc = #doc]
                             #unsafe_token fn #id(#params) #ret_type {
                                 self.#super_id(#param_names)
                             }
@@ -940,14 +1266,21 @@ impl<'a> RsCodeGenerator<'a> {
             let supers: Vec<_> = supers.into_iter().flatten().collect();
             let supers_name = SubclassName::get_supers_trait_name(name).get_final_ident();
             let methods_name = SubclassName::get_methods_trait_name(name).get_final_ident();
+            let methods_doc = Self::overridable_methods_doc(&cpp_name, methods);
             if !supers.is_empty() {
+                let supers_doc = format!(
+                    "The superclass implementations of the non-pure virtual methods of `{}`, which Rust subclasses may call.",
+                    cpp_name
+                );
                 bindgen_mod_items.push(parse_quote! {
+                    #[doc = #supers_doc]
                     #[allow(non_snake_case)]
                     pub trait #supers_name {
                         #(#supers)*
                     }
                 });
                 bindgen_mod_items.push(parse_quote! {
+                    #[doc = #methods_doc]
                     #[allow(non_snake_case)]
                     pub trait #methods_name : #supers_name {
                         #(#mains)*
@@ -956,6 +1289,7 @@ impl<'a> RsCodeGenerator<'a> {
                 materializations.push(Use::SpecificNameFromBindgen(supers_name));
             } else {
                 bindgen_mod_items.push(parse_quote! {
+                    #[doc = #methods_doc]
                     #[allow(non_snake_case)]
                     pub trait #methods_name {
                         #(#mains)*
@@ -966,6 +1300,37 @@ impl<'a> RsCodeGenerator<'a> {
         }
     }
 
+    /// Documents the `_methods` trait for a superclass, listing which
+    /// methods a Rust subclass must implement and which it may override.
+    fn overridable_methods_doc(cpp_name: &str, methods: &[SuperclassMethod]) -> String {
+        let list = |pure_virtual: bool| {
+            methods
+                .iter()
+                .filter(|m| m.is_pure_virtual == pure_virtual)
+                .map(|m| format!("* `{}`", m.name))
+                .join("\n")
+        };
+        let mut doc = format!(
+            "The virtual methods of the C++ class `{}`, which Rust subclasses implement.",
+            cpp_name
+        );
+        let required = list(true);
+        if !required.is_empty() {
+            doc.push_str(&format!(
+                "\n\nPure virtual methods, which every subclass must implement:\n{}",
+                required
+            ));
+        }
+        let optional = list(false);
+        if !optional.is_empty() {
+            doc.push_str(&format!(
+                "\n\nOther virtual methods, which a subclass may override, and which otherwise call the superclass implementation:\n{}",
+                optional
+            ));
+        }
+        doc
+    }
+
     /// Generates something in the output mod that will carry a docstring
     /// explaining why a given type or function couldn't have bindings
     /// generated.
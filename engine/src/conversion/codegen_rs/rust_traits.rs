@@ -0,0 +1,89 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::RustPath;
+use quote::quote;
+use syn::{parse_quote, ForeignItem, Ident, Item};
+
+use crate::conversion::{
+    analysis::fun::ReceiverMutability,
+    api::{RustTraitMethod, RustTraitNames},
+};
+
+/// Generates the Rust side of a Rust trait which is exposed to C++ as an
+/// abstract class. A `Box<dyn Trait>` is wrapped in a holder type which
+/// C++ owns via a `rust::Box`, and the C++ implementation of each pure
+/// virtual method calls an `extern "Rust"` trampoline function. The C++
+/// side is generated by `codegen_cpp`.
+/// Returns items for the `extern "Rust"` and `extern "C++"` sections
+/// of the bridge, global items, and items for the bindgen mod.
+pub(super) fn generate_rust_trait_adapter(
+    id: &Ident,
+    path: &RustPath,
+    methods: &[RustTraitMethod],
+) -> (Vec<ForeignItem>, Vec<ForeignItem>, Vec<Item>, Vec<Item>) {
+    let names = RustTraitNames::new(id);
+    let holder = names.holder();
+    let wrapper_fn = names.wrapper_fn();
+    let mut extern_rust_mod_items = vec![parse_quote! {
+        type #holder;
+    }];
+    let mut global_items = vec![parse_quote! {
+        pub use bindgen::root::#holder;
+    }];
+    for method in methods {
+        let name = &method.name;
+        let trampoline = names.trampoline(name);
+        let (arg_names, arg_types): (Vec<_>, Vec<_>) = method.params.iter().cloned().unzip();
+        let ret = &method.ret;
+        let (holder_ref, obj) = match method.receiver_mutability {
+            ReceiverMutability::Const => (quote! { & #holder }, quote! { &*holder.0 }),
+            ReceiverMutability::Mutable => (quote! { &mut #holder }, quote! { &mut *holder.0 }),
+        };
+        extern_rust_mod_items.push(parse_quote! {
+            fn #trampoline(holder: #holder_ref, #(#arg_names: #arg_types),*) #ret;
+        });
+        global_items.push(parse_quote! {
+            #[allow(non_snake_case)]
+            fn #trampoline(holder: #holder_ref, #(#arg_names: #arg_types),*) #ret {
+                super::#path::#name(#obj, #(#arg_names),*)
+            }
+        });
+    }
+    let extern_c_mod_items = vec![parse_quote! {
+        fn #wrapper_fn(holder: Box<#holder>) -> UniquePtr<#id>;
+    }];
+    let holder_doc = format!(
+        "Holds a Rust implementation of `{}` such that C++ can call it.",
+        id
+    );
+    let from_rust_doc = format!(
+        "Wraps a Rust implementation of the `{}` trait in a C++ object, which can be passed to C++ functions expecting a `{}`.",
+        id, id
+    );
+    let bindgen_mod_items = vec![
+        parse_quote! {
+            #[doc = #holder_doc]
+            pub struct #holder(pub Box<dyn super::super::super::#path>);
+        },
+        parse_quote! {
+            impl #id {
+                #[doc = #from_rust_doc]
+                pub fn from_rust(obj: Box<dyn super::super::super::#path>) -> cxx::UniquePtr<#id> {
+                    cxxbridge::#wrapper_fn(Box::new(#holder(obj)))
+                }
+            }
+        },
+    ];
+    (
+        extern_rust_mod_items,
+        extern_c_mod_items,
+        global_items,
+        bindgen_mod_items,
+    )
+}
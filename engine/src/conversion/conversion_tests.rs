@@ -12,6 +12,7 @@ use syn::parse_quote;
 use syn::ItemMod;
 
 use crate::CppCodegenOptions;
+use autocxx_clang_facts::ClangFacts;
 
 use super::BridgeConverter;
 
@@ -33,6 +34,7 @@ fn do_test(input: ItemMod) {
         input,
         UnsafePolicy::AllFunctionsSafe,
         inclusions,
+        &ClangFacts::default(),
         &CppCodegenOptions::default(),
     )
     .unwrap();
@@ -61,6 +61,18 @@ pub enum ConvertError {
     MethodOfGenericType,
     DuplicateItemsFoundInParsing,
     ConstructorWithOnlyOneParam,
+    InvalidSliceParam(String),
+    InvalidResultOf(String),
+    InvalidPointerParam(String),
+    InvalidLifetimeParam(String),
+    InvalidOwnershipTransfer(String),
+    InvalidDefaultParams(String),
+    VariadicFunction,
+    InvalidVariadicCall(String),
+    SpanOfNonPodType(QualifiedName),
+    UnsupportedCallback(String),
+    UnsupportedRustTrait(String),
+    UnmockableMethod(String),
 }
 
 fn format_maybe_identifier(id: &Option<Ident>) -> String {
@@ -115,6 +127,18 @@ impl Display for ConvertError {
             ConvertError::MethodOfGenericType => write!(f, "This type is templated, so we can't generate bindings. We will instead generate bindings for each instantiation.")?,
             ConvertError::DuplicateItemsFoundInParsing => write!(f, "bindgen generated multiple different APIs (functions/types) with this name. autocxx doesn't know how to diambiguate them, so we won't generate bindings for any of them.")?,
             ConvertError::ConstructorWithOnlyOneParam => write!(f, "bindgen generated a move or copy constructor with an unexpected number of parameters.")?,
+            ConvertError::InvalidSliceParam(desc) => write!(f, "A slice_param! directive could not be applied to this function: {}", desc)?,
+            ConvertError::InvalidResultOf(desc) => write!(f, "A result_of! directive could not be applied to this function: {}", desc)?,
+            ConvertError::InvalidPointerParam(desc) => write!(f, "A nonnull! or nullable! directive could not be applied to this function: {}", desc)?,
+            ConvertError::InvalidLifetimeParam(desc) => write!(f, "A lifetime! directive could not be applied to this function: {}", desc)?,
+            ConvertError::InvalidOwnershipTransfer(desc) => write!(f, "A returns_owned! or takes_ownership! directive could not be applied to this function: {}", desc)?,
+            ConvertError::InvalidDefaultParams(desc) => write!(f, "A defaults! directive could not be applied to this function: {}", desc)?,
+            ConvertError::VariadicFunction => write!(f, "This is a C-style variadic function. Use variadic! to generate a fixed-arity form which can be called from Rust.")?,
+            ConvertError::InvalidVariadicCall(desc) => write!(f, "A variadic! directive could not be applied to this function: {}", desc)?,
+            ConvertError::SpanOfNonPodType(tn) => write!(f, "This function uses a span of {}, but spans are only supported for POD element types.", tn.to_cpp_name())?,
+            ConvertError::UnsupportedCallback(desc) => write!(f, "This uses a function pointer or std::function which autocxx can't yet handle: {}", desc)?,
+            ConvertError::UnsupportedRustTrait(desc) => write!(f, "This Rust trait can't be exposed to C++ as an abstract class: {}", desc)?,
+            ConvertError::UnmockableMethod(method) => write!(f, "mock! can't generate this mock, because its pure virtual method {} returns a reference, and a closure can't return a reference which lives as long as the mock.", method)?,
         }
         Ok(())
     }
@@ -124,6 +124,15 @@ pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
                 details,
                 receiver,
             }))),
+            Api::RustTrait {
+                name,
+                path,
+                methods,
+            } => Ok(Box::new(std::iter::once(Api::RustTrait {
+                name,
+                path,
+                methods,
+            }))),
             Api::RustSubclassFn {
                 name,
                 subclass,
@@ -133,9 +142,9 @@ pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
                 subclass,
                 details,
             }))),
-            Api::Subclass { name, superclass } => Ok(Box::new(std::iter::once(Api::Subclass {
+            Api::Subclass { name, superclasses } => Ok(Box::new(std::iter::once(Api::Subclass {
                 name,
-                superclass,
+                superclasses,
             }))),
             Api::IgnoredItem { name, err, ctx } => {
                 Ok(Box::new(std::iter::once(Api::IgnoredItem {
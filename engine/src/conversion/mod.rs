@@ -20,6 +20,7 @@ mod parse;
 mod utilities;
 
 use analysis::fun::FnAnalyzer;
+use autocxx_clang_facts::ClangFacts;
 use autocxx_parser::IncludeCppConfig;
 pub(crate) use codegen_cpp::CppCodeGenerator;
 pub(crate) use convert_error::ConvertError;
@@ -42,6 +43,7 @@ use self::{
         pod::analyze_pod_apis,
         remove_ignored::filter_apis_by_ignored_dependents,
         tdef::convert_typedef_targets,
+        vectors::discard_unpushable_vector_elements,
     },
     api::AnalysisPhase,
     apivec::ApiVec,
@@ -119,6 +121,7 @@ impl<'a> BridgeConverter<'a> {
         mut bindgen_mod: ItemMod,
         unsafe_policy: UnsafePolicy,
         inclusions: String,
+        clang_facts: &ClangFacts,
         cpp_codegen_options: &CppCodegenOptions,
     ) -> Result<CodegenResults, ConvertError> {
         match &mut bindgen_mod.content {
@@ -143,7 +146,7 @@ impl<'a> BridgeConverter<'a> {
                 // This returns a new list of `Api`s, which will be parameterized with
                 // the analysis results. It also returns an object which can be used
                 // by subsequent phases to work out which objects are POD.
-                let analyzed_apis = analyze_pod_apis(apis, self.config)?;
+                let analyzed_apis = analyze_pod_apis(apis, self.config, clang_facts)?;
                 Self::dump_apis("pod analysis", &analyzed_apis);
                 let analyzed_apis = add_casts(analyzed_apis);
                 let analyzed_apis = create_alloc_and_frees(analyzed_apis);
@@ -153,14 +156,19 @@ impl<'a> BridgeConverter<'a> {
                 // part of `autocxx`. Again, this returns a new set of `Api`s, but
                 // parameterized by a richer set of metadata.
                 Self::dump_apis("adding casts", &analyzed_apis);
-                let analyzed_apis =
-                    FnAnalyzer::analyze_functions(analyzed_apis, unsafe_policy, self.config);
+                let analyzed_apis = FnAnalyzer::analyze_functions(
+                    analyzed_apis,
+                    unsafe_policy,
+                    self.config,
+                    clang_facts,
+                );
                 // If any of those functions turned out to be pure virtual, don't attempt
                 // to generate UniquePtr implementations for the type, since it can't
                 // be instantiated.
                 Self::dump_apis("analyze fns", &analyzed_apis);
                 let analyzed_apis = mark_types_abstract(analyzed_apis);
                 Self::dump_apis("marking abstract", &analyzed_apis);
+                let analyzed_apis = discard_unpushable_vector_elements(analyzed_apis);
                 // Annotate structs with a note of any copy/move constructors which
                 // we may want to retain to avoid garbage collecting them later.
                 let analyzed_apis = decorate_types_with_constructor_deps(analyzed_apis);
@@ -0,0 +1,170 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::RustTrait;
+use quote::{format_ident, ToTokens};
+use syn::{FnArg, Pat, ReturnType, TraitItem, Type};
+
+use crate::{
+    conversion::{analysis::fun::ReceiverMutability, api::RustTraitMethod, ConvertError},
+    known_types::known_types,
+    types::QualifiedName,
+};
+
+/// Rust types which may appear by value in the methods of a trait
+/// exposed to C++, other than references to `str` or slices.
+const BY_VALUE_TYPES: &[&str] = &[
+    "bool", "u8", "u16", "u32", "u64", "usize", "i8", "i16", "i32", "i64", "isize", "f32", "f64",
+    "String",
+];
+
+/// Works out the C++ signature of each method of a Rust trait which is to
+/// be exposed to C++ as an abstract class. This supports only object-safe
+/// methods taking `&self` or `&mut self`, and the types which cxx knows
+/// how to pass between Rust and C++ without further help.
+pub(super) fn analyze_rust_trait(
+    rust_trait: &RustTrait,
+) -> Result<Vec<RustTraitMethod>, ConvertError> {
+    let mut methods = Vec::new();
+    for item in &rust_trait.item.items {
+        let method = match item {
+            TraitItem::Method(method) => method,
+            _ => {
+                return Err(ConvertError::UnsupportedRustTrait(
+                    "only methods are supported".into(),
+                ))
+            }
+        };
+        let sig = &method.sig;
+        if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+            return Err(ConvertError::UnsupportedRustTrait(format!(
+                "{} is generic",
+                sig.ident
+            )));
+        }
+        let mut inputs = sig.inputs.iter();
+        let receiver_mutability = match inputs.next() {
+            Some(FnArg::Receiver(receiver)) if receiver.reference.is_some() => {
+                if receiver.mutability.is_some() {
+                    ReceiverMutability::Mutable
+                } else {
+                    ReceiverMutability::Const
+                }
+            }
+            _ => {
+                return Err(ConvertError::UnsupportedRustTrait(format!(
+                    "{} must take &self or &mut self",
+                    sig.ident
+                )))
+            }
+        };
+        let mut params = Vec::new();
+        let mut cpp_params = Vec::new();
+        for (i, input) in inputs.enumerate() {
+            let pt = match input {
+                FnArg::Typed(pt) => pt,
+                FnArg::Receiver(_) => unreachable!("receiver found after first parameter"),
+            };
+            let id = match pt.pat.as_ref() {
+                Pat::Ident(pi) => pi.ident.clone(),
+                _ => format_ident!("autocxx_arg{}", i),
+            };
+            cpp_params.push(format!("{} {}", cpp_type(&pt.ty)?, id));
+            params.push((id, pt.ty.as_ref().clone()));
+        }
+        let cpp_ret = match &sig.output {
+            ReturnType::Default => "void".to_string(),
+            ReturnType::Type(_, ty) => match ty.as_ref() {
+                Type::Tuple(tuple) if tuple.elems.is_empty() => "void".to_string(),
+                Type::Reference(_) => {
+                    return Err(ConvertError::UnsupportedRustTrait(format!(
+                        "{} returns a reference",
+                        sig.ident
+                    )))
+                }
+                ty => cpp_type(ty)?,
+            },
+        };
+        methods.push(RustTraitMethod {
+            name: sig.ident.clone(),
+            receiver_mutability,
+            params,
+            ret: sig.output.clone(),
+            cpp_params,
+            cpp_ret,
+        });
+    }
+    Ok(methods)
+}
+
+/// The C++ type which cxx uses for this Rust type.
+fn cpp_type(ty: &Type) -> Result<String, ConvertError> {
+    match ty {
+        Type::Path(typ) if typ.qself.is_none() => {
+            if let Some(id) = typ.path.get_ident() {
+                let name = id.to_string();
+                if BY_VALUE_TYPES.contains(&name.as_str()) {
+                    return Ok(match name.as_str() {
+                        "usize" => "size_t".into(),
+                        "isize" => "rust::isize".into(),
+                        _ => known_types()
+                            .special_cpp_name(&QualifiedName::new_from_cpp_name(&name))
+                            .unwrap(),
+                    });
+                }
+            }
+        }
+        Type::Reference(typr) => match typr.elem.as_ref() {
+            Type::Path(typ) if typr.mutability.is_none() && typ.path.is_ident("str") => {
+                return Ok("rust::Str".into())
+            }
+            Type::Slice(slice) => {
+                let constness = if typr.mutability.is_some() {
+                    ""
+                } else {
+                    "const "
+                };
+                return Ok(format!(
+                    "rust::Slice<{}{}>",
+                    constness,
+                    cpp_type(&slice.elem)?
+                ));
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+    Err(ConvertError::UnsupportedRustTrait(format!(
+        "the type {} can't be passed to or from C++",
+        ty.to_token_stream()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cpp_type;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_cpp_type() {
+        assert_eq!(cpp_type(&parse_quote! { u8 }).unwrap(), "uint8_t");
+        assert_eq!(cpp_type(&parse_quote! { usize }).unwrap(), "size_t");
+        assert_eq!(cpp_type(&parse_quote! { String }).unwrap(), "rust::String");
+        assert_eq!(cpp_type(&parse_quote! { &str }).unwrap(), "rust::Str");
+        assert_eq!(
+            cpp_type(&parse_quote! { &[u8] }).unwrap(),
+            "rust::Slice<const uint8_t>"
+        );
+        assert_eq!(
+            cpp_type(&parse_quote! { &mut [f64] }).unwrap(),
+            "rust::Slice<double>"
+        );
+        assert!(cpp_type(&parse_quote! { Vec<u8> }).is_err());
+        assert!(cpp_type(&parse_quote! { &mut str }).is_err());
+    }
+}
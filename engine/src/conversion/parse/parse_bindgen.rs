@@ -31,7 +31,7 @@ use super::{
     super::utilities::generate_utilities, bindgen_semantic_attributes::BindgenSemanticAttributes,
 };
 
-use super::parse_foreign_mod::ParseForeignMod;
+use super::{extern_rust_trait::analyze_rust_trait, parse_foreign_mod::ParseForeignMod};
 
 /// Parses a bindgen mod in order to understand the APIs within it.
 pub(crate) struct ParseBindgen<'a> {
@@ -85,11 +85,27 @@ impl<'a> ParseBindgen<'a> {
     /// Some API items are not populated from bindgen output, but instead
     /// directly from items in the config.
     fn add_apis_from_config(&mut self) {
-        self.apis
-            .extend(self.config.subclasses.iter().map(|sc| Api::Subclass {
-                name: SubclassName::new(sc.subclass.clone()),
-                superclass: QualifiedName::new_from_cpp_name(&sc.superclass),
-            }));
+        // A subclass may be listed several times, once per superclass.
+        let mut superclasses_by_subclass: Vec<(&Ident, Vec<QualifiedName>)> = Vec::new();
+        for sc in &self.config.subclasses {
+            let superclass =
+                QualifiedName::new_from_cpp_name(&self.config.superclass_name(&sc.superclass));
+            match superclasses_by_subclass
+                .iter_mut()
+                .find(|(sub, _)| *sub == &sc.subclass)
+            {
+                Some((_, superclasses)) => superclasses.push(superclass),
+                None => superclasses_by_subclass.push((&sc.subclass, vec![superclass])),
+            }
+        }
+        self.apis.extend(
+            superclasses_by_subclass
+                .into_iter()
+                .map(|(sub, superclasses)| Api::Subclass {
+                    name: SubclassName::new(sub.clone()),
+                    superclasses,
+                }),
+        );
         self.apis
             .extend(self.config.extern_rust_funs.iter().map(|fun| {
                 let id = fun.sig.ident.clone();
@@ -101,6 +117,21 @@ impl<'a> ParseBindgen<'a> {
                     }),
                 }
             }));
+        for rust_trait in &self.config.extern_rust_traits {
+            let id = rust_trait.item.ident.clone();
+            self.apis.push(match analyze_rust_trait(rust_trait) {
+                Ok(methods) => Api::RustTrait {
+                    name: ApiName::new_in_root_namespace(id),
+                    path: rust_trait.path.clone(),
+                    methods,
+                },
+                Err(err) => Api::IgnoredItem {
+                    name: ApiName::new_in_root_namespace(id.clone()),
+                    err,
+                    ctx: Some(ErrorContext::new_for_item(id)),
+                },
+            });
+        }
         let unique_rust_types: HashSet<&RustPath> = self.config.rust_types.iter().collect();
         self.apis.extend(unique_rust_types.into_iter().map(|path| {
             let id = path.get_final_ident();
@@ -113,6 +144,8 @@ impl<'a> ParseBindgen<'a> {
             self.config
                 .concretes
                 .iter()
+                // bindgen tells us about templated superclasses.
+                .filter(|(cpp_definition, _)| !self.config.is_templated_superclass(cpp_definition))
                 .map(|(cpp_definition, rust_id)| {
                     let name = ApiName::new_in_root_namespace(rust_id.clone());
                     Api::ConcreteType {
@@ -178,7 +211,10 @@ impl<'a> ParseBindgen<'a> {
                 // cxx::bridge can't cope with type aliases to generic
                 // types at the moment.
                 let name = api_name_qualified(ns, s.ident.clone(), &annotations)?;
-                let api = if ns.is_empty() && self.config.is_rust_type(&s.ident) {
+                let api = if ns.is_empty()
+                    && (self.config.is_rust_type(&s.ident)
+                        || self.config.is_rust_trait(&s.ident.to_string()))
+                {
                     None
                 } else if is_forward_declaration {
                     Some(UnanalyzedApi::ForwardDeclaration { name })
@@ -87,6 +87,7 @@ impl ParseForeignMod {
                     synthesized_this_type: None,
                     add_to_trait: None,
                     is_deleted: annotations.has_attr("deleted"),
+                    is_variadic: item.sig.variadic.is_some(),
                     synthetic_cpp: None,
                 });
                 Ok(())
@@ -11,9 +11,22 @@ use crate::{
     types::{make_ident, QualifiedName},
 };
 use indoc::indoc;
+use itertools::Itertools;
 use once_cell::sync::OnceCell;
+use quote::ToTokens;
 use std::collections::HashMap;
-use syn::{parse_quote, Type, TypePath, TypePtr};
+use syn::{
+    parse_quote, punctuated::Punctuated, token::Comma, GenericArgument, PathArguments, ReturnType,
+    Type, TypeBareFn, TypePath, TypePtr,
+};
+
+/// The largest `std::tuple` which we can return to Rust.
+const MAX_TUPLE_ELEMENTS: usize = 8;
+
+/// The templates which associative containers use by default for their
+/// comparator, hasher, key equality and allocator arguments.
+const DEFAULT_CONTAINER_ARGS: [&str; 4] =
+    ["std::less", "std::hash", "std::equal_to", "std::allocator"];
 
 //// The behavior of the type.
 #[derive(Debug)]
@@ -21,14 +34,21 @@ enum Behavior {
     CxxContainerByValueSafe,
     CxxContainerNotByValueSafe,
     CxxString,
+    CxxUtfString,
     RustStr,
     RustString,
     RustByValue,
     CByValue,
     CVariableLengthByValue,
     CVoid,
-    CChar16,
+    CUnicodeChar,
+    CVaList,
     RustContainerByValueSafe,
+    CxxSpan,
+    CxxMap,
+    CxxSet,
+    CxxTuple,
+    CxxFunction,
 }
 
 /// Details about known special types, mostly primitives.
@@ -71,15 +91,43 @@ impl TypeDetails {
             Behavior::RustString
             | Behavior::RustStr
             | Behavior::CxxString
+            | Behavior::CxxUtfString
             | Behavior::CxxContainerByValueSafe
             | Behavior::CxxContainerNotByValueSafe
-            | Behavior::RustContainerByValueSafe => {
+            | Behavior::RustContainerByValueSafe
+            | Behavior::CxxSpan
+            | Behavior::CxxMap
+            | Behavior::CxxSet
+            | Behavior::CxxTuple
+            | Behavior::CxxFunction => {
                 let tn = QualifiedName::new_from_cpp_name(&self.rs_name);
                 let cxx_name = tn.get_final_item();
+                let tuple_prelude;
                 let (templating, payload) = match self.behavior {
                     Behavior::CxxContainerByValueSafe
                     | Behavior::CxxContainerNotByValueSafe
-                    | Behavior::RustContainerByValueSafe => ("template<typename T> ", "T* ptr"),
+                    | Behavior::RustContainerByValueSafe
+                    | Behavior::CxxSpan
+                    | Behavior::CxxSet => ("template<typename T> ", "T* ptr"),
+                    Behavior::CxxFunction => ("template<typename F> ", "F* ptr"),
+                    Behavior::CxxMap => ("template<typename K, typename V> ", "K* key; V* value"),
+                    Behavior::CxxTuple => {
+                        // bindgen can't see through variadic template
+                        // parameters, so give it a fixed number of them.
+                        let arity = if self.cpp_name == "std::pair" {
+                            2
+                        } else {
+                            MAX_TUPLE_ELEMENTS
+                        };
+                        tuple_prelude = (
+                            format!(
+                                "template<{}> ",
+                                (1..=arity).map(|i| format!("typename T{}", i)).join(", ")
+                            ),
+                            (1..=arity).map(|i| format!("T{}* e{}", i, i)).join("; "),
+                        );
+                        (tuple_prelude.0.as_str(), tuple_prelude.1.as_str())
+                    }
                     _ => ("", "char* ptr"),
                 };
                 Some(format!(
@@ -120,9 +168,9 @@ impl TypeDetails {
 
     fn get_generic_behavior(&self) -> CxxGenericType {
         match self.behavior {
-            Behavior::CxxContainerByValueSafe | Behavior::CxxContainerNotByValueSafe => {
-                CxxGenericType::Cpp
-            }
+            Behavior::CxxContainerByValueSafe
+            | Behavior::CxxContainerNotByValueSafe
+            | Behavior::CxxSpan => CxxGenericType::Cpp,
             Behavior::RustContainerByValueSafe => CxxGenericType::Rust,
             _ => CxxGenericType::Not,
         }
@@ -154,6 +202,60 @@ pub enum CxxGenericType {
     Rust,
 }
 
+/// The contents of a `std::map`, `std::set` or similar.
+pub(crate) struct AssociativeContainerDetails {
+    pub(crate) key: Type,
+    /// `None` for sets.
+    pub(crate) value: Option<Type>,
+}
+
+impl AssociativeContainerDetails {
+    /// The name of a shim function (or type) for the given container.
+    pub(crate) fn shim_name(container: &str, operation: &str) -> String {
+        format!("{}_autocxx_{}", container, operation)
+    }
+
+    /// Whether a key or value should be passed to and from C++ by reference
+    /// rather than by value.
+    pub(crate) fn passed_by_reference(ty: &Type) -> bool {
+        match ty {
+            Type::Path(typ) => {
+                known_types().convertible_from_strs(&QualifiedName::from_type_path(typ))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The signature of a `std::function` which we can construct from a
+/// Rust closure.
+#[derive(Clone)]
+pub(crate) struct CallbackSignature(pub(crate) TypeBareFn);
+
+impl CallbackSignature {
+    /// The name of the Rust type which holds the closure, for a given
+    /// concrete `std::function` type.
+    pub(crate) fn closure_name(function: &str) -> String {
+        format!("{}_autocxx_closure", function)
+    }
+
+    /// The name of the `extern "Rust"` function which C++ calls in order
+    /// to invoke the closure.
+    pub(crate) fn trampoline_name(function: &str) -> String {
+        format!("{}_autocxx_call", function)
+    }
+
+    pub(crate) fn inputs(&self) -> impl Iterator<Item = &Type> {
+        self.0.inputs.iter().map(|arg| &arg.ty)
+    }
+}
+
+impl std::fmt::Debug for CallbackSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.to_token_stream())
+    }
+}
+
 pub struct KnownTypeConstructorDetails {
     pub has_move_constructor: bool,
     pub has_const_copy_constructor: bool,
@@ -206,11 +308,18 @@ impl TypeDatabase {
                         | Behavior::RustByValue
                         | Behavior::CByValue
                         | Behavior::CVariableLengthByValue
-                        | Behavior::CChar16
+                        | Behavior::CUnicodeChar
                         | Behavior::RustContainerByValueSafe => true,
                         Behavior::CxxString
+                        | Behavior::CxxUtfString
                         | Behavior::CxxContainerNotByValueSafe
-                        | Behavior::CVoid => false,
+                        | Behavior::CVoid
+                        | Behavior::CVaList
+                        | Behavior::CxxSpan
+                        | Behavior::CxxMap
+                        | Behavior::CxxSet
+                        | Behavior::CxxTuple
+                        | Behavior::CxxFunction => false,
                     },
                 )
             })
@@ -273,24 +382,54 @@ impl TypeDatabase {
     /// Get the list of types to give to bindgen to ask it _not_ to
     /// generate code for.
     pub(crate) fn get_initial_blocklist(&self) -> impl Iterator<Item = &str> + '_ {
-        self.by_rs_name
-            .iter()
-            .filter_map(|(_, td)| td.get_prelude_entry().map(|_| td.cpp_name.as_str()))
+        self.by_rs_name.iter().filter_map(|(_, td)| {
+            if td.get_prelude_entry().is_some() || matches!(td.behavior, Behavior::CVaList) {
+                Some(td.cpp_name.as_str())
+            } else {
+                None
+            }
+        })
     }
 
     /// Whether this is one of the ctypes (mostly variable length integers)
-    /// which we need to wrap.
+    /// which we need to wrap, or one of the C++ string types for which
+    /// we generate our own opaque type.
     pub(crate) fn is_ctype(&self, ty: &QualifiedName) -> bool {
         self.get(ty)
             .map(|td| {
                 matches!(
                     td.behavior,
-                    Behavior::CVariableLengthByValue | Behavior::CVoid | Behavior::CChar16
+                    Behavior::CVariableLengthByValue
+                        | Behavior::CVoid
+                        | Behavior::CUnicodeChar
+                        | Behavior::CVaList
+                        | Behavior::CxxUtfString
                 )
             })
             .unwrap_or(false)
     }
 
+    /// The width in bits of each code unit, if this is a `std::u16string`
+    /// or `std::u32string`. cxx has no support for these, so we make
+    /// our own opaque type for each, along with functions to convert
+    /// to and from Rust strings.
+    pub(crate) fn utf_string_code_unit_bits(&self, ty: &QualifiedName) -> Option<usize> {
+        self.get(ty).and_then(|td| match td.behavior {
+            Behavior::CxxUtfString => match td.cpp_name.as_str() {
+                "std::u16string" => Some(16),
+                "std::u32string" => Some(32),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    /// The name by which we refer to this known type, whichever of its
+    /// names we've been given.
+    pub(crate) fn canonical_name(&self, ty: &QualifiedName) -> Option<QualifiedName> {
+        self.get(ty).map(|td| td.to_typename())
+    }
+
     /// Whether this is a generic type acceptable to cxx. Otherwise,
     /// if we encounter a generic, we'll replace it with a synthesized concrete
     /// type.
@@ -309,6 +448,172 @@ impl TypeDatabase {
         self.get(ty).is_some()
     }
 
+    /// Whether this is a `std::vector`. As well as the element types which
+    /// cxx knows about, we allow vectors of any movable type generated by
+    /// autocxx, for which we generate a C++ shim to push elements.
+    pub(crate) fn is_vector(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|x| x.cpp_name == "std::vector")
+            .unwrap_or(false)
+    }
+
+    /// Whether this is a view onto contiguous memory (e.g. `std::span`)
+    /// which we represent as a Rust slice.
+    pub(crate) fn is_span(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|x| matches!(x.behavior, Behavior::CxxSpan))
+            .unwrap_or(false)
+    }
+
+    /// Whether this is a `std::map` or similar, for which we generate
+    /// shims to access the contents.
+    pub(crate) fn is_associative_container(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|x| matches!(x.behavior, Behavior::CxxMap | Behavior::CxxSet))
+            .unwrap_or(false)
+    }
+
+    /// The number of template arguments of a `std::map` or similar which
+    /// we care about: the key and, for maps, the value. Any others, such
+    /// as a comparator or allocator, are normally defaulted.
+    pub(crate) fn associative_container_arity(&self, ty: &QualifiedName) -> Option<usize> {
+        match self.get(ty)?.behavior {
+            Behavior::CxxMap => Some(2),
+            Behavior::CxxSet => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a `std::pair`, `std::tuple` or similar, which we can return
+    /// to Rust as a tuple.
+    pub(crate) fn is_tuple(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|x| matches!(x.behavior, Behavior::CxxTuple))
+            .unwrap_or(false)
+    }
+
+    /// Whether this is a `std::function`, which we can construct
+    /// from a Rust closure.
+    pub(crate) fn is_function(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|x| matches!(x.behavior, Behavior::CxxFunction))
+            .unwrap_or(false)
+    }
+
+    /// If this is a `std::function` whose signature we can call
+    /// from C++ into a Rust closure, returns that signature.
+    pub(crate) fn callback_signature(&self, ty: &Type) -> Option<CallbackSignature> {
+        let typ = match ty {
+            Type::Path(typ) => typ,
+            _ => return None,
+        };
+        if !self.is_function(&QualifiedName::from_type_path(typ)) {
+            return None;
+        }
+        match &typ.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(ab) => match ab.args.first() {
+                Some(GenericArgument::Type(Type::BareFn(bare_fn)))
+                    if ab.args.len() == 1 && self.is_callback_signature(bare_fn) =>
+                {
+                    Some(CallbackSignature(bare_fn.clone()))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether a function pointer or `std::function` of this signature
+    /// can be passed between Rust and C++. At present we only allow
+    /// simple numeric types.
+    pub(crate) fn is_callback_signature(&self, bare_fn: &TypeBareFn) -> bool {
+        let is_payload = |ty: &Type| match ty {
+            Type::Path(typ) => self
+                .get(&QualifiedName::from_type_path(typ))
+                .map(|x| {
+                    matches!(
+                        x.behavior,
+                        Behavior::CByValue | Behavior::CVariableLengthByValue
+                    )
+                })
+                .unwrap_or(false),
+            _ => false,
+        };
+        bare_fn.variadic.is_none()
+            && bare_fn.inputs.iter().all(|arg| is_payload(&arg.ty))
+            && match &bare_fn.output {
+                ReturnType::Default => true,
+                ReturnType::Type(_, ty) => is_payload(ty),
+            }
+    }
+
+    /// The key and, for maps, value template arguments of a `std::map` or
+    /// similar. bindgen also tells us about any comparator, hasher or
+    /// allocator arguments. Anything other than the defaults makes this a
+    /// different C++ type, which we can't represent, so we return `None`.
+    pub(crate) fn associative_container_args(
+        &self,
+        ty: &QualifiedName,
+        args: &Punctuated<GenericArgument, Comma>,
+    ) -> Option<Punctuated<GenericArgument, Comma>> {
+        let arity = self.associative_container_arity(ty)?;
+        let all_defaults = args.iter().skip(arity).all(|arg| match arg {
+            GenericArgument::Type(Type::Path(typ)) => DEFAULT_CONTAINER_ARGS
+                .contains(&QualifiedName::from_type_path(typ).to_cpp_name().as_str()),
+            _ => false,
+        });
+        if args.len() < arity || !all_defaults {
+            return None;
+        }
+        Some(args.iter().take(arity).cloned().collect())
+    }
+
+    /// If this is a `std::map` or similar, whose keys and values are of types
+    /// that we can pass across the bridge, return details of its contents.
+    pub(crate) fn associative_container_details(
+        &self,
+        ty: &Type,
+    ) -> Option<AssociativeContainerDetails> {
+        let typ = match ty {
+            Type::Path(typ) => typ,
+            _ => return None,
+        };
+        let tn = QualifiedName::from_type_path(typ);
+        let args: Vec<_> = match &typ.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(ab) => self
+                .associative_container_args(&tn, &ab.args)?
+                .into_iter()
+                .filter_map(|arg| match arg {
+                    GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                })
+                .collect(),
+            _ => return None,
+        };
+        if args.len() != self.associative_container_arity(&tn)?
+            || !args.iter().all(|ty| self.is_container_payload(ty))
+        {
+            return None;
+        }
+        let mut args = args.into_iter();
+        Some(AssociativeContainerDetails {
+            key: args.next().unwrap(),
+            value: args.next(),
+        })
+    }
+
+    /// Whether we can generate shims to get this type in and out of
+    /// associative containers.
+    fn is_container_payload(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Path(typ) => self
+                .get(&QualifiedName::from_type_path(typ))
+                .map(|x| matches!(x.behavior, Behavior::CxxString | Behavior::CByValue))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
     pub(crate) fn convertible_from_strs(&self, ty: &QualifiedName) -> bool {
         self.get(ty)
             .map(|x| matches!(x.behavior, Behavior::CxxString))
@@ -365,6 +670,70 @@ fn create_type_database() -> TypeDatabase {
         true,
         true,
     ));
+    db.insert(TypeDetails::new(
+        "autocxx::StdSpan",
+        "std::span",
+        Behavior::CxxSpan,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::AbslSpan",
+        "absl::Span",
+        Behavior::CxxSpan,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::StdMap",
+        "std::map",
+        Behavior::CxxMap,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::StdUnorderedMap",
+        "std::unordered_map",
+        Behavior::CxxMap,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::StdSet",
+        "std::set",
+        Behavior::CxxSet,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::StdPair",
+        "std::pair",
+        Behavior::CxxTuple,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::StdTuple",
+        "std::tuple",
+        Behavior::CxxTuple,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::StdFunction",
+        "std::function",
+        Behavior::CxxFunction,
+        None,
+        true,
+        true,
+    ));
     db.insert(TypeDetails::new(
         "cxx::CxxString",
         "std::string",
@@ -373,6 +742,24 @@ fn create_type_database() -> TypeDatabase {
         true,
         true,
     ));
+    // cxx knows nothing of these, so each include_cpp! mod gets its own
+    // opaque type for them, which is why they live in `cxxbridge`.
+    db.insert(TypeDetails::new(
+        "cxxbridge::CxxU16String",
+        "std::u16string",
+        Behavior::CxxUtfString,
+        None,
+        true,
+        true,
+    ));
+    db.insert(TypeDetails::new(
+        "cxxbridge::CxxU32String",
+        "std::u32string",
+        Behavior::CxxUtfString,
+        None,
+        true,
+        true,
+    ));
     db.insert(TypeDetails::new(
         "str",
         "rust::Str",
@@ -503,14 +890,49 @@ fn create_type_database() -> TypeDatabase {
         false,
         false,
     ));
+    // A `va_list` is an array of these on most platforms, so bindgen
+    // gives us a pointer to one wherever a `va_list` is passed. We
+    // don't let bindgen describe its innards; Rust can only pass it on.
+    db.insert(TypeDetails::new(
+        "autocxx::va_list_tag",
+        "__va_list_tag",
+        Behavior::CVaList,
+        None,
+        false,
+        false,
+    ));
     db.insert(TypeDetails::new(
         "autocxx::c_char16_t",
         "char16_t",
-        Behavior::CChar16,
+        Behavior::CUnicodeChar,
         Some("c_char16_t".into()),
         false,
         false,
     ));
+    db.insert(TypeDetails::new(
+        "autocxx::c_char32_t",
+        "char32_t",
+        Behavior::CUnicodeChar,
+        Some("c_char32_t".into()),
+        false,
+        false,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::c_char8_t",
+        "char8_t",
+        Behavior::CUnicodeChar,
+        Some("c_char8_t".into()),
+        false,
+        false,
+    ));
+    db.insert(TypeDetails::new(
+        "autocxx::c_wchar_t",
+        "wchar_t",
+        Behavior::CUnicodeChar,
+        Some("c_wchar_t".into()),
+        false,
+        false,
+    ));
     db
 }
 
@@ -520,3 +942,68 @@ pub(crate) fn ensure_pointee_is_valid(ptr: &TypePtr) -> Result<(), ConvertError>
         _ => Err(ConvertError::InvalidPointee),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::known_types;
+    use crate::types::QualifiedName;
+    use quote::ToTokens;
+    use syn::parse_quote;
+
+    fn to_string(ty: &syn::Type) -> String {
+        ty.to_token_stream().to_string()
+    }
+
+    #[test]
+    fn test_associative_container_defaulted_args() {
+        let details = known_types()
+            .associative_container_details(&parse_quote! {
+                root::std::map<root::std::string, u32, root::std::less<root::std::string>, root::std::allocator<u32>>
+            })
+            .unwrap();
+        assert_eq!(to_string(&details.key), "root :: std :: string");
+        assert_eq!(details.value.as_ref().map(to_string).unwrap(), "u32");
+        let details = known_types()
+            .associative_container_details(&parse_quote! {
+                root::std::set<u32, root::std::less<u32> >
+            })
+            .unwrap();
+        assert_eq!(to_string(&details.key), "u32");
+        assert!(details.value.is_none());
+        assert!(known_types()
+            .associative_container_details(&parse_quote! { root::std::map<u32> })
+            .is_none());
+    }
+
+    #[test]
+    fn test_associative_container_custom_args() {
+        assert!(known_types()
+            .associative_container_details(&parse_quote! {
+                root::std::set<u32, root::std::greater<u32> >
+            })
+            .is_none());
+        assert!(known_types()
+            .associative_container_details(&parse_quote! {
+                root::std::unordered_map<u32, u32, root::MyHash, root::std::equal_to<u32> >
+            })
+            .is_none());
+        let details = known_types()
+            .associative_container_details(&parse_quote! {
+                root::std::unordered_map<u32, u32, root::std::hash<u32>, root::std::equal_to<u32> >
+            })
+            .unwrap();
+        assert_eq!(to_string(&details.key), "u32");
+    }
+
+    #[test]
+    fn test_tuple_prelude() {
+        let prelude = known_types().get_prelude();
+        assert!(prelude.contains("replaces=\"std::pair\""));
+        assert!(prelude.contains("template<typename T1, typename T2> class StdPair"));
+        assert!(prelude.contains("replaces=\"std::tuple\""));
+        assert!(prelude.contains(
+            "template<typename T1, typename T2, typename T3, typename T4, typename T5, typename T6, typename T7, typename T8> class StdTuple"
+        ));
+        assert!(known_types().is_tuple(&QualifiedName::new_from_cpp_name("std::tuple")));
+    }
+}
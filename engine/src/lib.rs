@@ -27,6 +27,7 @@ mod types;
 #[cfg(any(test, feature = "build"))]
 mod builder;
 
+use autocxx_clang_facts::{declare_templated_superclasses, ClangFacts, UnredeclarableMethod};
 use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
 use conversion::BridgeConverter;
 use parse_callbacks::AutocxxParseCallbacks;
@@ -93,6 +94,9 @@ pub enum Error {
     /// Some error occcurred in converting the bindgen-style
     /// bindings to safe cxx bindings.
     Conversion(conversion::ConvertError),
+    /// A virtual method of a templated superclass couldn't be declared
+    /// for bindgen, so a subclass wouldn't be able to override it.
+    TemplatedSuperclass(UnredeclarableMethod),
 }
 
 impl Display for Error {
@@ -102,6 +106,7 @@ impl Display for Error {
             Error::Parsing(err) => write!(f, "The Rust file could not be parsed: {}", err)?,
             Error::NoAutoCxxInc => write!(f, "No C++ include directory was provided.")?,
             Error::Conversion(err) => write!(f, "autocxx could not generate the requested bindings. {}", err)?,
+            Error::TemplatedSuperclass(err) => write!(f, "{}", err)?,
         }
         Ok(())
     }
@@ -374,14 +379,40 @@ impl IncludeCppEngine {
         if let Some(dep_recorder) = dep_recorder {
             builder = builder.parse_callbacks(Box::new(AutocxxParseCallbacks(dep_recorder)));
         }
-        let header_contents = self.build_header();
+        let mut header_contents = self.build_header();
         self.dump_header_if_so_configured(&header_contents, &inc_dirs, extra_clang_args);
-        let header_and_prelude = format!("{}\n\n{}", known_types().get_prelude(), header_contents);
+        let clang_args: Vec<_> = make_clang_args(&inc_dirs, extra_clang_args).collect();
+        let mut header_and_prelude =
+            format!("{}\n\n{}", known_types().get_prelude(), header_contents);
+        let templated_superclasses: Vec<_> = self.config.templated_superclasses().collect();
+        if !templated_superclasses.is_empty() {
+            let templated_superclasses: Vec<_> = templated_superclasses
+                .iter()
+                .map(|(cpp_definition, name)| (cpp_definition.as_str(), name.as_ref()))
+                .collect();
+            // bindgen sees a class standing in for each instantiation,
+            // whereas in the C++ we generate, it's a typedef.
+            header_and_prelude.push_str(
+                &declare_templated_superclasses(
+                    "example.hpp",
+                    &header_contents,
+                    &clang_args,
+                    &templated_superclasses,
+                )
+                .map_err(Error::TemplatedSuperclass)?,
+            );
+            for (cpp_definition, name) in templated_superclasses {
+                header_contents.push_str(&format!("typedef {} {};\n", cpp_definition, name));
+            }
+        }
         log::info!("Header and prelude for bindgen:\n{}", header_and_prelude);
         builder = builder.header_contents("example.hpp", &header_and_prelude);
 
         let bindings = builder.generate().map_err(Error::Bindgen)?;
         let bindings = self.parse_bindings(bindings)?;
+        // libclang parses the header again only if our analysis asks
+        // something which bindgen didn't tell us.
+        let clang_facts = ClangFacts::new("example.hpp", &header_and_prelude, &clang_args);
 
         let converter = BridgeConverter::new(&self.config.inclusions, &self.config);
 
@@ -390,6 +421,7 @@ impl IncludeCppEngine {
                 bindings,
                 self.config.unsafe_policy.clone(),
                 header_contents,
+                &clang_facts,
                 cpp_codegen_options,
             )
             .map_err(Error::Conversion)?;
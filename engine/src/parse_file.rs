@@ -14,7 +14,7 @@ use crate::{
 };
 use autocxx_parser::directives::SUBCLASS;
 use autocxx_parser::{AllowlistEntry, RustPath, Subclass, SubclassAttrs};
-use proc_macro2::{Span, TokenStream};
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::ToTokens;
 use std::{collections::HashSet, fmt::Display, io::Read, path::PathBuf};
 use std::{panic::UnwindSafe, path::Path, rc::Rc};
@@ -43,6 +43,9 @@ pub enum ParseError {
     ZeroModsForDynamicDiscovery,
     MultipleModsForDynamicDiscovery,
     Discovery(DiscoveryErr),
+    /// A Rust subclass was declared `thread_safe` in its `subclass!`
+    /// directive but not its `#[subclass]` attribute, or vice versa.
+    SubclassThreadSafetyMismatch(String),
 }
 
 impl Display for ParseError {
@@ -63,6 +66,7 @@ impl Display for ParseError {
             ParseError::Discovery(DiscoveryErr::NoParameterOnMethod) => write!(f, "#[extern_rust_function] was attached to a method taking no parameters.")?,
             ParseError::Discovery(DiscoveryErr::NonReferenceReceiver) => write!(f, "#[extern_rust_function] was attached to a method taking a receiver by value.")?,
             ParseError::Discovery(DiscoveryErr::FoundExternRustFunWithinMod) => write!(f, "#[extern_rust_function] was in an impl block nested wihtin another block. This is only supported in the outermost mod of a file, alongside the include_cpp!.")?,
+            ParseError::SubclassThreadSafetyMismatch(subclass) => write!(f, "Rust subclass {} must be declared thread_safe in both its subclass! directive and its #[subclass] attribute, or in neither.", subclass)?,
         }
         Ok(())
     }
@@ -88,6 +92,9 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
         auto_allowlist: bool,
         results: Vec<Segment>,
         extra_superclasses: Vec<Subclass>,
+        /// Each struct with a `#[subclass]` attribute, and whether that
+        /// attribute says `thread_safe`.
+        subclass_thread_safety: Vec<(Ident, bool)>,
         discoveries: Discoveries,
     }
     impl State {
@@ -129,6 +136,8 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                             mod_state.parse_item(item, Some(mod_path.clone()))?
                         }
                         self.extra_superclasses.extend(mod_state.extra_superclasses);
+                        self.subclass_thread_safety
+                            .extend(mod_state.subclass_thread_safety);
                         self.discoveries.extend(mod_state.discoveries);
                         Segment::Mod(
                             mod_state.results,
@@ -144,7 +153,7 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                         Segment::Other(Item::Mod(itm))
                     }
                 }
-                Item::Struct(ref its) if self.auto_allowlist => {
+                Item::Struct(ref its) => {
                     let attrs = &its.attrs;
                     let is_superclass_attr = attrs.iter().find(|attr| {
                         attr.path
@@ -154,15 +163,25 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                             .unwrap_or(false)
                     });
                     if let Some(is_superclass_attr) = is_superclass_attr {
-                        if !is_superclass_attr.tokens.is_empty() {
-                            let subclass = its.ident.clone();
-                            let args: SubclassAttrs = is_superclass_attr
+                        let subclass = its.ident.clone();
+                        let args: SubclassAttrs = if is_superclass_attr.tokens.is_empty() {
+                            SubclassAttrs::default()
+                        } else {
+                            is_superclass_attr
                                 .parse_args()
-                                .map_err(ParseError::Syntax)?;
-                            if let Some(superclass) = args.superclass {
+                                .map_err(ParseError::Syntax)?
+                        };
+                        self.subclass_thread_safety
+                            .push((subclass.clone(), args.thread_safe));
+                        if self.auto_allowlist {
+                            for superclass in args.superclasses {
                                 self.extra_superclasses.push(Subclass {
                                     superclass,
-                                    subclass,
+                                    subclass: subclass.clone(),
+                                    thread_safe: args.thread_safe,
+                                    on_panic: args.on_panic,
+                                    on_cpp_destroy: args.on_cpp_destroy,
+                                    factory: args.factory,
                                 })
                             }
                         }
@@ -194,6 +213,7 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
         auto_allowlist,
         mut results,
         mut extra_superclasses,
+        subclass_thread_safety,
         mut discoveries,
     } = state;
 
@@ -233,6 +253,10 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                     .config_mut()
                     .rust_types
                     .append(&mut discoveries.extern_rust_types);
+                engine
+                    .config_mut()
+                    .extern_rust_traits
+                    .append(&mut discoveries.extern_rust_traits);
             }
         }
         if autocxx_seg_iterator.next().is_some() {
@@ -244,6 +268,18 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
         _ => None,
     });
     for seg in autocxx_seg_iterator {
+        // The #[subclass] attribute decides which traits the Rust subclass
+        // implements, and the directive decides the code we generate for
+        // it, so the two must agree.
+        for subclass in &seg.config.subclasses {
+            if subclass_thread_safety.iter().any(|(id, thread_safe)| {
+                *id == subclass.subclass && *thread_safe != subclass.thread_safe
+            }) {
+                return Err(ParseError::SubclassThreadSafetyMismatch(
+                    subclass.subclass.to_string(),
+                ));
+            }
+        }
         seg.config.confirm_complete();
     }
     Ok(ParsedFile(results))
@@ -4173,6 +4173,326 @@ fn test_vector_cycle_bare() {
     run_test("", hdr, rs, &["take_vec", "get_vec"], &[]);
 }
 
+#[test]
+fn test_vector_of_pod_as_slice() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <vector>
+        struct A {
+            uint32_t a;
+        };
+        inline std::vector<A> get_vec() {
+            std::vector<A> items;
+            items.push_back(A { 3 });
+            items.push_back(A { 4 });
+            return items;
+        }
+        inline uint32_t sum_vec(const std::vector<A>& many_as) {
+            uint32_t total = 0;
+            for (const auto& a : many_as) {
+                total += a.a;
+            }
+            return total;
+        }
+    "};
+    let rs = quote! {
+        let mut v = ffi::get_vec();
+        assert_eq!(v.as_ref().unwrap().as_slice().iter().map(|a| a.a).collect::<Vec<_>>(), vec![3, 4]);
+        v.pin_mut().push(ffi::A { a: 5 });
+        assert_eq!(ffi::sum_vec(&v), 12);
+    };
+    run_test("", hdr, rs, &["get_vec", "sum_vec"], &["A"]);
+}
+
+#[test]
+fn test_vector_of_non_pod_push() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        #include <vector>
+        struct A {
+            A(uint32_t val) : a(val) {}
+            uint32_t get() const { return a; }
+            std::string so_we_are_non_trivial;
+        private:
+            uint32_t a;
+        };
+        inline std::vector<A> get_vec() {
+            std::vector<A> items;
+            items.push_back(A(3));
+            return items;
+        }
+        inline uint32_t sum_vec(const std::vector<A>& many_as) {
+            uint32_t total = 0;
+            for (const auto& a : many_as) {
+                total += a.get();
+            }
+            return total;
+        }
+    "};
+    let rs = quote! {
+        use autocxx::CxxVectorExt;
+        let mut v = ffi::get_vec();
+        v.pin_mut().push_value(ffi::A::make_unique(4));
+        let five = ffi::A::make_unique(5);
+        v.pin_mut().push_value(&five);
+        assert_eq!(v.len(), 3);
+        assert_eq!(v.get(1).unwrap().get(), 4);
+        assert_eq!(ffi::sum_vec(&v), 12);
+    };
+    run_test("", hdr, rs, &["A", "get_vec", "sum_vec"], &[]);
+}
+
+#[test]
+fn test_vector_push_only_for_vector_elements() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        #include <vector>
+        struct A {
+            A() {}
+            std::string so_we_are_non_trivial;
+        };
+        struct B {
+            B() {}
+            std::string so_we_are_non_trivial;
+        };
+        inline std::vector<A> get_vec() {
+            return std::vector<A>();
+        }
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("A")
+            generate!("B")
+            generate!("get_vec")
+        },
+        None,
+        Some(Box::new(CppMatcher::new(
+            &["A_vector_push"],
+            &["B_vector_push"],
+        ))),
+        None,
+    );
+}
+
+#[test]
+fn test_string_map() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <map>
+        #include <string>
+        inline std::map<std::string, std::string> get_map() {
+            std::map<std::string, std::string> items;
+            items[\"a\"] = \"apple\";
+            items[\"b\"] = \"banana\";
+            return items;
+        }
+        inline uint32_t count_entries(const std::map<std::string, std::string>& items) {
+            return items.size();
+        }
+    "};
+    let rs = quote! {
+        let mut m = ffi::get_map();
+        assert_eq!(m.len(), 2);
+        autocxx::cxx::let_cxx_string!(a = "a");
+        assert!(m.contains(&a));
+        assert_eq!(m.get(&a).unwrap().to_str().unwrap(), "apple");
+        autocxx::cxx::let_cxx_string!(c = "c");
+        autocxx::cxx::let_cxx_string!(cherry = "cherry");
+        m.pin_mut().insert(&c, &cherry);
+        assert_eq!(ffi::count_entries(&m), 3);
+        let keys: Vec<_> = m.iter().map(|(k, _)| k.to_str().unwrap().to_string()).collect();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+    };
+    run_test("", hdr, rs, &["get_map", "count_entries"], &[]);
+}
+
+#[test]
+fn test_int_set() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <set>
+        inline std::set<uint32_t> get_set() {
+            return std::set<uint32_t> { 3, 1, 2 };
+        }
+    "};
+    let rs = quote! {
+        let mut s = ffi::get_set();
+        assert!(s.contains(2));
+        assert!(!s.contains(4));
+        s.pin_mut().insert(4);
+        assert_eq!(s.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert!(!s.is_empty());
+    };
+    run_test("", hdr, rs, &["get_set"], &[]);
+}
+
+#[test]
+fn test_unordered_map() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <unordered_map>
+        inline std::unordered_map<uint32_t, uint32_t> get_squares() {
+            std::unordered_map<uint32_t, uint32_t> items;
+            for (uint32_t i = 0; i < 4; i++) {
+                items[i] = i * i;
+            }
+            return items;
+        }
+    "};
+    let rs = quote! {
+        let mut m = ffi::get_squares();
+        assert_eq!(m.len(), 4);
+        assert!(m.contains(3));
+        assert_eq!(*m.get(3).unwrap(), 9);
+        m.pin_mut().insert(5, 25);
+        assert_eq!(*m.get(5).unwrap(), 25);
+        assert!(!m.contains(4));
+    };
+    run_test("", hdr, rs, &["get_squares"], &[]);
+}
+
+#[test]
+fn test_set_custom_comparator() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <functional>
+        #include <set>
+        inline std::set<uint32_t, std::greater<uint32_t>> get_set() {
+            return std::set<uint32_t, std::greater<uint32_t>> { 3, 1, 2 };
+        }
+    "};
+    let rs = quote! {
+        ffi::get_set();
+    };
+    run_test_expect_fail("", hdr, rs, &["get_set"], &[]);
+}
+
+#[test]
+fn test_return_pair() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        #include <utility>
+        struct A {
+            uint32_t a;
+        };
+        inline std::pair<uint32_t, bool> lookup(uint32_t key) {
+            return std::make_pair(key * 2, key != 0);
+        }
+        inline std::pair<A, std::string> describe(uint32_t key) {
+            return std::make_pair(A { key }, std::string(\"described\"));
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::lookup(3), (6, true));
+        assert_eq!(ffi::lookup(0), (0, false));
+        let (a, description) = ffi::describe(4);
+        assert_eq!(a.a, 4);
+        assert_eq!(description.to_str().unwrap(), "described");
+    };
+    run_test("", hdr, rs, &["lookup", "describe"], &["A"]);
+}
+
+#[test]
+fn test_return_tuple() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        #include <tuple>
+        struct A {
+            uint32_t a;
+        };
+        inline std::tuple<uint32_t, bool, int8_t> lookup(uint32_t key) {
+            return std::make_tuple(key * 2, key != 0, -1);
+        }
+        inline std::tuple<A, std::string, uint32_t, A> describe(uint32_t key) {
+            return std::make_tuple(A { key }, std::string(\"described\"), key + 1, A { key + 2 });
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::lookup(3), (6, true, -1));
+        assert_eq!(ffi::lookup(0), (0, false, -1));
+        let (a, description, next, a2) = ffi::describe(4);
+        assert_eq!(a.a, 4);
+        assert_eq!(description.to_str().unwrap(), "described");
+        assert_eq!(next, 5);
+        assert_eq!(a2.a, 6);
+    };
+    run_test("", hdr, rs, &["lookup", "describe"], &["A"]);
+}
+
+#[test]
+fn test_return_two_element_tuple() {
+    // Fewer elements than the fixed number of template parameters we
+    // give bindgen for std::tuple.
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <tuple>
+        inline std::tuple<uint32_t, bool> halve(uint32_t value) {
+            return std::make_tuple(value / 2, value % 2 != 0);
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::halve(7), (3, true));
+        assert_eq!(ffi::halve(8), (4, false));
+    };
+    run_test("", hdr, rs, &["halve"], &[]);
+}
+
+#[test]
+fn test_take_std_function() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <functional>
+        inline uint32_t apply_twice(uint32_t val, std::function<uint32_t(uint32_t)> f) {
+            return f(f(val));
+        }
+        inline void repeat(uint32_t times, const std::function<void(uint32_t)>& f) {
+            for (uint32_t i = 0; i < times; i++) {
+                f(i);
+            }
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::apply_twice(3, |x| x * 2), 12);
+        let total = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let total_in_closure = total.clone();
+        ffi::repeat(4, move |i| {
+            total_in_closure.fetch_add(i, std::sync::atomic::Ordering::SeqCst);
+        });
+        assert_eq!(total.load(std::sync::atomic::Ordering::SeqCst), 6);
+    };
+    run_test("", hdr, rs, &["apply_twice", "repeat"], &[]);
+}
+
+#[test]
+fn test_take_function_pointer() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        typedef uint32_t (*transform_t)(uint32_t);
+        inline uint32_t apply(uint32_t val, uint32_t (*f)(uint32_t)) {
+            return f(val);
+        }
+        inline uint32_t apply_typedef(uint32_t val, transform_t f) {
+            return f(val);
+        }
+    "};
+    let rs = quote! {
+        unsafe extern "C" fn triple(val: u32) -> u32 {
+            val * 3
+        }
+        assert_eq!(ffi::apply(2, triple), 6);
+        assert_eq!(ffi::apply_typedef(3, triple), 9);
+    };
+    run_test("", hdr, rs, &["apply", "apply_typedef"], &[]);
+}
+
 #[test]
 fn test_typedef_to_std() {
     let hdr = indoc! {"
@@ -5450,6 +5770,106 @@ fn test_private_inheritance() {
     run_test("", hdr, rs, &["A", "B"], &[]);
 }
 
+#[test]
+fn test_multiple_inheritance_casts() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        class Object {
+        public:
+            virtual ~Object() {}
+            uint32_t get_id() const { return id; }
+        private:
+            uint32_t id = 1;
+        };
+        class Observer {
+        public:
+            virtual ~Observer() {}
+            uint32_t get_events() const { return events; }
+        private:
+            uint32_t events = 2;
+        };
+        class Widget : public Object, public Observer {
+        public:
+            uint32_t get_width() const { return width; }
+        private:
+            uint32_t width = 3;
+        };
+        class Button : public Widget {};
+        class Left : public Object {};
+        class Right : public Object {};
+        class Diamond : public Left, public Right {};
+        inline std::unique_ptr<Button> make_button() {
+            return std::make_unique<Button>();
+        }
+    "};
+    let rs = quote! {
+        let button = ffi::make_button();
+        let button = button.as_ref().unwrap();
+        let widget: &ffi::Widget = button.as_ref();
+        assert_eq!(widget.get_width(), 3);
+        let object: &ffi::Object = button.as_ref();
+        assert_eq!(object.get_id(), 1);
+        let observer: &ffi::Observer = button.as_ref();
+        assert_eq!(observer.get_events(), 2);
+        let observer: &ffi::Observer = widget.as_ref();
+        assert_eq!(observer.get_events(), 2);
+    };
+    run_test(
+        "",
+        hdr,
+        rs,
+        &[
+            "Object",
+            "Observer",
+            "Widget",
+            "Button",
+            "Left",
+            "Right",
+            "Diamond",
+            "make_button",
+        ],
+        &[],
+    );
+}
+
+#[test]
+fn test_virtual_base_casts() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        class Object {
+        public:
+            virtual ~Object() {}
+            uint32_t get_id() const { return id; }
+        private:
+            uint32_t id = 1;
+        };
+        class Left : public virtual Object {};
+        class Right : public virtual Object {};
+        class Diamond : public Left, public Right {};
+        inline std::unique_ptr<Diamond> make_diamond() {
+            return std::make_unique<Diamond>();
+        }
+    "};
+    let rs = quote! {
+        let diamond = ffi::make_diamond();
+        let diamond = diamond.as_ref().unwrap();
+        let object: &ffi::Object = diamond.as_ref();
+        assert_eq!(object.get_id(), 1);
+        let left: &ffi::Left = diamond.as_ref();
+        let object: &ffi::Object = left.as_ref();
+        assert_eq!(object.get_id(), 1);
+    };
+    run_test(
+        "",
+        hdr,
+        rs,
+        &["Object", "Left", "Right", "Diamond", "make_diamond"],
+        &[],
+    );
+}
+
 #[test]
 fn test_error_generated_for_static_data() {
     let hdr = indoc! {"
@@ -5812,85 +6232,118 @@ fn test_stringview() {
 }
 
 #[test]
-fn test_include_cpp_alone() {
+fn test_slice_param() {
     let hdr = indoc! {"
         #include <cstdint>
-        inline uint32_t give_int() {
-            return 5;
+        #include <cstddef>
+        inline uint32_t sum(const uint32_t* data, size_t len) {
+            uint32_t total = 0;
+            for (size_t i = 0; i < len; i++) {
+                total += data[i];
+            }
+            return total;
         }
     "};
-    let hexathorpe = Token![#](Span::call_site());
     let rs = quote! {
-        use autocxx::include_cpp;
-        include_cpp! {
-            #hexathorpe include "input.h"
-            safety!(unsafe_ffi)
-            generate!("give_int")
-        }
-        fn main() {
-            assert_eq!(ffi::give_int(), 5);
-        }
+        assert_eq!(ffi::sum(&[1, 2, 3]), 6);
     };
-    do_run_test_manual("", hdr, rs, None, None).unwrap();
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("sum")
+            slice_param!("sum", data, len)
+        },
+        None,
+        None,
+        None,
+    );
 }
 
 #[test]
-fn test_include_cpp_in_path() {
+fn test_utf_strings() {
     let hdr = indoc! {"
+        #include <string>
         #include <cstdint>
-        inline uint32_t give_int() {
-            return 5;
+        inline uint32_t u16_length(const std::u16string& s) {
+            return s.size();
+        }
+        inline std::u16string u16_greeting() {
+            return u\"Grüße\";
+        }
+        inline uint32_t u32_length(const std::u32string& s) {
+            return s.size();
+        }
+        inline std::u32string u32_reversed(const std::u32string& s) {
+            return std::u32string(s.rbegin(), s.rend());
         }
     "};
-    let hexathorpe = Token![#](Span::call_site());
     let rs = quote! {
-            autocxx::include_cpp! {
-                #hexathorpe include "input.h"
-                safety!(unsafe_ffi)
-                generate!("give_int")
-            }
-            fn main() {
-                assert_eq!(ffi::give_int(), 5);
-            }
+        use ffi::ToCppU16String;
+        use ffi::ToCppU32String;
+        assert_eq!(ffi::u16_length(&"🦀s".into_cpp_u16string()), 3);
+        assert_eq!(ffi::u16_greeting().to_string_lossy(), "Grüße");
+        assert_eq!(ffi::u32_length(&"🦀s".to_string().into_cpp_u32string()), 2);
+        assert_eq!(
+            ffi::u32_reversed(&"abc🦀".into_cpp_u32string()).to_string_lossy(),
+            "🦀cba"
+        );
     };
-    do_run_test_manual("", hdr, rs, None, None).unwrap();
+    run_test(
+        "",
+        hdr,
+        rs,
+        &["u16_length", "u16_greeting", "u32_length", "u32_reversed"],
+        &[],
+    );
 }
 
 #[test]
-fn test_bitset() {
+fn test_variadic() {
     let hdr = indoc! {"
-        #include <cstddef>
-        template <size_t _N_words, size_t _Size>
-        class __bitset
-        {
-        public:
-            typedef size_t              __storage_type;
-            __storage_type __first_[_N_words];
-            inline bool all() {}
-        };
-
-        template <size_t _Size>
-        class bitset
-            : private __bitset<_Size == 0 ? 0 : (_Size - 1) / (sizeof(size_t) * 8) + 1, _Size>
-        {
-        public:
-            static const unsigned __n_words = _Size == 0 ? 0 : (_Size - 1) / (sizeof(size_t) * 8) + 1;
-            typedef __bitset<__n_words, _Size> base;
-            bool all() const noexcept;
-        };
-
-
-        typedef bitset<1> mybitset;
+        #include <cstdarg>
+        #include <cstdint>
+        #include <cstdio>
+        #include <string>
+        inline std::string& last_message() {
+            static std::string message;
+            return message;
+        }
+        inline void vformat_message(const char* fmt, va_list args) {
+            char buf[100];
+            vsnprintf(buf, sizeof(buf), fmt, args);
+            last_message() = buf;
+        }
+        inline void format_message(const char* fmt, ...) {
+            va_list args;
+            va_start(args, fmt);
+            vformat_message(fmt, args);
+            va_end(args);
+        }
+        inline uint32_t message_length() {
+            return last_message().size();
+        }
     "};
-
-    let rs = quote! {};
-
+    let rs = quote! {
+        let fmt = std::ffi::CString::new("%d %s").unwrap();
+        let goats = std::ffi::CString::new("goats").unwrap();
+        unsafe { ffi::format_message(fmt.as_ptr(), 42, goats.as_c_str()) };
+        assert_eq!(ffi::message_length(), 8);
+        let fmt = std::ffi::CString::new("hello").unwrap();
+        unsafe { ffi::format_message1(fmt.as_ptr()) };
+        assert_eq!(ffi::message_length(), 5);
+    };
     run_test_ex(
         "",
         hdr,
         rs,
         quote! {
-            generate_all!()
+            generate!("format_message")
+            generate!("vformat_message")
+            generate!("message_length")
+            variadic!("format_message", [i32, &CStr])
+            variadic!("format_message", [])
         },
         None,
         None,
@@ -5899,100 +6352,169 @@ fn test_bitset() {
 }
 
 #[test]
-fn test_cint_vector() {
+fn test_default_params() {
     let hdr = indoc! {"
-        #include <vector>
         #include <cstdint>
-        inline std::vector<int32_t> give_vec() {
-            return std::vector<int32_t> {1,2};
+        inline uint32_t sum(uint32_t a, uint32_t b = 20, uint32_t c = 300) {
+            return a + b + c;
         }
+        class Counter {
+        public:
+            uint32_t add(uint32_t by = 1) { total += by; return total; }
+        private:
+            uint32_t total = 0;
+        };
     "};
-
     let rs = quote! {
-        assert_eq!(ffi::give_vec().as_ref().unwrap().as_slice(), &[1,2]);
+        assert_eq!(ffi::sum(1, 2, 3), 6);
+        assert_eq!(ffi::sum_defaulting_c(1, 2), 303);
+        assert_eq!(ffi::sum_defaulting_b(1), 321);
+        let mut counter = ffi::Counter::make_unique();
+        assert_eq!(counter.pin_mut().add(5), 5);
+        assert_eq!(counter.pin_mut().add_defaulting_by(), 6);
     };
-
-    run_test("", hdr, rs, &["give_vec"], &[]);
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("sum")
+            generate!("Counter")
+            defaults!("sum", b)
+            defaults!("Counter::add", by)
+        },
+        None,
+        None,
+        None,
+    );
 }
 
 #[test]
-#[ignore] // https://github.com/google/autocxx/issues/422
-fn test_int_vector() {
+fn test_downcast() {
     let hdr = indoc! {"
-        #include <vector>
-        std::vector<int> give_vec() {
-            return std::vector<int> {1,2};
+        #include <cstdint>
+        #include <memory>
+        class Shape {
+        public:
+            virtual ~Shape() {}
+            virtual uint32_t sides() const = 0;
+        };
+        class Square : public Shape {
+        public:
+            uint32_t sides() const override { return 4; }
+            uint32_t get_width() const { return width; }
+            void grow() { width++; }
+        private:
+            uint32_t width = 2;
+        };
+        class Triangle : public Shape {
+        public:
+            uint32_t sides() const override { return 3; }
+        };
+        inline std::unique_ptr<Shape> make_square() {
+            return std::make_unique<Square>();
         }
     "};
-
     let rs = quote! {
-        assert_eq!(ffi::give_vec().as_ref().unwrap().as_slice(), &[autocxx::c_int(1),autocxx::c_int(2)]);
+        use autocxx::Downcast;
+        let mut shape = ffi::make_square();
+        assert_eq!(shape.sides(), 4);
+        assert!(shape.as_ref().unwrap().downcast_ref::<ffi::Triangle>().is_none());
+        let square = shape.as_ref().unwrap().downcast_ref::<ffi::Square>().unwrap();
+        assert_eq!(square.get_width(), 2);
+        shape.pin_mut().downcast_mut::<ffi::Square>().unwrap().grow();
+        assert!(shape.pin_mut().downcast_mut::<ffi::Triangle>().is_none());
+        assert_eq!(shape.as_ref().unwrap().downcast_ref::<ffi::Square>().unwrap().get_width(), 3);
     };
-
-    run_test("", hdr, rs, &["give_vec"], &[]);
+    run_test(
+        "",
+        hdr,
+        rs,
+        &["Shape", "Square", "Triangle", "make_square"],
+        &[],
+    );
 }
 
 #[test]
-fn test_deleted_function() {
-    // We shouldn't generate bindings for deleted functions.
-    // The test is successful if the bindings compile, i.e. if autocxx doesn't
-    // attempt to call the deleted function.
+fn test_ownership_transfer() {
     let hdr = indoc! {"
-        class A {
+        #include <cstdint>
+        class Widget {
         public:
-            void foo() = delete;
+            Widget(uint32_t size) : size(size) {}
+            uint32_t get_size() const { return size; }
+        private:
+            uint32_t size;
         };
+        inline Widget* create_widget(uint32_t size) {
+            return new Widget(size);
+        }
+        inline uint32_t adopt_widget(Widget* widget) {
+            uint32_t size = widget->get_size();
+            delete widget;
+            return size;
+        }
     "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["A"], &[]);
+    let rs = quote! {
+        let widget = ffi::create_widget(4);
+        assert_eq!(widget.get_size(), 4);
+        assert_eq!(ffi::adopt_widget(widget), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("Widget")
+            generate!("create_widget")
+            generate!("adopt_widget")
+            returns_owned!("create_widget")
+            takes_ownership!("adopt_widget", widget)
+        },
+        None,
+        None,
+        None,
+    );
 }
 
 #[test]
-fn test_ignore_move_constructor() {
+fn test_lifetime_param() {
     let hdr = indoc! {"
-        class A {
-        public:
-            A() {}
-            A(A&&) {};
+        #include <cstdint>
+        struct A {
+            uint32_t a;
         };
-    "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["A"], &[]);
-}
-
-#[test]
-fn test_ignore_function_with_rvalue_ref() {
-    let hdr = indoc! {"
-        #include <string>
-
-        void moveme(std::string &&);
-    "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["moveme"], &[]);
-}
-
-#[test]
-fn test_overloaded_ignored_function() {
-    // When overloaded functions are ignored during import, the placeholder
-    // functions generated for them should have unique names, just as they
-    // would have if they had been imported successfully.
-    // The test is successful if the bindings compile.
-    let hdr = indoc! {"
-        struct Blocked {};
-        class A {
-        public:
-            void take_blocked(Blocked);
-            void take_blocked(Blocked, int);
+        struct B {
+            uint32_t b;
+        };
+        inline const uint32_t& pick_a(const A& a, const B&) {
+            return a.a;
+        }
+        struct Holder {
+            uint32_t val;
+            const uint32_t& get(const A&) const {
+                return val;
+            }
         };
     "};
-    let rs = quote! {};
+    let rs = quote! {
+        let a = ffi::A { a: 3 };
+        let b = ffi::B { b: 4 };
+        assert_eq!(*ffi::pick_a(&a, &b), 3);
+        let h = ffi::Holder { val: 5 };
+        assert_eq!(*h.get(&a), 5);
+    };
     run_test_ex(
         "",
         hdr,
         rs,
         quote! {
-            generate!("A")
-            block!("Blocked")
+            generate!("pick_a")
+            generate_pod!("A")
+            generate_pod!("B")
+            generate_pod!("Holder")
+            lifetime!("pick_a", a)
+            lifetime!("Holder::get", self)
         },
         None,
         None,
@@ -6001,36 +6523,63 @@ fn test_overloaded_ignored_function() {
 }
 
 #[test]
-fn test_namespaced_constant() {
+fn test_lifetimebound_attribute() {
     let hdr = indoc! {"
-        namespace A {
-            const int kConstant = 3;
+        #include <cstdint>
+        #ifdef __clang__
+        #define AUTOCXX_LIFETIME_BOUND [[clang::lifetimebound]]
+        #else
+        #define AUTOCXX_LIFETIME_BOUND
+        #endif
+        struct A {
+            uint32_t a;
+        };
+        struct B {
+            uint32_t b;
+        };
+        inline const uint32_t& pick_a(const A& a AUTOCXX_LIFETIME_BOUND, const B&) {
+            return a.a;
         }
+        struct Holder {
+            uint32_t val;
+            const uint32_t& get(const A&) const AUTOCXX_LIFETIME_BOUND {
+                return val;
+            }
+        };
     "};
     let rs = quote! {
-        assert_eq!(ffi::A::kConstant, 3);
+        let a = ffi::A { a: 3 };
+        let b = ffi::B { b: 4 };
+        assert_eq!(*ffi::pick_a(&a, &b), 3);
+        let h = ffi::Holder { val: 5 };
+        assert_eq!(*h.get(&a), 5);
     };
-    run_test("", hdr, rs, &["A::kConstant"], &[]);
+    run_test("", hdr, rs, &["pick_a"], &["A", "B", "Holder"]);
 }
 
 #[test]
-fn test_issue_470_492() {
+fn test_pointer_nullability() {
     let hdr = indoc! {"
-        namespace std {
-            template <bool, typename _Iftrue, typename _Iffalse> struct a;
+        #include <cstdint>
+        inline void copy_value(uint32_t* dest, const uint32_t* src) {
+            *dest = src ? *src : 0;
         }
-        template <typename> struct b;
-        template <typename d> struct c {
-            typedef std::a<b<d>::c, int, int> e;
-        };
     "};
-    let rs = quote! {};
+    let rs = quote! {
+        let mut dest = 3u32;
+        ffi::copy_value(std::pin::Pin::new(&mut dest), Some(&7));
+        assert_eq!(dest, 7);
+        ffi::copy_value(std::pin::Pin::new(&mut dest), None);
+        assert_eq!(dest, 0);
+    };
     run_test_ex(
         "",
         hdr,
         rs,
         quote! {
-            generate_all!()
+            generate!("copy_value")
+            nonnull!("copy_value", dest)
+            nullable!("copy_value", src)
         },
         None,
         None,
@@ -6039,21 +6588,42 @@ fn test_issue_470_492() {
 }
 
 #[test]
-fn test_no_impl() {
+fn test_pointer_nullability_attributes() {
     let hdr = indoc! {"
-        struct A {
-            int a;
+        #include <cstdint>
+        #ifdef __clang__
+        #define AUTOCXX_NONNULL _Nonnull
+        #define AUTOCXX_NULLABLE _Nullable
+        #else
+        #define AUTOCXX_NONNULL
+        #define AUTOCXX_NULLABLE
+        #endif
+        inline void copy_value(uint32_t* AUTOCXX_NONNULL dest, const uint32_t* AUTOCXX_NULLABLE src) {
+            *dest = src ? *src : 0;
+        }
+        class Counter {
+        public:
+            uint32_t count = 0;
+            void add(const uint32_t* AUTOCXX_NONNULL amount) { count += *amount; }
         };
     "};
-    let rs = quote! {};
+    let rs = quote! {
+        let mut dest = 3u32;
+        ffi::copy_value(std::pin::Pin::new(&mut dest), Some(&7));
+        assert_eq!(dest, 7);
+        ffi::copy_value(std::pin::Pin::new(&mut dest), None);
+        assert_eq!(dest, 0);
+        let mut counter = ffi::Counter { count: 1 };
+        counter.add(&2);
+        assert_eq!(counter.count, 3);
+    };
     run_test_ex(
         "",
         hdr,
         rs,
         quote! {
-            exclude_impls!()
-            exclude_utilities!()
-            generate!("A")
+            generate!("copy_value")
+            generate_pod!("Counter")
         },
         None,
         None,
@@ -6062,22 +6632,40 @@ fn test_no_impl() {
 }
 
 #[test]
-fn test_generate_all() {
+fn test_result_of() {
     let hdr = indoc! {"
         #include <cstdint>
-        inline uint32_t give_int() {
-            return 5;
+        enum class OpenError {
+            Ok,
+            NotFound,
+        };
+        inline OpenError open_file(uint32_t id, uint32_t* handle) {
+            if (id == 0) {
+                return OpenError::NotFound;
+            }
+            *handle = id + 100;
+            return OpenError::Ok;
+        }
+        inline bool close_file(uint32_t handle) {
+            return handle > 100;
         }
     "};
     let rs = quote! {
-        assert_eq!(ffi::give_int(), 5);
+        assert_eq!(unsafe { ffi::open_file(3) }.unwrap(), 103);
+        assert!(unsafe { ffi::open_file(0) }.unwrap_err() == ffi::OpenError::NotFound);
+        assert!(ffi::close_file(103).is_ok());
+        assert!(ffi::close_file(3).is_err());
     };
     run_test_ex(
         "",
         hdr,
         rs,
         quote! {
-            generate_all!()
+            generate!("open_file")
+            generate!("close_file")
+            generate!("OpenError")
+            result_of!("open_file", ok_when = "OpenError::Ok", out = "handle")
+            result_of!("close_file", ok_when = "true")
         },
         None,
         None,
@@ -6086,23 +6674,24 @@ fn test_generate_all() {
 }
 
 #[test]
-fn test_std_thing() {
+fn test_result_of_out_is_unsafe() {
     let hdr = indoc! {"
         #include <cstdint>
-        namespace std {
-            struct A {
-                uint8_t a;
-            };
+        inline bool get_answer(uint32_t* answer) {
+            *answer = 42;
+            return true;
         }
-        typedef char daft;
     "};
-    let rs = quote! {};
-    run_test_ex(
+    let rs = quote! {
+        assert_eq!(ffi::get_answer().unwrap(), 42);
+    };
+    run_test_expect_fail_ex(
         "",
         hdr,
         rs,
         quote! {
-            generate_all!()
+            generate!("get_answer")
+            result_of!("get_answer", ok_when = "true", out = "answer")
         },
         None,
         None,
@@ -6111,103 +6700,216 @@ fn test_std_thing() {
 }
 
 #[test]
-fn test_two_mods() {
+fn test_result_of_non_pod_status() {
     let hdr = indoc! {"
         #include <cstdint>
-        struct A {
-            uint32_t a;
-        };
-        inline A give_a() {
-            A a;
-            a.a = 5;
-            return a;
-        }
-        inline uint32_t get_a(A a) {
-            return a.a;
-        }
-        struct B {
-            uint32_t a;
+        #include <string>
+        class Status {
+        public:
+            Status() {}
+            Status(std::string message) : message_(message) {}
+            bool ok() const { return message_.empty(); }
+            const std::string& message() const { return message_; }
+        private:
+            std::string message_;
         };
-        inline B give_b() {
-            B a;
-            a.a = 8;
-            return a;
+        inline Status load(uint32_t id, uint32_t* data) {
+            if (id == 0) {
+                return Status(\"not found\");
+            }
+            *data = id * 2;
+            return Status();
         }
-        inline uint32_t get_b(B a) {
-            return a.a;
+        inline Status save(uint32_t id) {
+            return id == 0 ? Status(\"read only\") : Status();
         }
     "};
-    let hexathorpe = Token![#](Span::call_site());
     let rs = quote! {
-        autocxx::include_cpp! {
-            #hexathorpe include "input.h"
-            safety!(unsafe_ffi)
-            generate!("give_a")
-            generate!("get_a")
-        }
-        autocxx::include_cpp! {
-            #hexathorpe include "input.h"
-            name!(ffi2)
-            generate!("give_b")
-            generate!("get_b")
+        assert_eq!(unsafe { ffi::load(3) }.unwrap(), 6);
+        let err = unsafe { ffi::load(0) }.unwrap_err();
+        assert_eq!(err.message().to_str().unwrap(), "not found");
+        assert!(ffi::save(3).is_ok());
+        assert_eq!(ffi::save(0).unwrap_err().message().to_str().unwrap(), "read only");
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("load")
+            generate!("save")
+            generate!("Status")
+            result_of!("load", ok_if = "status.ok()", out = "data")
+            result_of!("save", ok_if = "status.ok()")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_throws() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <stdexcept>
+        inline uint32_t parse(uint32_t val) {
+            if (val == 0) {
+                throw std::runtime_error(\"zero\");
+            }
+            return val;
         }
-        fn main() {
-            let a = ffi::give_a();
-            assert_eq!(ffi::get_a(a), 5);
-            let b = unsafe { ffi2::give_b() };
-            assert_eq!(unsafe { ffi2::get_b(b) }, 8);
+        class Reader {
+        public:
+            void read(uint32_t len) const {
+                if (len > 4) {
+                    throw std::out_of_range(\"too long\");
+                }
+            }
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::parse(3).unwrap(), 3);
+        assert_eq!(ffi::parse(0).unwrap_err().what(), "zero");
+        let reader = ffi::Reader {};
+        assert!(reader.read(2).is_ok());
+        assert_eq!(reader.read(5).unwrap_err().what(), "too long");
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("parse")
+            generate_pod!("Reader")
+            throws!("parse")
+            throws!("Reader::read")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_throws_auto_skips_noexcept() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <stdexcept>
+        inline uint32_t parse(uint32_t val) {
+            if (val == 0) {
+                throw std::runtime_error(\"zero\");
+            }
+            return val;
         }
+        inline uint32_t twice(uint32_t val) noexcept {
+            return val * 2;
+        }
+        class Reader {
+        public:
+            uint32_t len() const noexcept { return 4; }
+            void read(uint32_t len) const {
+                if (len > 4) {
+                    throw std::out_of_range(\"too long\");
+                }
+            }
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::parse(0).unwrap_err().what(), "zero");
+        assert_eq!(ffi::twice(3), 6);
+        let reader = ffi::Reader {};
+        assert_eq!(reader.len(), 4);
+        assert_eq!(reader.read(5).unwrap_err().what(), "too long");
     };
-    do_run_test_manual("", hdr, rs, None, None).unwrap();
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("parse")
+            generate!("twice")
+            generate_pod!("Reader")
+            throws!(auto)
+        },
+        None,
+        None,
+        None,
+    );
 }
 
+#[cfg_attr(skip_windows_msvc_failing_tests, ignore)]
+#[cfg_attr(skip_windows_gnu_failing_tests, ignore)]
 #[test]
-fn test_manual_bridge() {
+fn test_span() {
     let hdr = indoc! {"
+        #include <span>
         #include <cstdint>
-        inline uint32_t give_int() {
-            return 5;
+        inline uint32_t sum_span(std::span<uint32_t> data) {
+            uint32_t total = 0;
+            for (auto i : data) {
+                total += i;
+            }
+            return total;
         }
-        inline uint32_t give_int2() {
+        inline std::span<uint32_t> first_two(std::span<uint32_t> data) {
+            return data.first(2);
+        }
+        inline uint32_t max_span(std::span<const uint32_t> data) {
+            uint32_t max = 0;
+            for (auto i : data) {
+                max = i > max ? i : max;
+            }
+            return max;
+        }
+    "};
+    let rs = quote! {
+        let mut data = [1, 2, 3];
+        assert_eq!(ffi::sum_span(&mut data), 6);
+        assert_eq!(ffi::first_two(&mut data), &[1, 2]);
+        let shared: &[u32] = &data;
+        assert_eq!(ffi::max_span(shared), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["sum_span", "first_two", "max_span"], &[], None),
+        make_clang_arg_adder(&["-std=c++20"]),
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_include_cpp_alone() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t give_int() {
             return 5;
         }
     "};
     let hexathorpe = Token![#](Span::call_site());
     let rs = quote! {
-        autocxx::include_cpp! {
+        use autocxx::include_cpp;
+        include_cpp! {
             #hexathorpe include "input.h"
             safety!(unsafe_ffi)
             generate!("give_int")
         }
-        #[cxx::bridge]
-        mod ffi2 {
-            unsafe extern "C++" {
-                include!("input.h");
-                fn give_int2() -> u32;
-            }
-        }
         fn main() {
             assert_eq!(ffi::give_int(), 5);
-            assert_eq!(ffi2::give_int2(), 5);
         }
     };
     do_run_test_manual("", hdr, rs, None, None).unwrap();
 }
 
 #[test]
-fn test_manual_bridge_mixed_types() {
+fn test_include_cpp_in_path() {
     let hdr = indoc! {"
-        #include <memory>
-        struct A {
-            int a;
-        };
-        inline int take_A(const A& a) {
-            return a.a;
-        }
-        inline std::unique_ptr<A> give_A() {
-            auto a = std::make_unique<A>();
-            a->a = 5;
-            return a;
+        #include <cstdint>
+        inline uint32_t give_int() {
+            return 5;
         }
     "};
     let hexathorpe = Token![#](Span::call_site());
@@ -6215,366 +6917,1180 @@ fn test_manual_bridge_mixed_types() {
             autocxx::include_cpp! {
                 #hexathorpe include "input.h"
                 safety!(unsafe_ffi)
-                generate!("take_A")
-                generate!("A")
-            }
-            #[cxx::bridge]
-            mod ffi2 {
-                unsafe extern "C++" {
-                    include!("input.h");
-                    type A = crate::ffi::A;
-                    fn give_A() -> UniquePtr<A>;
-                }
+                generate!("give_int")
             }
             fn main() {
-                let a = ffi2::give_A();
-                assert_eq!(ffi::take_A(&a), autocxx::c_int(5));
+                assert_eq!(ffi::give_int(), 5);
             }
     };
     do_run_test_manual("", hdr, rs, None, None).unwrap();
 }
 
 #[test]
-fn test_issue486() {
+fn test_bitset() {
     let hdr = indoc! {"
-        namespace a {
-            namespace spanner {
-                class Key;
-            }
-        } // namespace a
-        namespace spanner {
-            class Key {
-                public:
-                    bool b(a::spanner::Key &);
-            };
-        } // namespace spanner
-    "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["spanner::Key"], &[]);
-}
+        #include <cstddef>
+        template <size_t _N_words, size_t _Size>
+        class __bitset
+        {
+        public:
+            typedef size_t              __storage_type;
+            __storage_type __first_[_N_words];
+            inline bool all() {}
+        };
 
-#[test]
-#[ignore]
-fn test_issue616() {
-    let hdr = indoc! {"
-        namespace N {
-            template <typename> class B{};
-            template <typename c> class C {
-            public:
-            using U = B<c>;
-            };
-            }
-            class A : N::C<A> {
-            U u;
+        template <size_t _Size>
+        class bitset
+            : private __bitset<_Size == 0 ? 0 : (_Size - 1) / (sizeof(size_t) * 8) + 1, _Size>
+        {
+        public:
+            static const unsigned __n_words = _Size == 0 ? 0 : (_Size - 1) / (sizeof(size_t) * 8) + 1;
+            typedef __bitset<__n_words, _Size> base;
+            bool all() const noexcept;
         };
+
+
+        typedef bitset<1> mybitset;
     "};
+
     let rs = quote! {};
-    run_test("", hdr, rs, &["A"], &[]);
+
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_all!()
+        },
+        None,
+        None,
+        None,
+    );
 }
 
 #[test]
-fn test_shared_ptr() {
+fn test_cint_vector() {
     let hdr = indoc! {"
-        #include <memory>
-        struct A {
-            int a;
-        };
-        inline std::shared_ptr<A> make_shared_int() {
-            return std::make_shared<A>(A { 3 });
-        }
-        inline int take_shared_int(std::shared_ptr<A> a) {
-            return a->a;
-        }
-        inline std::weak_ptr<A> shared_to_weak(std::shared_ptr<A> a) {
-            return std::weak_ptr<A>(a);
+        #include <vector>
+        #include <cstdint>
+        inline std::vector<int32_t> give_vec() {
+            return std::vector<int32_t> {1,2};
         }
     "};
+
     let rs = quote! {
-        let a = ffi::make_shared_int();
-        assert_eq!(ffi::take_shared_int(a.clone()), autocxx::c_int(3));
-        ffi::shared_to_weak(a).upgrade();
+        assert_eq!(ffi::give_vec().as_ref().unwrap().as_slice(), &[1,2]);
     };
-    run_test(
-        "",
-        hdr,
-        rs,
-        &["make_shared_int", "take_shared_int", "shared_to_weak"],
-        &[],
-    );
+
+    run_test("", hdr, rs, &["give_vec"], &[]);
 }
 
 #[test]
-#[ignore] // https://github.com/google/autocxx/issues/799
-fn test_shared_ptr_const() {
+#[ignore] // https://github.com/google/autocxx/issues/422
+fn test_int_vector() {
     let hdr = indoc! {"
-        #include <memory>
-        inline std::shared_ptr<const int> make_shared_int() {
-            return std::make_shared<const int>(3);
-        }
-        inline int take_shared_int(std::shared_ptr<const int> a) {
-            return *a;
+        #include <vector>
+        std::vector<int> give_vec() {
+            return std::vector<int> {1,2};
         }
     "};
+
     let rs = quote! {
-        let a = ffi::make_shared_int();
-        assert_eq!(ffi::take_shared_int(a.clone()), autocxx::c_int(3));
+        assert_eq!(ffi::give_vec().as_ref().unwrap().as_slice(), &[autocxx::c_int(1),autocxx::c_int(2)]);
     };
-    run_test("", hdr, rs, &["make_shared_int", "take_shared_int"], &[]);
+
+    run_test("", hdr, rs, &["give_vec"], &[]);
 }
 
 #[test]
-fn test_rust_reference() {
-    let hdr = indoc! {"
-    #include <cstdint>
+fn test_deleted_function() {
+    // We shouldn't generate bindings for deleted functions.
+    // The test is successful if the bindings compile, i.e. if autocxx doesn't
+    // attempt to call the deleted function.
+    let hdr = indoc! {"
+        class A {
+        public:
+            void foo() = delete;
+        };
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &["A"], &[]);
+}
 
-    struct RustType;
-    inline uint32_t take_rust_reference(const RustType&) {
-        return 4;
-    }
+#[test]
+fn test_ignore_move_constructor() {
+    let hdr = indoc! {"
+        class A {
+        public:
+            A() {}
+            A(A&&) {};
+        };
     "};
-    let rs = quote! {
-        let foo = RustType(3);
-        assert_eq!(ffi::take_rust_reference(&foo), 4);
-    };
-    run_test_ex(
-        "",
-        hdr,
-        rs,
-        quote! {
-            generate!("take_rust_reference")
-            extern_rust_type!(RustType)
-        },
-        None,
-        None,
-        Some(quote! {
-            pub struct RustType(i32);
-        }),
-    );
+    let rs = quote! {};
+    run_test("", hdr, rs, &["A"], &[]);
 }
 
 #[test]
-fn test_rust_reference_autodiscover() {
+fn test_ignore_function_with_rvalue_ref() {
     let hdr = indoc! {"
-    #include <cstdint>
+        #include <string>
 
-    struct RustType;
-    inline uint32_t take_rust_reference(const RustType&) {
-        return 4;
-    }
+        void moveme(std::string &&);
     "};
-    let rs = quote! {
-        let foo = RustType(3);
-        let result = ffi::take_rust_reference(&foo);
-        assert_eq!(result, 4);
-    };
-    run_test_ex(
-        "",
-        hdr,
-        rs,
-        quote! {},
-        Some(Box::new(EnableAutodiscover)),
-        None,
-        Some(quote! {
-            #[autocxx::extern_rust::extern_rust_type]
-            pub struct RustType(i32);
-        }),
-    );
+    let rs = quote! {};
+    run_test("", hdr, rs, &["moveme"], &[]);
 }
 
 #[test]
-fn test_pass_thru_rust_reference() {
+fn test_overloaded_ignored_function() {
+    // When overloaded functions are ignored during import, the placeholder
+    // functions generated for them should have unique names, just as they
+    // would have if they had been imported successfully.
+    // The test is successful if the bindings compile.
     let hdr = indoc! {"
-    #include <cstdint>
-
-    struct RustType;
-    inline const RustType& pass_rust_reference(const RustType& a) {
-        return a;
-    }
+        struct Blocked {};
+        class A {
+        public:
+            void take_blocked(Blocked);
+            void take_blocked(Blocked, int);
+        };
     "};
-    let rs = quote! {
-        let foo = RustType(3);
-        assert_eq!(ffi::pass_rust_reference(&foo).0, 3);
-    };
+    let rs = quote! {};
     run_test_ex(
         "",
         hdr,
         rs,
         quote! {
-            generate!("pass_rust_reference")
-            extern_rust_type!(RustType)
+            generate!("A")
+            block!("Blocked")
         },
         None,
         None,
-        Some(quote! {
-            pub struct RustType(i32);
-        }),
+        None,
     );
 }
 
 #[test]
-fn test_extern_rust_method() {
+fn test_namespaced_constant() {
     let hdr = indoc! {"
-        #include <cstdint>
-        struct RustType;
-        uint32_t examine(const RustType& foo);
+        namespace A {
+            const int kConstant = 3;
+        }
     "};
-    let cxx = indoc! {"
-        uint32_t examine(const RustType& foo) {
-            return foo.get();
-        }"};
     let rs = quote! {
-        let a = RustType(74);
-        assert_eq!(ffi::examine(&a), 74);
+        assert_eq!(ffi::A::kConstant, 3);
     };
-    run_test_ex(
-        cxx,
-        hdr,
-        rs,
-        directives_from_lists(&["examine"], &[], None),
-        Some(Box::new(EnableAutodiscover)),
-        None,
-        Some(quote! {
-            #[autocxx::extern_rust::extern_rust_type]
-            pub struct RustType(i32);
-            impl RustType {
-                #[autocxx::extern_rust::extern_rust_function]
-                pub fn get(&self) -> i32 {
-                    return self.0
-                }
-            }
-        }),
-    );
+    run_test("", hdr, rs, &["A::kConstant"], &[]);
 }
 
 #[test]
-fn test_rust_reference_no_autodiscover() {
+fn test_issue_470_492() {
     let hdr = indoc! {"
-    #include <cstdint>
-
-    struct RustType;
-    inline uint32_t take_rust_reference(const RustType&) {
-        return 4;
-    }
+        namespace std {
+            template <bool, typename _Iftrue, typename _Iffalse> struct a;
+        }
+        template <typename> struct b;
+        template <typename d> struct c {
+            typedef std::a<b<d>::c, int, int> e;
+        };
     "};
-    let rs = quote! {
-        let foo = RustType(3);
-        let result = ffi::take_rust_reference(&foo);
-        assert_eq!(result, 4);
-    };
+    let rs = quote! {};
     run_test_ex(
         "",
         hdr,
         rs,
-        directives_from_lists(&["take_rust_reference"], &[], None),
+        quote! {
+            generate_all!()
+        },
+        None,
         None,
         None,
-        Some(quote! {
-            #[autocxx::extern_rust::extern_rust_type]
-            pub struct RustType(i32);
-        }),
     );
 }
 
 #[test]
-#[cfg_attr(skip_windows_msvc_failing_tests, ignore)]
-// TODO - replace make_clang_arg_adder with something that knows how to add an MSVC-suitable
-// directive for the cc build.
-fn test_cpp17() {
+fn test_no_impl() {
     let hdr = indoc! {"
-        static_assert(__cplusplus >= 201703L, \"This file expects a C++17 compatible compiler.\");
-        inline void foo() {}
+        struct A {
+            int a;
+        };
     "};
+    let rs = quote! {};
     run_test_ex(
         "",
         hdr,
+        rs,
         quote! {
-            ffi::foo();
-        },
-        quote! {
-            generate!("foo")
+            exclude_impls!()
+            exclude_utilities!()
+            generate!("A")
         },
-        make_cpp17_adder(),
+        None,
         None,
         None,
     );
 }
 
 #[test]
-fn test_box() {
+fn test_generate_all() {
     let hdr = indoc! {"
-        #include <cxx.h>
-        struct Foo;
-        inline void take_box(rust::Box<Foo>) {
+        #include <cstdint>
+        inline uint32_t give_int() {
+            return 5;
         }
     "};
+    let rs = quote! {
+        assert_eq!(ffi::give_int(), 5);
+    };
     run_test_ex(
         "",
         hdr,
+        rs,
         quote! {
-            ffi::take_box(Box::new(Foo { a: "Hello".into() }))
-        },
-        quote! {
-            generate!("take_box")
-            extern_rust_type!(Foo)
+            generate_all!()
         },
         None,
         None,
-        Some(quote! {
-            pub struct Foo {
-                a: String,
-            }
-        }),
+        None,
     );
 }
 
 #[test]
-fn test_box_via_extern_rust() {
+fn test_std_thing() {
     let hdr = indoc! {"
-        #include <cxx.h>
-        struct Foo;
-        inline void take_box(rust::Box<Foo>) {
+        #include <cstdint>
+        namespace std {
+            struct A {
+                uint8_t a;
+            };
         }
+        typedef char daft;
     "};
+    let rs = quote! {};
     run_test_ex(
         "",
         hdr,
+        rs,
         quote! {
-            ffi::take_box(Box::new(Foo { a: "Hello".into() }))
+            generate_all!()
         },
-        quote! {},
-        Some(Box::new(EnableAutodiscover)),
         None,
-        Some(quote! {
-            #[autocxx::extern_rust::extern_rust_type]
-            pub struct Foo {
-                a: String,
-            }
-        }),
+        None,
+        None,
     );
 }
 
 #[test]
-fn test_box_via_extern_rust_in_mod() {
+fn test_two_mods() {
     let hdr = indoc! {"
-        #include <cxx.h>
-        struct Foo;
-        inline void take_box(rust::Box<Foo>) {
+        #include <cstdint>
+        struct A {
+            uint32_t a;
+        };
+        inline A give_a() {
+            A a;
+            a.a = 5;
+            return a;
         }
-    "};
-    run_test_ex(
-        "",
-        hdr,
+        inline uint32_t get_a(A a) {
+            return a.a;
+        }
+        struct B {
+            uint32_t a;
+        };
+        inline B give_b() {
+            B a;
+            a.a = 8;
+            return a;
+        }
+        inline uint32_t get_b(B a) {
+            return a.a;
+        }
+    "};
+    let hexathorpe = Token![#](Span::call_site());
+    let rs = quote! {
+        autocxx::include_cpp! {
+            #hexathorpe include "input.h"
+            safety!(unsafe_ffi)
+            generate!("give_a")
+            generate!("get_a")
+        }
+        autocxx::include_cpp! {
+            #hexathorpe include "input.h"
+            name!(ffi2)
+            generate!("give_b")
+            generate!("get_b")
+        }
+        fn main() {
+            let a = ffi::give_a();
+            assert_eq!(ffi::get_a(a), 5);
+            let b = unsafe { ffi2::give_b() };
+            assert_eq!(unsafe { ffi2::get_b(b) }, 8);
+        }
+    };
+    do_run_test_manual("", hdr, rs, None, None).unwrap();
+}
+
+#[test]
+fn test_manual_bridge() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t give_int() {
+            return 5;
+        }
+        inline uint32_t give_int2() {
+            return 5;
+        }
+    "};
+    let hexathorpe = Token![#](Span::call_site());
+    let rs = quote! {
+        autocxx::include_cpp! {
+            #hexathorpe include "input.h"
+            safety!(unsafe_ffi)
+            generate!("give_int")
+        }
+        #[cxx::bridge]
+        mod ffi2 {
+            unsafe extern "C++" {
+                include!("input.h");
+                fn give_int2() -> u32;
+            }
+        }
+        fn main() {
+            assert_eq!(ffi::give_int(), 5);
+            assert_eq!(ffi2::give_int2(), 5);
+        }
+    };
+    do_run_test_manual("", hdr, rs, None, None).unwrap();
+}
+
+#[test]
+fn test_manual_bridge_mixed_types() {
+    let hdr = indoc! {"
+        #include <memory>
+        struct A {
+            int a;
+        };
+        inline int take_A(const A& a) {
+            return a.a;
+        }
+        inline std::unique_ptr<A> give_A() {
+            auto a = std::make_unique<A>();
+            a->a = 5;
+            return a;
+        }
+    "};
+    let hexathorpe = Token![#](Span::call_site());
+    let rs = quote! {
+            autocxx::include_cpp! {
+                #hexathorpe include "input.h"
+                safety!(unsafe_ffi)
+                generate!("take_A")
+                generate!("A")
+            }
+            #[cxx::bridge]
+            mod ffi2 {
+                unsafe extern "C++" {
+                    include!("input.h");
+                    type A = crate::ffi::A;
+                    fn give_A() -> UniquePtr<A>;
+                }
+            }
+            fn main() {
+                let a = ffi2::give_A();
+                assert_eq!(ffi::take_A(&a), autocxx::c_int(5));
+            }
+    };
+    do_run_test_manual("", hdr, rs, None, None).unwrap();
+}
+
+#[test]
+fn test_issue486() {
+    let hdr = indoc! {"
+        namespace a {
+            namespace spanner {
+                class Key;
+            }
+        } // namespace a
+        namespace spanner {
+            class Key {
+                public:
+                    bool b(a::spanner::Key &);
+            };
+        } // namespace spanner
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &["spanner::Key"], &[]);
+}
+
+#[test]
+#[ignore]
+fn test_issue616() {
+    let hdr = indoc! {"
+        namespace N {
+            template <typename> class B{};
+            template <typename c> class C {
+            public:
+            using U = B<c>;
+            };
+            }
+            class A : N::C<A> {
+            U u;
+        };
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &["A"], &[]);
+}
+
+#[test]
+fn test_shared_ptr() {
+    let hdr = indoc! {"
+        #include <memory>
+        struct A {
+            int a;
+        };
+        inline std::shared_ptr<A> make_shared_int() {
+            return std::make_shared<A>(A { 3 });
+        }
+        inline int take_shared_int(std::shared_ptr<A> a) {
+            return a->a;
+        }
+        inline std::weak_ptr<A> shared_to_weak(std::shared_ptr<A> a) {
+            return std::weak_ptr<A>(a);
+        }
+    "};
+    let rs = quote! {
+        let a = ffi::make_shared_int();
+        assert_eq!(ffi::take_shared_int(a.clone()), autocxx::c_int(3));
+        ffi::shared_to_weak(a).upgrade();
+    };
+    run_test(
+        "",
+        hdr,
+        rs,
+        &["make_shared_int", "take_shared_int", "shared_to_weak"],
+        &[],
+    );
+}
+
+#[test]
+#[ignore] // https://github.com/google/autocxx/issues/799
+fn test_shared_ptr_const() {
+    let hdr = indoc! {"
+        #include <memory>
+        inline std::shared_ptr<const int> make_shared_int() {
+            return std::make_shared<const int>(3);
+        }
+        inline int take_shared_int(std::shared_ptr<const int> a) {
+            return *a;
+        }
+    "};
+    let rs = quote! {
+        let a = ffi::make_shared_int();
+        assert_eq!(ffi::take_shared_int(a.clone()), autocxx::c_int(3));
+    };
+    run_test("", hdr, rs, &["make_shared_int", "take_shared_int"], &[]);
+}
+
+#[test]
+fn test_rust_reference() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    struct RustType;
+    inline uint32_t take_rust_reference(const RustType&) {
+        return 4;
+    }
+    "};
+    let rs = quote! {
+        let foo = RustType(3);
+        assert_eq!(ffi::take_rust_reference(&foo), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("take_rust_reference")
+            extern_rust_type!(RustType)
+        },
+        None,
+        None,
+        Some(quote! {
+            pub struct RustType(i32);
+        }),
+    );
+}
+
+#[test]
+fn test_rust_reference_autodiscover() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    struct RustType;
+    inline uint32_t take_rust_reference(const RustType&) {
+        return 4;
+    }
+    "};
+    let rs = quote! {
+        let foo = RustType(3);
+        let result = ffi::take_rust_reference(&foo);
+        assert_eq!(result, 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {},
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            pub struct RustType(i32);
+        }),
+    );
+}
+
+#[test]
+fn test_pass_thru_rust_reference() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    struct RustType;
+    inline const RustType& pass_rust_reference(const RustType& a) {
+        return a;
+    }
+    "};
+    let rs = quote! {
+        let foo = RustType(3);
+        assert_eq!(ffi::pass_rust_reference(&foo).0, 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("pass_rust_reference")
+            extern_rust_type!(RustType)
+        },
+        None,
+        None,
+        Some(quote! {
+            pub struct RustType(i32);
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_method() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct RustType;
+        uint32_t examine(const RustType& foo);
+    "};
+    let cxx = indoc! {"
+        uint32_t examine(const RustType& foo) {
+            return foo.get();
+        }"};
+    let rs = quote! {
+        let a = RustType(74);
+        assert_eq!(ffi::examine(&a), 74);
+    };
+    run_test_ex(
+        cxx,
+        hdr,
+        rs,
+        directives_from_lists(&["examine"], &[], None),
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            pub struct RustType(i32);
+            impl RustType {
+                #[autocxx::extern_rust::extern_rust_function]
+                pub fn get(&self) -> i32 {
+                    return self.0
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_rust_reference_no_autodiscover() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    struct RustType;
+    inline uint32_t take_rust_reference(const RustType&) {
+        return 4;
+    }
+    "};
+    let rs = quote! {
+        let foo = RustType(3);
+        let result = ffi::take_rust_reference(&foo);
+        assert_eq!(result, 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["take_rust_reference"], &[], None),
+        None,
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            pub struct RustType(i32);
+        }),
+    );
+}
+
+#[test]
+#[cfg_attr(skip_windows_msvc_failing_tests, ignore)]
+// TODO - replace make_clang_arg_adder with something that knows how to add an MSVC-suitable
+// directive for the cc build.
+fn test_cpp17() {
+    let hdr = indoc! {"
+        static_assert(__cplusplus >= 201703L, \"This file expects a C++17 compatible compiler.\");
+        inline void foo() {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            ffi::foo();
+        },
+        quote! {
+            generate!("foo")
+        },
+        make_cpp17_adder(),
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_box() {
+    let hdr = indoc! {"
+        #include <cxx.h>
+        struct Foo;
+        inline void take_box(rust::Box<Foo>) {
+        }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            ffi::take_box(Box::new(Foo { a: "Hello".into() }))
+        },
+        quote! {
+            generate!("take_box")
+            extern_rust_type!(Foo)
+        },
+        None,
+        None,
+        Some(quote! {
+            pub struct Foo {
+                a: String,
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_box_via_extern_rust() {
+    let hdr = indoc! {"
+        #include <cxx.h>
+        struct Foo;
+        inline void take_box(rust::Box<Foo>) {
+        }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            ffi::take_box(Box::new(Foo { a: "Hello".into() }))
+        },
+        quote! {},
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            pub struct Foo {
+                a: String,
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_box_via_extern_rust_in_mod() {
+    let hdr = indoc! {"
+        #include <cxx.h>
+        struct Foo;
+        inline void take_box(rust::Box<Foo>) {
+        }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            ffi::take_box(Box::new(bar::Foo { a: "Hello".into() }))
+        },
+        quote! {},
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            mod bar {
+                #[autocxx::extern_rust::extern_rust_type]
+                pub struct Foo {
+                    pub a: String,
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_fn_simple() {
+    let cpp = indoc! {"
+        void foo() {
+            my_rust_fun();
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cxx.h>
+        inline void do_thing() {}
+    "};
+    run_test_ex(
+        cpp,
+        hdr,
+        quote! {
+            ffi::do_thing();
+        },
+        quote! {
+            generate!("do_thing")
+        },
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_function]
+            fn my_rust_fun() {
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_fn_in_mod() {
+    let hdr = indoc! {"
+        #include <cxx.h>
+        inline void do_thing() {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {},
+        quote! {
+            generate!("do_thing")
+        },
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            mod bar {
+                #[autocxx::extern_rust::extern_rust_function]
+                pub fn my_rust_fun() {
+
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_issue_956() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline void take_int(int&) {}
+        inline void take_uin16(uint16_t&) {}
+        inline void take_char16(char16_t &) {}
+    "};
+    run_test(
+        "",
+        hdr,
+        quote! {},
+        &["take_int", "take_uin16", "take_char16"],
+        &[],
+    );
+}
+
+#[test]
+fn test_extern_rust_fn_no_autodiscover() {
+    let hdr = indoc! {"
+        #include <cxx.h>
+    "};
+    let cpp = indoc! {"
+        void call_it() {
+            my_rust_fun();
+        }
+    "};
+    run_test_ex(
+        cpp,
+        hdr,
+        quote! {},
+        quote! {},
+        None,
+        None,
+        Some(quote! {
+            mod bar {
+                #[autocxx::extern_rust::extern_rust_function]
+                pub fn my_rust_fun() {
+
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_trait() {
+    let hdr = indoc! {"
+        #pragma once
+        #include <cstddef>
+        class Sink;
+        void write_hello(Sink& sink);
+        size_t total_len(const Sink& sink);
+    "};
+    let cpp = indoc! {"
+        #include \"autocxxgen_ffi.h\"
+        void write_hello(Sink& sink) {
+            const uint8_t hello[] = {'h', 'e', 'l', 'l', 'o'};
+            sink.write(rust::Slice<const uint8_t>(hello, 5));
+        }
+        size_t total_len(const Sink& sink) {
+            return sink.total();
+        }
+    "};
+    run_test_ex(
+        cpp,
+        hdr,
+        quote! {
+            let mut sink = ffi::Sink::from_rust(Box::new(Recorder::default()));
+            ffi::write_hello(sink.pin_mut());
+            ffi::write_hello(sink.pin_mut());
+            assert_eq!(ffi::total_len(&sink), 10);
+        },
+        quote! {
+            generate!("write_hello")
+            generate!("total_len")
+        },
+        None,
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_trait]
+            pub trait Sink {
+                fn write(&mut self, data: &[u8]);
+                fn total(&self) -> usize;
+            }
+
+            #[derive(Default)]
+            pub struct Recorder(Vec<u8>);
+
+            impl Sink for Recorder {
+                fn write(&mut self, data: &[u8]) {
+                    self.0.extend_from_slice(data);
+                }
+                fn total(&self) -> usize {
+                    self.0.len()
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_mut() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual void foo() = 0;
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&mut self) {
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_const() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual void foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self) {
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_calls_impossible() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual void foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {
+            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self) {
+                    use ffi::Observer_supers;
+                    self.foo_super()
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_not_pub() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual void foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {
+            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self) {
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_ptr_param() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    struct A {
+        uint8_t a;
+    };
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual void foo(const A*) const {};
+        virtual ~Observer() {}
+    };
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+        },
+        quote! {
+            generate!("A")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                unsafe fn foo(&self, a: *const ffi::A) {
+                    use ffi::Observer_supers;
+                    self.foo_super(a)
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_return() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self) -> u32 {
+                    4
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_passed_to_fn() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void take_observer(const Observer&) {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
         quote! {
-            ffi::take_box(Box::new(bar::Foo { a: "Hello".into() }))
+            let o = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            ffi::take_observer(o.borrow().as_ref());
+        },
+        quote! {
+            generate!("take_observer")
+            subclass!("Observer",MyObserver)
         },
-        quote! {},
-        Some(Box::new(EnableAutodiscover)),
+        None,
         None,
         Some(quote! {
-            mod bar {
-                #[autocxx::extern_rust::extern_rust_type]
-                pub struct Foo {
-                    pub a: String,
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self) -> u32 {
+                    4
                 }
             }
         }),
@@ -6582,155 +8098,200 @@ fn test_box_via_extern_rust_in_mod() {
 }
 
 #[test]
-fn test_extern_rust_fn_simple() {
-    let cpp = indoc! {"
-        void foo() {
-            my_rust_fun();
-        }
-    "};
+fn test_pv_subclass_derive_defaults() {
     let hdr = indoc! {"
-        #include <cxx.h>
-        inline void do_thing() {}
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void take_observer(const Observer&) {}
     "};
     run_test_ex(
-        cpp,
+        "",
         hdr,
         quote! {
-            ffi::do_thing();
+            use autocxx::subclass::CppSubclassDefault;
+            let o = MyObserver::default_rust_owned();
+            ffi::take_observer(o.borrow().as_ref());
         },
         quote! {
-            generate!("do_thing")
+            generate!("take_observer")
+            subclass!("Observer",MyObserver)
         },
-        Some(Box::new(EnableAutodiscover)),
+        None,
         None,
         Some(quote! {
-            #[autocxx::extern_rust::extern_rust_function]
-            fn my_rust_fun() {
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl ffi::Observer_methods for MyObserver {
+                fn foo(&self) -> u32 {
+                    4
+                }
             }
         }),
     );
 }
 
 #[test]
-fn test_extern_rust_fn_in_mod() {
+fn test_non_pv_subclass_simple() {
     let hdr = indoc! {"
-        #include <cxx.h>
-        inline void do_thing() {}
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual void foo() const {}
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
     "};
     run_test_ex(
         "",
         hdr,
-        quote! {},
         quote! {
-            generate!("do_thing")
+            let obs = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            obs.borrow().foo();
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserver)
         },
-        Some(Box::new(EnableAutodiscover)),
+        None,
         None,
         Some(quote! {
-            mod bar {
-                #[autocxx::extern_rust::extern_rust_function]
-                pub fn my_rust_fun() {
-
-                }
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
             }
         }),
     );
 }
 
 #[test]
-fn test_issue_956() {
+fn test_two_subclasses() {
     let hdr = indoc! {"
-        #include <cstdint>
-        inline void take_int(int&) {}
-        inline void take_uin16(uint16_t&) {}
-        inline void take_char16(char16_t &) {}
-    "};
-    run_test(
-        "",
-        hdr,
-        quote! {},
-        &["take_int", "take_uin16", "take_char16"],
-        &[],
-    );
-}
+    #include <cstdint>
 
-#[test]
-fn test_extern_rust_fn_no_autodiscover() {
-    let hdr = indoc! {"
-        #include <cxx.h>
-    "};
-    let cpp = indoc! {"
-        void call_it() {
-            my_rust_fun();
-        }
+    class Observer {
+    public:
+        Observer() {}
+        virtual void foo() const {}
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
     "};
     run_test_ex(
-        cpp,
+        "",
         hdr,
-        quote! {},
-        quote! {},
+        quote! {
+            let obs = MyObserverA::new_rust_owned(MyObserverA { a: 3, cpp_peer: Default::default() });
+            obs.borrow().foo();
+            let obs = MyObserverB::new_rust_owned(MyObserverB { a: 3, cpp_peer: Default::default() });
+            obs.borrow().foo();
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserverA)
+            subclass!("Observer",MyObserverB)
+        },
         None,
         None,
         Some(quote! {
-            mod bar {
-                #[autocxx::extern_rust::extern_rust_function]
-                pub fn my_rust_fun() {
-
-                }
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserverA {
+                a: u32
+            }
+            impl Observer_methods for MyObserverA {
+            }
+            #[autocxx::subclass::subclass]
+            pub struct MyObserverB {
+                a: u32
+            }
+            impl Observer_methods for MyObserverB {
             }
         }),
     );
 }
 
 #[test]
-fn test_pv_subclass_mut() {
+fn test_two_superclasses_with_same_name_method() {
     let hdr = indoc! {"
     #include <cstdint>
 
-    class Observer {
+    class ObserverA {
     public:
-        Observer() {}
-        virtual void foo() = 0;
-        virtual ~Observer() {}
+        ObserverA() {}
+        virtual void foo() const {}
+        virtual ~ObserverA() {}
     };
+
+    class ObserverB {
+        public:
+            ObserverB() {}
+            virtual void foo() const {}
+            virtual ~ObserverB() {}
+        };
     inline void bar() {}
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            let obs = MyObserverA::new_rust_owned(MyObserverA { a: 3, cpp_peer: Default::default() });
+            obs.borrow().foo();
+            let obs = MyObserverB::new_rust_owned(MyObserverB { a: 3, cpp_peer: Default::default() });
+            obs.borrow().foo();
         },
         quote! {
             generate!("bar")
-            subclass!("Observer",MyObserver)
+            subclass!("ObserverA",MyObserverA)
+            subclass!("ObserverB",MyObserverB)
         },
         None,
         None,
         Some(quote! {
             use autocxx::subclass::CppSubclass;
-            use ffi::Observer_methods;
+            use ffi::ObserverA_methods;
+            use ffi::ObserverB_methods;
             #[autocxx::subclass::subclass]
-            pub struct MyObserver {
+            pub struct MyObserverA {
                 a: u32
             }
-            impl Observer_methods for MyObserver {
-                fn foo(&mut self) {
-                }
+            impl ObserverA_methods for MyObserverA {
+            }
+            #[autocxx::subclass::subclass]
+            pub struct MyObserverB {
+                a: u32
+            }
+            impl ObserverB_methods for MyObserverB {
             }
         }),
     );
 }
 
 #[test]
-fn test_pv_subclass_const() {
+fn test_pv_protected_constructor() {
     let hdr = indoc! {"
     #include <cstdint>
 
     class Observer {
-    public:
+    protected:
         Observer() {}
-        virtual void foo() const = 0;
+    public:
+        virtual void foo() const {}
         virtual ~Observer() {}
     };
     inline void bar() {}
@@ -6739,7 +8300,8 @@ fn test_pv_subclass_const() {
         "",
         hdr,
         quote! {
-            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            let obs = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            obs.borrow().foo();
         },
         quote! {
             generate!("bar")
@@ -6755,31 +8317,32 @@ fn test_pv_subclass_const() {
                 a: u32
             }
             impl Observer_methods for MyObserver {
-                fn foo(&self) {
-                }
             }
         }),
     );
 }
 
 #[test]
-fn test_pv_subclass_calls_impossible() {
+fn test_pv_protected_method() {
     let hdr = indoc! {"
     #include <cstdint>
 
     class Observer {
     public:
         Observer() {}
-        virtual void foo() const = 0;
+        virtual void foo() const {}
         virtual ~Observer() {}
+    protected:
+        virtual void baz() const {}
     };
     inline void bar() {}
     "};
-    run_test_expect_fail_ex(
+    run_test_ex(
         "",
         hdr,
         quote! {
-            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            let obs = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            obs.borrow().foo();
         },
         quote! {
             generate!("bar")
@@ -6795,9 +8358,12 @@ fn test_pv_subclass_calls_impossible() {
                 a: u32
             }
             impl Observer_methods for MyObserver {
+                fn baz(&self) {
+                }
+
                 fn foo(&self) {
                     use ffi::Observer_supers;
-                    self.foo_super()
+                    self.baz_super()
                 }
             }
         }),
@@ -6805,39 +8371,50 @@ fn test_pv_subclass_calls_impossible() {
 }
 
 #[test]
-fn test_pv_subclass_not_pub() {
+fn test_subclass_methods_trait_docs() {
     let hdr = indoc! {"
     #include <cstdint>
 
     class Observer {
     public:
         Observer() {}
-        virtual void foo() const = 0;
+        virtual uint32_t get() const = 0;
+        virtual void reset() {}
         virtual ~Observer() {}
     };
-    inline void bar() {}
     "};
-    run_test_expect_fail_ex(
+    run_test_ex(
         "",
         hdr,
         quote! {
-            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            let obs = MyObserver::default_rust_owned();
+            assert_eq!(obs.borrow().get(), 3);
         },
         quote! {
-            generate!("bar")
             subclass!("Observer",MyObserver)
         },
         None,
-        None,
+        Some(make_string_finder(
+            [
+                "Pure virtual methods, which every subclass must implement:",
+                "* `get`",
+                "Other virtual methods, which a subclass may override",
+                "* `reset`",
+                "Implements the pure virtual C++ method `Observer::get`",
+                "If you don't implement this, it calls `reset_super`",
+            ]
+            .map(|s| s.to_string())
+            .to_vec(),
+        )),
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
+            use autocxx::subclass::CppSubclassDefault;
             use ffi::Observer_methods;
             #[autocxx::subclass::subclass]
-            struct MyObserver {
-                a: u32
-            }
+            #[derive(Default)]
+            pub struct MyObserver;
             impl Observer_methods for MyObserver {
-                fn foo(&self) {
+                fn get(&self) -> u32 {
+                    3
                 }
             }
         }),
@@ -6845,43 +8422,134 @@ fn test_pv_subclass_not_pub() {
 }
 
 #[test]
-fn test_pv_subclass_ptr_param() {
+fn test_pv_subclass_allocation_not_self_owned() {
     let hdr = indoc! {"
     #include <cstdint>
-    struct A {
-        uint8_t a;
-    };
+    extern \"C\" void mark_freed() noexcept;
+    extern \"C\" void mark_allocated() noexcept;
 
-    class Observer {
+    class TestObserver {
     public:
-        Observer() {}
-        virtual void foo(const A*) const {};
-        virtual ~Observer() {}
+        TestObserver() {
+            mark_allocated();
+        }
+        virtual void a() const = 0;
+        virtual ~TestObserver() {
+            mark_freed();
+        }
     };
+    inline void TriggerTestObserverA(const TestObserver& obs) {
+        obs.a();
+    }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by C++
+            let obs = MyTestObserver::new_cpp_owned(
+                MyTestObserver::new()
+            );
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            let obs_superclass = obs.as_ref().unwrap(); // &subclass
+            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
+            ffi::TriggerTestObserverA(obs_superclass);
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            std::mem::drop(obs);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by Rust
+            let obs = MyTestObserver::new_rust_owned(
+                MyTestObserver::new()
+            );
+            //let cpp_peer_ptr = unsafe { obs.borrow_mut().peer_mut().get_unchecked_mut() as *mut ffi::MyTestObserverCpp };
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            std::mem::drop(obs);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
         },
         quote! {
-            generate!("A")
-            subclass!("Observer",MyObserver)
+            generate!("TriggerTestObserverA")
+            subclass!("TestObserver",MyTestObserver)
         },
         None,
         None,
         Some(quote! {
+            use once_cell::sync::Lazy;
+            use std::sync::Mutex;
+
             use autocxx::subclass::CppSubclass;
-            use ffi::Observer_methods;
+            use ffi::TestObserver_methods;
             #[autocxx::subclass::subclass]
-            pub struct MyObserver {
-                a: u32
+            pub struct MyTestObserver {
+                data: ExternalEngine,
             }
-            impl Observer_methods for MyObserver {
-                unsafe fn foo(&self, a: *const ffi::A) {
-                    use ffi::Observer_supers;
-                    self.foo_super(a)
+            impl TestObserver_methods for MyTestObserver {
+                fn a(&self) {
+                    self.data.do_something();
+                }
+            }
+            impl MyTestObserver {
+                fn new() -> Self {
+                    Self {
+                        cpp_peer: Default::default(),
+                        data: ExternalEngine::default(),
+                    }
+                }
+            }
+
+            #[no_mangle]
+            pub fn mark_allocated() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
+            }
+
+            #[no_mangle]
+            pub fn mark_freed() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
+            }
+
+            #[derive(Default)]
+            struct Status {
+                cpp_allocated: bool,
+                rust_allocated: bool,
+                a_called: bool,
+            }
+
+            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
+
+            pub struct ExternalEngine;
+
+            impl ExternalEngine {
+                fn do_something(&self) {
+                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
+                }
+            }
+
+            impl Default for ExternalEngine {
+                fn default() -> Self {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
+                    ExternalEngine
+                }
+            }
+
+            impl Drop for ExternalEngine {
+                fn drop(&mut self) {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
                 }
             }
         }),
@@ -6889,40 +8557,158 @@ fn test_pv_subclass_ptr_param() {
 }
 
 #[test]
-fn test_pv_subclass_return() {
+fn test_pv_subclass_allocation_self_owned() {
     let hdr = indoc! {"
     #include <cstdint>
+    extern \"C\" void mark_freed() noexcept;
+    extern \"C\" void mark_allocated() noexcept;
 
-    class Observer {
+    class TestObserver {
     public:
-        Observer() {}
-        virtual uint32_t foo() const = 0;
-        virtual ~Observer() {}
+        TestObserver() {
+            mark_allocated();
+        }
+        virtual void a() const = 0;
+        virtual ~TestObserver() {
+            mark_freed();
+        }
     };
-    inline void bar() {}
+    inline void TriggerTestObserverA(const TestObserver& obs) {
+        const_cast<TestObserver&>(obs).a();
+    }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by C++
+            let obs = MyTestObserver::new_cpp_owned(
+                MyTestObserver::new(false)
+            );
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            let obs_superclass = obs.as_ref().unwrap(); // &subclass
+            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
+
+            ffi::TriggerTestObserverA(obs_superclass);
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            std::mem::drop(obs);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by Rust
+            let obs = MyTestObserver::new_rust_owned(
+                MyTestObserver::new(false)
+            );
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
+
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            std::mem::drop(obs);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when self-owned
+            let obs = MyTestObserver::new_self_owned(
+                MyTestObserver::new(true)
+            );
+            let obs_superclass_ptr: *const ffi::TestObserver = obs.as_ref().borrow().as_ref();
+            // Retain just a pointer on the Rust side, so there is no Rust-side
+            // ownership.
+            std::mem::drop(obs);
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            ffi::TriggerTestObserverA(unsafe { obs_superclass_ptr.as_ref().unwrap() });
+
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
         },
         quote! {
-            generate!("bar")
-            subclass!("Observer",MyObserver)
+            generate!("TriggerTestObserverA")
+            subclass!("TestObserver",MyTestObserver)
         },
         None,
         None,
         Some(quote! {
+            use once_cell::sync::Lazy;
+            use std::sync::Mutex;
+
             use autocxx::subclass::CppSubclass;
-            use ffi::Observer_methods;
-            #[autocxx::subclass::subclass]
-            pub struct MyObserver {
-                a: u32
+            use autocxx::subclass::CppSubclassSelfOwned;
+            use ffi::TestObserver_methods;
+            #[autocxx::subclass::subclass(self_owned)]
+            pub struct MyTestObserver {
+                data: ExternalEngine,
+                self_owning: bool,
+            }
+            impl TestObserver_methods for MyTestObserver {
+                fn a(&self) {
+                    self.data.do_something();
+                    if self.self_owning {
+                        self.delete_self();
+                    }
+                }
+            }
+            impl MyTestObserver {
+                fn new(self_owning: bool) -> Self {
+                    Self {
+                        cpp_peer: Default::default(),
+                        data: ExternalEngine::default(),
+                        self_owning,
+                    }
+                }
+            }
+
+            #[no_mangle]
+            pub fn mark_allocated() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
+            }
+
+            #[no_mangle]
+            pub fn mark_freed() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
+            }
+
+            #[derive(Default)]
+            struct Status {
+                cpp_allocated: bool,
+                rust_allocated: bool,
+                a_called: bool,
+            }
+
+            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
+
+            pub struct ExternalEngine;
+
+            impl ExternalEngine {
+                fn do_something(&self) {
+                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
+                }
             }
-            impl Observer_methods for MyObserver {
-                fn foo(&self) -> u32 {
-                    4
+
+            impl Default for ExternalEngine {
+                fn default() -> Self {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
+                    ExternalEngine
+                }
+            }
+
+            impl Drop for ExternalEngine {
+                fn drop(&mut self) {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
                 }
             }
         }),
@@ -6930,41 +8716,62 @@ fn test_pv_subclass_return() {
 }
 
 #[test]
-fn test_pv_subclass_passed_to_fn() {
+fn test_thread_safe_subclass() {
     let hdr = indoc! {"
     #include <cstdint>
+    #include <thread>
+    #include <vector>
 
     class Observer {
     public:
-        Observer() {}
-        virtual uint32_t foo() const = 0;
+        virtual void notify(uint32_t value) = 0;
         virtual ~Observer() {}
     };
-    inline void take_observer(const Observer&) {}
+    inline void notify_from_threads(const Observer& obs, uint32_t count) {
+        std::vector<std::thread> threads;
+        for (uint32_t i = 0; i < count; i++) {
+            threads.emplace_back([&obs, i]() { const_cast<Observer&>(obs).notify(i); });
+        }
+        for (auto& t : threads) {
+            t.join();
+        }
+    }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            let o = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
-            ffi::take_observer(o.borrow().as_ref());
+            let obs = MyObserver::default_cpp_owned();
+            let obs_superclass = obs.as_ref().unwrap();
+            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyObserverCpp, &ffi::Observer>(obs_superclass) };
+            ffi::notify_from_threads(obs_superclass, 8);
+            assert_eq!(TOTAL.load(Ordering::SeqCst), 28);
+            assert_eq!(CALLS.load(Ordering::SeqCst), 8);
         },
         quote! {
-            generate!("take_observer")
-            subclass!("Observer",MyObserver)
+            generate!("notify_from_threads")
+            subclass!("Observer", MyObserver, thread_safe)
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
+            use autocxx::subclass::CppSubclassThreadSafeDefault;
             use ffi::Observer_methods;
-            #[autocxx::subclass::subclass]
+            use std::sync::atomic::{AtomicU32, Ordering};
+
+            static TOTAL: AtomicU32 = AtomicU32::new(0);
+            static CALLS: AtomicU32 = AtomicU32::new(0);
+
+            #[autocxx::subclass::subclass(thread_safe)]
+            #[derive(Default)]
             pub struct MyObserver {
-                a: u32
+                calls: u32,
             }
             impl Observer_methods for MyObserver {
-                fn foo(&self) -> u32 {
-                    4
+                fn notify(&mut self, value: u32) {
+                    self.calls += 1;
+                    CALLS.store(self.calls, Ordering::SeqCst);
+                    TOTAL.fetch_add(value, Ordering::SeqCst);
                 }
             }
         }),
@@ -6972,407 +8779,366 @@ fn test_pv_subclass_passed_to_fn() {
 }
 
 #[test]
-fn test_pv_subclass_derive_defaults() {
+fn test_thread_safe_subclass_mismatch() {
     let hdr = indoc! {"
     #include <cstdint>
 
     class Observer {
     public:
-        Observer() {}
-        virtual uint32_t foo() const = 0;
+        virtual void notify(uint32_t value) = 0;
         virtual ~Observer() {}
     };
-    inline void take_observer(const Observer&) {}
     "};
-    run_test_ex(
+    run_test_expect_fail_ex(
         "",
         hdr,
+        quote! {},
         quote! {
-            use autocxx::subclass::CppSubclassDefault;
-            let o = MyObserver::default_rust_owned();
-            ffi::take_observer(o.borrow().as_ref());
-        },
-        quote! {
-            generate!("take_observer")
-            subclass!("Observer",MyObserver)
+            subclass!("Observer", MyObserver, thread_safe)
         },
         None,
         None,
         Some(quote! {
+            use ffi::Observer_methods;
+
             #[autocxx::subclass::subclass]
             #[derive(Default)]
-            pub struct MyObserver {
-                a: u32
-            }
-            impl ffi::Observer_methods for MyObserver {
-                fn foo(&self) -> u32 {
-                    4
-                }
+            pub struct MyObserver;
+            impl Observer_methods for MyObserver {
+                fn notify(&mut self, _value: u32) {}
             }
         }),
     );
 }
 
 #[test]
-fn test_non_pv_subclass_simple() {
+fn test_subclass_panic_policies() {
     let hdr = indoc! {"
     #include <cstdint>
+    #include <exception>
 
     class Observer {
     public:
-        Observer() {}
-        virtual void foo() const {}
+        virtual uint32_t get() = 0;
         virtual ~Observer() {}
     };
-    inline void bar() {}
+    inline uint32_t get_or(Observer& obs, uint32_t fallback) {
+        try {
+            return obs.get();
+        } catch (const std::exception&) {
+            return fallback;
+        }
+    }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            let obs = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
-            obs.borrow().foo();
+            let mut thrower = Thrower::default_cpp_owned();
+            assert_eq!(ffi::get_or(thrower.pin_mut().As_Observer_mut(), 99), 99);
+            let mut defaulter = Defaulter::default_cpp_owned();
+            assert_eq!(ffi::get_or(defaulter.pin_mut().As_Observer_mut(), 99), 0);
         },
         quote! {
-            generate!("bar")
-            subclass!("Observer",MyObserver)
+            generate!("get_or")
+            subclass!("Observer", Thrower, on_panic(exception))
+            subclass!("Observer", Defaulter, on_panic(return_default))
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
+            use autocxx::subclass::CppSubclassDefault;
             use ffi::Observer_methods;
+
             #[autocxx::subclass::subclass]
-            pub struct MyObserver {
-                a: u32
+            #[derive(Default)]
+            pub struct Thrower;
+            impl Observer_methods for Thrower {
+                fn get(&mut self) -> u32 {
+                    panic!("thrower")
+                }
             }
-            impl Observer_methods for MyObserver {
+
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct Defaulter;
+            impl Observer_methods for Defaulter {
+                fn get(&mut self) -> u32 {
+                    panic!("defaulter")
+                }
             }
         }),
     );
 }
 
 #[test]
-fn test_two_subclasses() {
+fn test_subclass_on_cpp_destroy() {
     let hdr = indoc! {"
-    #include <cstdint>
-
     class Observer {
     public:
-        Observer() {}
-        virtual void foo() const {}
+        virtual void notify() = 0;
         virtual ~Observer() {}
     };
-    inline void bar() {}
+    inline void destroy(Observer& obs) {
+        delete &obs;
+    }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            let obs = MyObserverA::new_rust_owned(MyObserverA { a: 3, cpp_peer: Default::default() });
-            obs.borrow().foo();
-            let obs = MyObserverB::new_rust_owned(MyObserverB { a: 3, cpp_peer: Default::default() });
-            obs.borrow().foo();
+            let destroyed = std::rc::Rc::new(std::cell::Cell::new(false));
+            let watcher = Watcher::new_cpp_owned(Watcher {
+                cpp_peer: Default::default(),
+                destroyed: destroyed.clone(),
+            });
+            let raw = watcher.into_raw();
+            unsafe { ffi::destroy(std::pin::Pin::new_unchecked(&mut *raw).As_Observer_mut()) };
+            assert!(destroyed.get());
+
+            let keeper = Keeper::default_self_owned();
+            let weak = keeper.borrow().peer_weak();
+            assert!(weak.is_alive());
+            assert!(unsafe { weak.get() }.is_some());
+            keeper.borrow().delete_self();
+            drop(keeper);
+            assert!(!weak.is_alive());
+            assert!(unsafe { weak.get() }.is_none());
         },
         quote! {
-            generate!("bar")
-            subclass!("Observer",MyObserverA)
-            subclass!("Observer",MyObserverB)
+            generate!("destroy")
+            subclass!("Observer", Watcher, on_cpp_destroy)
+            subclass!("Observer", Keeper)
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
+            use autocxx::subclass::prelude::*;
             use ffi::Observer_methods;
+
             #[autocxx::subclass::subclass]
-            pub struct MyObserverA {
-                a: u32
+            pub struct Watcher {
+                destroyed: std::rc::Rc<std::cell::Cell<bool>>,
             }
-            impl Observer_methods for MyObserverA {
+            impl Observer_methods for Watcher {
+                fn notify(&mut self) {}
             }
-            #[autocxx::subclass::subclass]
-            pub struct MyObserverB {
-                a: u32
+            impl CppPeerDestroyHook for Watcher {
+                fn on_cpp_destroy(&mut self) {
+                    self.destroyed.set(true);
+                }
             }
-            impl Observer_methods for MyObserverB {
+
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct Keeper;
+            impl Observer_methods for Keeper {
+                fn notify(&mut self) {}
             }
+            impl CppSubclassSelfOwned<ffi::KeeperCpp> for Keeper {}
         }),
     );
 }
 
 #[test]
-fn test_two_superclasses_with_same_name_method() {
+fn test_subclass_factory() {
     let hdr = indoc! {"
     #include <cstdint>
 
-    class ObserverA {
+    class Plugin {
     public:
-        ObserverA() {}
-        virtual void foo() const {}
-        virtual ~ObserverA() {}
+        virtual uint32_t apply(uint32_t input) const = 0;
+        virtual ~Plugin() {}
     };
+    uint32_t run_plugins();
+    "};
+    let cxx = indoc! {"
+    #include \"autocxxgen_ffi.h\"
 
-    class ObserverB {
-        public:
-            ObserverB() {}
-            virtual void foo() const {}
-            virtual ~ObserverB() {}
-        };
-    inline void bar() {}
+    uint32_t run_plugins() {
+        auto doubler = make_rust_subclass<Plugin>(\"Doubler\");
+        auto missing = make_rust_subclass<Plugin>(\"Missing\");
+        return doubler->apply(21) + (missing ? 1000 : 0);
+    }
     "};
     run_test_ex(
-        "",
+        cxx,
         hdr,
         quote! {
-            let obs = MyObserverA::new_rust_owned(MyObserverA { a: 3, cpp_peer: Default::default() });
-            obs.borrow().foo();
-            let obs = MyObserverB::new_rust_owned(MyObserverB { a: 3, cpp_peer: Default::default() });
-            obs.borrow().foo();
+            assert_eq!(ffi::run_plugins(), 42);
         },
         quote! {
-            generate!("bar")
-            subclass!("ObserverA",MyObserverA)
-            subclass!("ObserverB",MyObserverB)
+            generate!("run_plugins")
+            subclass!("Plugin", Doubler, factory)
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
-            use ffi::ObserverA_methods;
-            use ffi::ObserverB_methods;
-            #[autocxx::subclass::subclass]
-            pub struct MyObserverA {
-                a: u32
-            }
-            impl ObserverA_methods for MyObserverA {
-            }
+            use ffi::Plugin_methods;
+
             #[autocxx::subclass::subclass]
-            pub struct MyObserverB {
-                a: u32
-            }
-            impl ObserverB_methods for MyObserverB {
+            #[derive(Default)]
+            pub struct Doubler;
+            impl Plugin_methods for Doubler {
+                fn apply(&self, input: u32) -> u32 {
+                    input * 2
+                }
             }
         }),
     );
 }
 
 #[test]
-fn test_pv_protected_constructor() {
+fn test_mock() {
     let hdr = indoc! {"
     #include <cstdint>
 
     class Observer {
-    protected:
-        Observer() {}
     public:
-        virtual void foo() const {}
+        virtual void notify(uint32_t value) = 0;
+        virtual uint32_t total() const = 0;
         virtual ~Observer() {}
     };
-    inline void bar() {}
+    inline uint32_t notify_twice(Observer& obs) {
+        obs.notify(1);
+        obs.notify(2);
+        return obs.total();
+    }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            let obs = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
-            obs.borrow().foo();
+            let mock = ffi::MockObserver::default_rust_owned();
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            let seen_by_mock = seen.clone();
+            mock.borrow_mut()
+                .expect_notify(move |value| seen_by_mock.borrow_mut().push(value));
+            mock.borrow_mut().expect_total(|| 3);
+            assert_eq!(
+                unsafe {
+                    ffi::MockObserver::with_cpp(&mock, |observer| ffi::notify_twice(observer))
+                },
+                3
+            );
+            assert_eq!(*seen.borrow(), vec![1, 2]);
+            assert_eq!(mock.borrow().notify_calls(), 2);
+            assert_eq!(mock.borrow().total_calls(), 1);
         },
         quote! {
-            generate!("bar")
-            subclass!("Observer",MyObserver)
+            generate!("notify_twice")
+            mock!("Observer")
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
-            use ffi::Observer_methods;
-            #[autocxx::subclass::subclass]
-            pub struct MyObserver {
-                a: u32
-            }
-            impl Observer_methods for MyObserver {
-            }
+            use autocxx::subclass::CppSubclassDefault;
+            use std::cell::RefCell;
+            use std::rc::Rc;
         }),
     );
 }
 
 #[test]
-fn test_pv_protected_method() {
+fn test_mock_returning_reference() {
     let hdr = indoc! {"
     #include <cstdint>
 
-    class Observer {
+    class Registry {
     public:
-        Observer() {}
-        virtual void foo() const {}
-        virtual ~Observer() {}
-    protected:
-        virtual void baz() const {}
+        virtual const uint32_t& lookup(uint32_t key) const = 0;
+        virtual ~Registry() {}
     };
-    inline void bar() {}
+    inline uint32_t lookup_one(const Registry& registry) {
+        return registry.lookup(1);
+    }
     "};
-    run_test_ex(
+    run_test_expect_fail_ex(
         "",
         hdr,
         quote! {
-            let obs = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
-            obs.borrow().foo();
+            let mock = ffi::MockRegistry::default_rust_owned();
         },
         quote! {
-            generate!("bar")
-            subclass!("Observer",MyObserver)
+            generate!("lookup_one")
+            mock!("Registry")
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
-            use ffi::Observer_methods;
-            #[autocxx::subclass::subclass]
-            pub struct MyObserver {
-                a: u32
-            }
-            impl Observer_methods for MyObserver {
-                fn baz(&self) {
-                }
-
-                fn foo(&self) {
-                    use ffi::Observer_supers;
-                    self.baz_super()
-                }
-            }
+            use autocxx::subclass::CppSubclassDefault;
         }),
     );
 }
 
 #[test]
-fn test_pv_subclass_allocation_not_self_owned() {
+fn test_subclass_multiple_superclasses() {
     let hdr = indoc! {"
     #include <cstdint>
-    extern \"C\" void mark_freed() noexcept;
-    extern \"C\" void mark_allocated() noexcept;
 
-    class TestObserver {
+    class KeyListener {
     public:
-        TestObserver() {
-            mark_allocated();
-        }
-        virtual void a() const = 0;
-        virtual ~TestObserver() {
-            mark_freed();
-        }
+        virtual void key_pressed(uint32_t key) = 0;
+        virtual ~KeyListener() {}
     };
-    inline void TriggerTestObserverA(const TestObserver& obs) {
-        obs.a();
+    class MouseListener {
+    public:
+        virtual void clicked(uint32_t x, uint32_t y) = 0;
+        virtual uint32_t buttons() const { return 1; }
+        virtual ~MouseListener() {}
+    };
+    inline void press_key(KeyListener& listener, uint32_t key) {
+        listener.key_pressed(key);
+    }
+    inline uint32_t click(MouseListener& listener) {
+        listener.clicked(2, 3);
+        return listener.buttons();
     }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by C++
-            let obs = MyTestObserver::new_cpp_owned(
-                MyTestObserver::new()
-            );
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            let obs_superclass = obs.as_ref().unwrap(); // &subclass
-            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
-            ffi::TriggerTestObserverA(obs_superclass);
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            std::mem::drop(obs);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by Rust
-            let obs = MyTestObserver::new_rust_owned(
-                MyTestObserver::new()
-            );
-            //let cpp_peer_ptr = unsafe { obs.borrow_mut().peer_mut().get_unchecked_mut() as *mut ffi::MyTestObserverCpp };
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            std::mem::drop(obs);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            let obs = InputHandler::default_rust_owned();
+            // Don't hold a borrow of the subclass while C++ calls into it.
+            let key_listener: *mut ffi::KeyListener = unsafe {
+                autocxx::PinMut::<ffi::KeyListener>::pin_mut(&mut *obs.borrow_mut()).get_unchecked_mut()
+            };
+            let mouse_listener: *mut ffi::MouseListener = unsafe {
+                autocxx::PinMut::<ffi::MouseListener>::pin_mut(&mut *obs.borrow_mut()).get_unchecked_mut()
+            };
+            ffi::press_key(unsafe { std::pin::Pin::new_unchecked(&mut *key_listener) }, 7);
+            assert_eq!(obs.borrow().last_key, 7);
+            assert_eq!(ffi::click(unsafe { std::pin::Pin::new_unchecked(&mut *mouse_listener) }), 1);
+            assert_eq!(obs.borrow().last_click, (2, 3));
         },
         quote! {
-            generate!("TriggerTestObserverA")
-            subclass!("TestObserver",MyTestObserver)
+            generate!("press_key")
+            generate!("click")
+            subclass!("KeyListener", InputHandler)
+            subclass!("MouseListener", InputHandler)
         },
         None,
         None,
         Some(quote! {
-            use once_cell::sync::Lazy;
-            use std::sync::Mutex;
+            use autocxx::subclass::CppSubclassDefault;
+            use ffi::KeyListener_methods;
+            use ffi::MouseListener_methods;
 
-            use autocxx::subclass::CppSubclass;
-            use ffi::TestObserver_methods;
             #[autocxx::subclass::subclass]
-            pub struct MyTestObserver {
-                data: ExternalEngine,
-            }
-            impl TestObserver_methods for MyTestObserver {
-                fn a(&self) {
-                    self.data.do_something();
-                }
-            }
-            impl MyTestObserver {
-                fn new() -> Self {
-                    Self {
-                        cpp_peer: Default::default(),
-                        data: ExternalEngine::default(),
-                    }
-                }
-            }
-
-            #[no_mangle]
-            pub fn mark_allocated() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
-            }
-
-            #[no_mangle]
-            pub fn mark_freed() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
-            }
-
             #[derive(Default)]
-            struct Status {
-                cpp_allocated: bool,
-                rust_allocated: bool,
-                a_called: bool,
-            }
-
-            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
-
-            pub struct ExternalEngine;
-
-            impl ExternalEngine {
-                fn do_something(&self) {
-                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
-                }
+            pub struct InputHandler {
+                last_key: u32,
+                last_click: (u32, u32),
             }
-
-            impl Default for ExternalEngine {
-                fn default() -> Self {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
-                    ExternalEngine
+            impl KeyListener_methods for InputHandler {
+                fn key_pressed(&mut self, key: u32) {
+                    self.last_key = key;
                 }
             }
-
-            impl Drop for ExternalEngine {
-                fn drop(&mut self) {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
+            impl MouseListener_methods for InputHandler {
+                fn clicked(&mut self, x: u32, y: u32) {
+                    self.last_click = (x, y);
                 }
             }
         }),
@@ -7380,158 +9146,202 @@ fn test_pv_subclass_allocation_not_self_owned() {
 }
 
 #[test]
-fn test_pv_subclass_allocation_self_owned() {
+fn test_subclass_templated_superclass() {
     let hdr = indoc! {"
     #include <cstdint>
-    extern \"C\" void mark_freed() noexcept;
-    extern \"C\" void mark_allocated() noexcept;
 
-    class TestObserver {
+    struct Event {
+        uint32_t id;
+    };
+    namespace ns {
+    template <typename T>
+    class Listener {
     public:
-        TestObserver() {
-            mark_allocated();
-        }
-        virtual void a() const = 0;
-        virtual ~TestObserver() {
-            mark_freed();
-        }
+        virtual void on_event(const T& event) = 0;
+        virtual uint32_t priority() const { return 1; }
+        virtual ~Listener() {}
     };
-    inline void TriggerTestObserverA(const TestObserver& obs) {
-        const_cast<TestObserver&>(obs).a();
+    }
+    inline uint32_t dispatch(ns::Listener<Event>& listener, uint32_t id) {
+        Event event { id };
+        listener.on_event(event);
+        return listener.priority();
     }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by C++
-            let obs = MyTestObserver::new_cpp_owned(
-                MyTestObserver::new(false)
-            );
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            let obs_superclass = obs.as_ref().unwrap(); // &subclass
-            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
-
-            ffi::TriggerTestObserverA(obs_superclass);
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            std::mem::drop(obs);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by Rust
-            let obs = MyTestObserver::new_rust_owned(
-                MyTestObserver::new(false)
-            );
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
-
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            std::mem::drop(obs);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when self-owned
-            let obs = MyTestObserver::new_self_owned(
-                MyTestObserver::new(true)
-            );
-            let obs_superclass_ptr: *const ffi::TestObserver = obs.as_ref().borrow().as_ref();
-            // Retain just a pointer on the Rust side, so there is no Rust-side
-            // ownership.
-            std::mem::drop(obs);
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            ffi::TriggerTestObserverA(unsafe { obs_superclass_ptr.as_ref().unwrap() });
-
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            let obs = MyListener::default_rust_owned();
+            // Don't hold a borrow of the subclass while C++ calls into it.
+            let listener: *mut ffi::EventListener = unsafe {
+                autocxx::PinMut::<ffi::EventListener>::pin_mut(&mut *obs.borrow_mut()).get_unchecked_mut()
+            };
+            assert_eq!(ffi::dispatch(unsafe { std::pin::Pin::new_unchecked(&mut *listener) }, 7), 1);
+            assert_eq!(obs.borrow().last_event, 7);
         },
         quote! {
-            generate!("TriggerTestObserverA")
-            subclass!("TestObserver",MyTestObserver)
+            generate!("dispatch")
+            generate_pod!("Event")
+            concrete!("ns::Listener<Event>", EventListener)
+            subclass!("ns::Listener<Event>", MyListener)
         },
         None,
         None,
         Some(quote! {
-            use once_cell::sync::Lazy;
-            use std::sync::Mutex;
+            use autocxx::subclass::CppSubclassDefault;
+            use ffi::EventListener_methods;
 
-            use autocxx::subclass::CppSubclass;
-            use autocxx::subclass::CppSubclassSelfOwned;
-            use ffi::TestObserver_methods;
-            #[autocxx::subclass::subclass(self_owned)]
-            pub struct MyTestObserver {
-                data: ExternalEngine,
-                self_owning: bool,
-            }
-            impl TestObserver_methods for MyTestObserver {
-                fn a(&self) {
-                    self.data.do_something();
-                    if self.self_owning {
-                        self.delete_self();
-                    }
-                }
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct MyListener {
+                last_event: u32,
             }
-            impl MyTestObserver {
-                fn new(self_owning: bool) -> Self {
-                    Self {
-                        cpp_peer: Default::default(),
-                        data: ExternalEngine::default(),
-                        self_owning,
-                    }
+            impl EventListener_methods for MyListener {
+                fn on_event(&mut self, event: &ffi::Event) {
+                    self.last_event = event.id;
                 }
             }
+        }),
+    );
+}
 
-            #[no_mangle]
-            pub fn mark_allocated() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
-            }
+#[test]
+fn test_subclass_templated_superclass_default_argument() {
+    let hdr = indoc! {"
+    #include <cstdint>
 
-            #[no_mangle]
-            pub fn mark_freed() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
-            }
+    struct Event {
+        uint32_t id;
+    };
+    namespace ns {
+    template <typename T>
+    class Listener {
+    public:
+        virtual void on_event(const T& event, uint32_t count = 1) = 0;
+        virtual ~Listener() {}
+    };
+    }
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {},
+        quote! {
+            generate_pod!("Event")
+            concrete!("ns::Listener<Event>", EventListener)
+            subclass!("ns::Listener<Event>", MyListener)
+        },
+        None,
+        None,
+        Some(quote! {
+            use ffi::EventListener_methods;
 
+            #[autocxx::subclass::subclass]
             #[derive(Default)]
-            struct Status {
-                cpp_allocated: bool,
-                rust_allocated: bool,
-                a_called: bool,
+            pub struct MyListener;
+            impl EventListener_methods for MyListener {
+                fn on_event(&mut self, _event: &ffi::Event, _count: u32) {}
             }
+        }),
+    );
+}
 
-            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
+#[test]
+fn test_subclass_protected_methods() {
+    let hdr = indoc! {"
+    #include <cstdint>
 
-            pub struct ExternalEngine;
+    class Widget {
+    public:
+        void render() { draw(); }
+        uint32_t value() const { return val; }
+        virtual ~Widget() {}
+    protected:
+        virtual void draw() = 0;
+        uint32_t scale(uint32_t x) const { return x * 2; }
+        void set_value(uint32_t v) { val = v; }
+    private:
+        uint32_t val = 0;
+    };
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let mut widget = MyWidget::default_cpp_owned();
+            widget.pin_mut().As_Widget_mut().render();
+            assert_eq!(widget.As_Widget().value(), 42);
+        },
+        quote! {
+            generate!("Widget")
+            subclass!("Widget", MyWidget)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use autocxx::subclass::CppSubclassDefault;
+            use ffi::Widget_methods;
 
-            impl ExternalEngine {
-                fn do_something(&self) {
-                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct MyWidget;
+            impl Widget_methods for MyWidget {
+                fn draw(&mut self) {
+                    let scaled = self.peer().scale(21);
+                    self.peer_mut().set_value(scaled);
                 }
             }
+        }),
+    );
+}
 
-            impl Default for ExternalEngine {
-                fn default() -> Self {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
-                    ExternalEngine
-                }
-            }
+#[test]
+fn test_subclass_protected_fields() {
+    let hdr = indoc! {"
+    #include <cstdint>
 
-            impl Drop for ExternalEngine {
-                fn drop(&mut self) {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
+    class Counter {
+    public:
+        Counter() : step(2) {}
+        void tick() { advance(); }
+        uint32_t total() const { return count; }
+        virtual ~Counter() {}
+    protected:
+        virtual void advance() = 0;
+        uint32_t count = 0;
+        const uint32_t step;
+    };
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let mut counter = MyCounter::default_cpp_owned();
+            counter.pin_mut().As_Counter_mut().tick();
+            counter.pin_mut().As_Counter_mut().tick();
+            assert_eq!(counter.As_Counter().total(), 4);
+        },
+        quote! {
+            generate!("Counter")
+            subclass!("Counter", MyCounter)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use autocxx::subclass::CppSubclassDefault;
+            use ffi::Counter_methods;
+
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct MyCounter;
+            impl Counter_methods for MyCounter {
+                fn advance(&mut self) {
+                    let step = *self.peer().step();
+                    let count = *self.peer().count();
+                    *self.peer_mut().count_mut() = count + step;
                 }
             }
         }),
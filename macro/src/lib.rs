@@ -57,9 +57,17 @@ pub fn subclass(attr: TokenStream, item: TokenStream) -> TokenStream {
     };
     let subclass_attrs: SubclassAttrs = syn::parse(attr)
         .unwrap_or_else(|_| abort!(Span::call_site(), "Unable to parse attributes"));
+    let (subclass_trait, self_owned_trait) = if subclass_attrs.thread_safe {
+        (
+            quote! { CppSubclassThreadSafe },
+            quote! { CppSubclassThreadSafeSelfOwned },
+        )
+    } else {
+        (quote! { CppSubclass }, quote! { CppSubclassSelfOwned })
+    };
     let self_owned_bit = if subclass_attrs.self_owned {
         Some(quote! {
-            impl autocxx::subclass::CppSubclassSelfOwned<ffi::#cpp_ident> for #id {}
+            impl autocxx::subclass::#self_owned_trait<ffi::#cpp_ident> for #id {}
         })
     } else {
         None
@@ -67,7 +75,7 @@ pub fn subclass(attr: TokenStream, item: TokenStream) -> TokenStream {
     let toks = quote! {
         #s
 
-        impl autocxx::subclass::CppSubclass<ffi::#cpp_ident> for #id {
+        impl autocxx::subclass::#subclass_trait<ffi::#cpp_ident> for #id {
             fn peer_holder_mut(&mut self) -> &mut autocxx::subclass::CppSubclassCppPeerHolder<ffi::#cpp_ident> {
                 &mut self.cpp_peer
             }
@@ -115,6 +123,23 @@ pub fn extern_rust_function(attr: TokenStream, input: TokenStream) -> TokenStrea
     input
 }
 
+/// Attribute to state that a Rust trait is to be exposed to C++ as an
+/// abstract class, which C++ can call into for any `Box<dyn Trait>`.
+#[proc_macro_error]
+#[proc_macro_attribute]
+pub fn extern_rust_trait(attr: TokenStream, input: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        abort!(Span::call_site(), "Expected no attributes");
+    }
+    let i: Item =
+        syn::parse(input.clone()).unwrap_or_else(|_| abort!(Span::call_site(), "Expected an item"));
+    match i {
+        Item::Trait(..) => {}
+        _ => abort!(Span::call_site(), "Expected a trait"),
+    }
+    input
+}
+
 /// Attribute which should never be encountered in real life.
 /// This is something which features in the Rust source code generated
 /// by autocxx-bindgen and passed to autocxx-engine, which should never
@@ -13,11 +13,11 @@ use std::{
 
 use proc_macro2::Span;
 use quote::ToTokens;
+use syn::{ext::IdentExt, Ident, Result as ParseResult};
 use syn::{
     parse::{Parse, ParseStream},
-    Signature, Token,
+    ItemTrait, Signature, Token,
 };
-use syn::{Ident, Result as ParseResult};
 
 use crate::{
     directives::{EXTERN_RUST_TYPE, SUBCLASS},
@@ -135,6 +135,153 @@ impl Default for Allowlist {
 pub struct Subclass {
     pub superclass: String,
     pub subclass: Ident,
+    /// Whether C++ may call this subclass from any thread.
+    pub thread_safe: bool,
+    /// What to do if a Rust override of a virtual method panics, if
+    /// specified.
+    pub on_panic: Option<SubclassPanicPolicy>,
+    /// Whether the subclass wants to be told when C++ destroys its
+    /// C++ peer.
+    pub on_cpp_destroy: bool,
+    /// Whether C++ may construct this subclass by name, using
+    /// `make_rust_subclass`.
+    pub factory: bool,
+}
+
+/// What to do when a Rust subclass's override of a C++ virtual method
+/// panics, rather than letting the panic unwind into C++.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubclassPanicPolicy {
+    /// Report the panic and abort the process.
+    Abort,
+    /// Throw a C++ exception carrying the panic message.
+    Exception,
+    /// Return `Default::default()` from the method.
+    ReturnDefault,
+}
+
+#[allow(clippy::derivable_impls)] // nightly-only
+impl Default for SubclassPanicPolicy {
+    fn default() -> Self {
+        SubclassPanicPolicy::Abort
+    }
+}
+
+impl Parse for SubclassPanicPolicy {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let policy: Ident = input.parse()?;
+        if policy == "abort" {
+            Ok(SubclassPanicPolicy::Abort)
+        } else if policy == "exception" {
+            Ok(SubclassPanicPolicy::Exception)
+        } else if policy == "return_default" {
+            Ok(SubclassPanicPolicy::ReturnDefault)
+        } else {
+            Err(syn::Error::new(
+                policy.span(),
+                "Expected abort, exception or return_default",
+            ))
+        }
+    }
+}
+
+impl ToTokens for SubclassPanicPolicy {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let policy = match self {
+            SubclassPanicPolicy::Abort => "abort",
+            SubclassPanicPolicy::Exception => "exception",
+            SubclassPanicPolicy::ReturnDefault => "return_default",
+        };
+        tokens.extend(Ident::new(policy, Span::call_site()).into_token_stream());
+    }
+}
+
+/// A request to fuse a pointer parameter and a length parameter of
+/// a C++ function into a single Rust slice parameter.
+#[derive(Debug)]
+pub struct SliceParam {
+    pub function: String,
+    pub data: Ident,
+    pub len: Ident,
+}
+
+/// A statement that a C++ function takes ownership of the object
+/// passed to it via a pointer parameter.
+#[derive(Debug)]
+pub struct OwnershipParam {
+    pub function: String,
+    pub param: Ident,
+}
+
+/// A statement that the reference returned by a C++ function borrows
+/// from a given parameter (which may be `self`).
+#[derive(Debug)]
+pub struct LifetimeParam {
+    pub function: String,
+    pub param: Ident,
+}
+
+/// A statement that a C++ function has default values for a given
+/// parameter and all those which follow it.
+#[derive(Debug)]
+pub struct DefaultParams {
+    pub function: String,
+    pub first: Ident,
+}
+
+/// A statement of whether a pointer parameter of a C++ function
+/// may be null.
+#[derive(Debug)]
+pub struct PointerParam {
+    pub function: String,
+    pub param: Ident,
+    pub nullable: bool,
+}
+
+/// A request to turn the status code returned by a C++ function into
+/// a Rust `Result`, optionally returning an out-parameter on success.
+#[derive(Debug)]
+pub struct ResultOf {
+    pub function: String,
+    /// How to tell whether the status code means success.
+    pub check: StatusCheck,
+    /// The pointer parameter into which the function writes its
+    /// real result.
+    pub out: Option<Ident>,
+}
+
+/// How a `result_of!` function's status code is checked for success.
+#[derive(Debug)]
+pub enum StatusCheck {
+    /// A Rust expression equal to the status code on success. Used for
+    /// primitive, enum and POD status codes.
+    OkWhen(String),
+    /// A C++ expression, in terms of `status`, which is true on success,
+    /// e.g. `status.ok()`. Used for non-POD status types.
+    OkIf(String),
+}
+
+/// A fixed-arity form in which a C-style variadic function may be
+/// called, giving the Rust types of the arguments in place of `...`.
+pub struct VariadicCall {
+    pub function: String,
+    pub extra_params: Vec<syn::Type>,
+}
+
+impl std::fmt::Debug for VariadicCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VariadicCall")
+            .field("function", &self.function)
+            .field(
+                "extra_params",
+                &self
+                    .extra_params
+                    .iter()
+                    .map(|ty| ty.to_token_stream().to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
 }
 
 #[derive(Clone)]
@@ -153,6 +300,22 @@ impl std::fmt::Debug for RustFun {
     }
 }
 
+/// A Rust trait which is to be exposed to C++ as an abstract class.
+#[derive(Clone)]
+pub struct RustTrait {
+    pub path: RustPath,
+    pub item: ItemTrait,
+}
+
+impl std::fmt::Debug for RustTrait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RustTrait")
+            .field("path", &self.path)
+            .field("item", &self.item.to_token_stream().to_string())
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct IncludeCppConfig {
     pub inclusions: Vec<String>,
@@ -168,7 +331,21 @@ pub struct IncludeCppConfig {
     pub rust_types: Vec<RustPath>,
     pub subclasses: Vec<Subclass>,
     pub extern_rust_funs: Vec<RustFun>,
+    pub extern_rust_traits: Vec<RustTrait>,
     pub concretes: HashMap<String, Ident>,
+    slice_params: Vec<SliceParam>,
+    pointer_params: Vec<PointerParam>,
+    lifetime_params: Vec<LifetimeParam>,
+    owned_returns: Vec<String>,
+    ownership_params: Vec<OwnershipParam>,
+    throwing_functions: Vec<String>,
+    throws_by_default: bool,
+    result_ofs: Vec<ResultOf>,
+    default_params: Vec<DefaultParams>,
+    variadic_calls: Vec<VariadicCall>,
+    /// Superclasses for which we generate a mock subclass. Each is also
+    /// listed in `subclasses`.
+    mocks: Vec<String>,
 }
 
 impl Parse for IncludeCppConfig {
@@ -191,7 +368,19 @@ impl Parse for IncludeCppConfig {
         let mut mod_name = None;
         let mut subclasses = Vec::new();
         let mut extern_rust_funs = Vec::new();
+        let mut extern_rust_traits = Vec::new();
         let mut concretes = HashMap::new();
+        let mut slice_params = Vec::new();
+        let mut pointer_params = Vec::new();
+        let mut lifetime_params = Vec::new();
+        let mut owned_returns = Vec::new();
+        let mut ownership_params = Vec::new();
+        let mut throwing_functions = Vec::new();
+        let mut throws_by_default = false;
+        let mut result_ofs = Vec::new();
+        let mut default_params = Vec::new();
+        let mut variadic_calls = Vec::new();
+        let mut mocks = Vec::new();
 
         while !input.is_empty() {
             let has_hexathorpe = input.parse::<Option<syn::token::Pound>>()?.is_some();
@@ -248,6 +437,137 @@ impl Parse for IncludeCppConfig {
                     syn::parenthesized!(args in input);
                     let generate: syn::LitStr = args.parse()?;
                     constructor_blocklist.push(generate.value());
+                } else if ident == "slice_param" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let data: syn::Ident = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let len: syn::Ident = args.parse()?;
+                    slice_params.push(SliceParam {
+                        function: function.value(),
+                        data,
+                        len,
+                    });
+                } else if ident == "nonnull" || ident == "nullable" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let param: syn::Ident = args.parse()?;
+                    pointer_params.push(PointerParam {
+                        function: function.value(),
+                        param,
+                        nullable: ident == "nullable",
+                    });
+                } else if ident == "lifetime" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let param = syn::Ident::parse_any(&args)?;
+                    lifetime_params.push(LifetimeParam {
+                        function: function.value(),
+                        param,
+                    });
+                } else if ident == "defaults" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let first: syn::Ident = args.parse()?;
+                    default_params.push(DefaultParams {
+                        function: function.value(),
+                        first,
+                    });
+                } else if ident == "variadic" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let types;
+                    syn::bracketed!(types in args);
+                    let extra_params =
+                        syn::punctuated::Punctuated::<syn::Type, syn::token::Comma>::parse_terminated(
+                            &types,
+                        )?;
+                    variadic_calls.push(VariadicCall {
+                        function: function.value(),
+                        extra_params: extra_params.into_iter().collect(),
+                    });
+                } else if ident == "returns_owned" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    owned_returns.push(function.value());
+                } else if ident == "takes_ownership" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let param: syn::Ident = args.parse()?;
+                    ownership_params.push(OwnershipParam {
+                        function: function.value(),
+                        param,
+                    });
+                } else if ident == "result_of" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    let mut check = None;
+                    let mut out = None;
+                    while !args.is_empty() {
+                        args.parse::<syn::token::Comma>()?;
+                        let key: syn::Ident = args.parse()?;
+                        args.parse::<syn::token::Eq>()?;
+                        let val: syn::LitStr = args.parse()?;
+                        if key == "ok_when" || key == "ok_if" {
+                            if check.is_some() {
+                                return Err(syn::Error::new(
+                                    key.span(),
+                                    "only one of 'ok_when' and 'ok_if' may be given",
+                                ));
+                            }
+                            check = Some(if key == "ok_when" {
+                                val.parse::<syn::Expr>()?;
+                                StatusCheck::OkWhen(val.value())
+                            } else {
+                                StatusCheck::OkIf(val.value())
+                            });
+                        } else if key == "out" {
+                            out = Some(val.parse::<syn::Ident>()?);
+                        } else {
+                            return Err(syn::Error::new(
+                                key.span(),
+                                "expected 'ok_when', 'ok_if' or 'out'",
+                            ));
+                        }
+                    }
+                    let check = check.ok_or_else(|| {
+                        syn::Error::new(function.span(), "result_of! requires 'ok_when' or 'ok_if'")
+                    })?;
+                    result_ofs.push(ResultOf {
+                        function: function.value(),
+                        check,
+                        out,
+                    });
+                } else if ident == "throws" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    if args.peek(syn::LitStr) {
+                        let function: syn::LitStr = args.parse()?;
+                        throwing_functions.push(function.value());
+                    } else {
+                        let mode: syn::Ident = args.parse()?;
+                        if mode != "auto" {
+                            return Err(syn::Error::new(
+                                mode.span(),
+                                "expected a function name or 'auto'",
+                            ));
+                        }
+                        throws_by_default = true;
+                    }
                 } else if ident == "rust_type" || ident == EXTERN_RUST_TYPE {
                     let args;
                     syn::parenthesized!(args in input);
@@ -259,10 +579,50 @@ impl Parse for IncludeCppConfig {
                     let superclass: syn::LitStr = args.parse()?;
                     args.parse::<syn::token::Comma>()?;
                     let subclass: syn::Ident = args.parse()?;
+                    let mut thread_safe = false;
+                    let mut on_panic = None;
+                    let mut on_cpp_destroy = false;
+                    let mut factory = false;
+                    while args.parse::<Option<syn::token::Comma>>()?.is_some() {
+                        let option: Ident = args.parse()?;
+                        if option == "thread_safe" {
+                            thread_safe = true;
+                        } else if option == "on_panic" {
+                            let policy;
+                            syn::parenthesized!(policy in args);
+                            on_panic = Some(policy.parse()?);
+                        } else if option == "on_cpp_destroy" {
+                            on_cpp_destroy = true;
+                        } else if option == "factory" {
+                            factory = true;
+                        } else {
+                            return Err(syn::Error::new(
+                                option.span(),
+                                "Expected thread_safe, on_panic, on_cpp_destroy or factory",
+                            ));
+                        }
+                    }
                     subclasses.push(Subclass {
                         superclass: superclass.value(),
                         subclass,
+                        thread_safe,
+                        on_panic,
+                        on_cpp_destroy,
+                        factory,
+                    });
+                } else if ident == "mock" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let superclass: syn::LitStr = args.parse()?;
+                    subclasses.push(Subclass {
+                        superclass: superclass.value(),
+                        subclass: mock_name(&superclass.value()),
+                        thread_safe: false,
+                        on_panic: None,
+                        on_cpp_destroy: false,
+                        factory: false,
                     });
+                    mocks.push(superclass.value());
                 } else if ident == "parse_only" {
                     parse_only = true;
                     swallow_parentheses(&input, &ident)?;
@@ -295,6 +655,13 @@ impl Parse for IncludeCppConfig {
                         sig,
                         receiver: None,
                     });
+                } else if ident == "extern_rust_trait" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let path: RustPath = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let item: ItemTrait = args.parse()?;
+                    extern_rust_traits.push(RustTrait { path, item });
                 } else {
                     return Err(syn::Error::new(
                         ident.span(),
@@ -321,11 +688,50 @@ impl Parse for IncludeCppConfig {
             mod_name,
             subclasses,
             extern_rust_funs,
+            extern_rust_traits,
             concretes,
+            slice_params,
+            pointer_params,
+            lifetime_params,
+            owned_returns,
+            ownership_params,
+            throwing_functions,
+            throws_by_default,
+            result_ofs,
+            default_params,
+            variadic_calls,
+            mocks,
         })
     }
 }
 
+/// The name of the mock subclass generated for a superclass, e.g.
+/// `MockObserver` for `ns::Observer`, or `MockListener_Event` for
+/// `ns::Listener<Event>`.
+fn mock_name(superclass: &str) -> Ident {
+    let (template, args) = match superclass.find('<') {
+        Some(idx) => superclass.split_at(idx),
+        None => (superclass, ""),
+    };
+    let final_item = template.rsplit("::").next().unwrap();
+    let mut name = format!("Mock{}", final_item);
+    if !args.is_empty() {
+        name.push('_');
+        name.push_str(&sanitize_cpp_name(args));
+    }
+    Ident::new(&name, Span::call_site())
+}
+
+/// Turns a C++ type such as `ns::Listener<Event>` into something usable
+/// in an identifier, such as `ns_Listener_Event`.
+fn sanitize_cpp_name(cpp_name: &str) -> String {
+    cpp_name
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
 fn swallow_parentheses(input: &ParseStream, latest_ident: &Ident) -> ParseResult<()> {
     let args;
     syn::parenthesized!(args in input);
@@ -390,7 +796,7 @@ impl IncludeCppConfig {
                         [
                             format!("{}Cpp", sc.subclass),
                             sc.subclass.to_string(), // TODO may not be necessary
-                            sc.superclass.clone(),
+                            self.superclass_name(&sc.superclass).into_owned(),
                         ]
                     })),
             )),
@@ -412,10 +818,10 @@ impl IncludeCppConfig {
             .flat_map(|sc| {
                 [
                     Cow::Owned(sc.subclass.to_string()),
-                    Cow::Borrowed(&sc.superclass),
+                    self.superclass_name(&sc.superclass),
                 ]
             })
-            .any(|item| cpp_name == item.as_str())
+            .any(|item| cpp_name == item)
     }
 
     /// Whether this type is on the allowlist specified by the user.
@@ -432,6 +838,7 @@ impl IncludeCppConfig {
             || self.is_subclass_holder(cpp_name)
             || self.is_subclass_cpp(cpp_name)
             || self.is_rust_fun(cpp_name)
+            || self.is_rust_trait(cpp_name)
             || self.is_concrete_type(cpp_name)
             || match &self.allowlist {
                 Allowlist::Unspecified(_) => panic!("Eek no allowlist yet"),
@@ -455,6 +862,94 @@ impl IncludeCppConfig {
         self.blocklist.iter()
     }
 
+    /// Any requests to treat pairs of pointer and length parameters
+    /// of the given function as a single slice.
+    pub fn get_slice_params<'a>(
+        &'a self,
+        cpp_name: &'a str,
+    ) -> impl Iterator<Item = &'a SliceParam> + 'a {
+        self.slice_params
+            .iter()
+            .filter(move |sp| sp.function == cpp_name)
+    }
+
+    /// Any statements of whether pointer parameters of the given function
+    /// may be null.
+    pub fn get_pointer_params<'a>(
+        &'a self,
+        cpp_name: &'a str,
+    ) -> impl Iterator<Item = &'a PointerParam> + 'a {
+        self.pointer_params
+            .iter()
+            .filter(move |pp| pp.function == cpp_name)
+    }
+
+    /// The parameter from which the reference returned by the given
+    /// function borrows, if this has been specified.
+    pub fn get_lifetime_param(&self, cpp_name: &str) -> Option<&Ident> {
+        self.lifetime_params
+            .iter()
+            .find(|lp| lp.function == cpp_name)
+            .map(|lp| &lp.param)
+    }
+
+    /// The first parameter of the given function which has a default
+    /// value, if this has been specified.
+    pub fn get_first_defaulted_param(&self, cpp_name: &str) -> Option<&Ident> {
+        self.default_params
+            .iter()
+            .find(|dp| dp.function == cpp_name)
+            .map(|dp| &dp.first)
+    }
+
+    /// Any fixed-arity forms in which the given variadic function
+    /// should be callable.
+    pub fn get_variadic_calls<'a>(
+        &'a self,
+        cpp_name: &'a str,
+    ) -> impl Iterator<Item = &'a VariadicCall> + 'a {
+        self.variadic_calls
+            .iter()
+            .filter(move |vc| vc.function == cpp_name)
+    }
+
+    /// Whether the given function returns a pointer which the caller owns.
+    pub fn function_returns_owned(&self, cpp_name: &str) -> bool {
+        self.owned_returns.iter().any(|f| f == cpp_name)
+    }
+
+    /// Any pointer parameters of the given function which take ownership
+    /// of the object passed in.
+    pub fn get_ownership_params<'a>(
+        &'a self,
+        cpp_name: &'a str,
+    ) -> impl Iterator<Item = &'a Ident> + 'a {
+        self.ownership_params
+            .iter()
+            .filter(move |op| op.function == cpp_name)
+            .map(|op| &op.param)
+    }
+
+    /// Any request to turn the status code returned by the given function
+    /// into a `Result`.
+    pub fn get_result_of(&self, cpp_name: &str) -> Option<&ResultOf> {
+        self.result_ofs.iter().find(|ro| ro.function == cpp_name)
+    }
+
+    /// Whether exceptions thrown by this C++ function should be returned
+    /// to Rust as an `Err`. `throws!(auto)` doesn't apply to functions
+    /// declared `noexcept`, but naming a function explicitly always does.
+    pub fn function_throws(&self, cpp_name: &str, noexcept: bool) -> bool {
+        (self.throws_by_default && !noexcept)
+            || self.throwing_functions.iter().any(|f| f == cpp_name)
+    }
+
+    /// Whether `throws!(auto)` was specified, such that we need to know
+    /// which functions are `noexcept`.
+    pub fn throws_by_default(&self) -> bool {
+        self.throws_by_default
+    }
+
     fn is_concrete_type(&self, cpp_name: &str) -> bool {
         self.concretes.values().any(|val| *val == cpp_name)
     }
@@ -473,6 +968,13 @@ impl IncludeCppConfig {
         self.uniquify_name_per_mod("autocxx_make_string")
     }
 
+    /// The name of a helper function which converts to or from
+    /// a C++ string type which cxx doesn't know about.
+    pub fn get_utf_string_helper_name(&self, operation: &str, type_name: &str) -> String {
+        self.uniquify_name_per_mod(&format!("autocxx_{}_{}", operation, type_name))
+            .to_string()
+    }
+
     pub fn is_rust_type(&self, id: &Ident) -> bool {
         self.rust_types
             .iter()
@@ -487,18 +989,93 @@ impl IncludeCppConfig {
             .any(|id| id == possible_fun)
     }
 
+    /// Whether this is a Rust trait which we expose to C++ as an
+    /// abstract class.
+    pub fn is_rust_trait(&self, id: &str) -> bool {
+        self.extern_rust_traits.iter().any(|rt| rt.item.ident == id)
+    }
+
     pub fn superclasses(&self) -> impl Iterator<Item = &String> {
         let mut uniquified = HashSet::new();
         uniquified.extend(self.subclasses.iter().map(|sc| &sc.superclass));
         uniquified.into_iter()
     }
 
+    /// The name by which we refer to a superclass. For a template
+    /// instantiation such as `ns::Listener<Event>` this is the name given
+    /// to it by `concrete!`, or else one we make up, since bindgen can only
+    /// tell us about its methods if we give it a name.
+    pub fn superclass_name<'a>(&'a self, superclass: &'a str) -> Cow<'a, str> {
+        if !superclass.contains('<') {
+            Cow::Borrowed(superclass)
+        } else if let Some(id) = self.concretes.get(superclass) {
+            Cow::Owned(id.to_string())
+        } else {
+            Cow::Owned(format!("{}_AutocxxConcrete", sanitize_cpp_name(superclass)))
+        }
+    }
+
+    /// Superclasses which are template instantiations, along with the
+    /// names by which we refer to them.
+    pub fn templated_superclasses(&self) -> impl Iterator<Item = (&String, Cow<'_, str>)> {
+        self.superclasses()
+            .filter(|sc| sc.contains('<'))
+            .map(|sc| (sc, self.superclass_name(sc)))
+    }
+
+    /// Whether this `concrete!` type is a superclass, in which case we
+    /// learn about it from bindgen rather than treating it as opaque.
+    pub fn is_templated_superclass(&self, cpp_definition: &str) -> bool {
+        self.superclasses().any(|sc| sc == cpp_definition)
+    }
+
     pub fn is_subclass_holder(&self, id: &str) -> bool {
         self.subclasses
             .iter()
             .any(|sc| format!("{}Holder", sc.subclass) == id)
     }
 
+    /// Whether this Rust subclass was declared `thread_safe`, so may be
+    /// called by C++ from any thread.
+    pub fn is_thread_safe_subclass(&self, id: &Ident) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| &sc.subclass == id && sc.thread_safe)
+    }
+
+    /// Whether this Rust subclass was declared `on_cpp_destroy`, so wants
+    /// to be told when C++ destroys its C++ peer.
+    pub fn subclass_has_cpp_destroy_hook(&self, id: &Ident) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| &sc.subclass == id && sc.on_cpp_destroy)
+    }
+
+    /// Whether this Rust subclass was declared `factory`, so C++ may
+    /// construct it by name.
+    pub fn is_factory_subclass(&self, id: &Ident) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| &sc.subclass == id && sc.factory)
+    }
+
+    /// Whether this Rust subclass is a mock which we generate, rather than
+    /// one written by the user.
+    pub fn is_mock(&self, id: &Ident) -> bool {
+        self.mocks
+            .iter()
+            .any(|superclass| mock_name(superclass) == *id)
+    }
+
+    /// What should happen if this Rust subclass panics when called from C++.
+    pub fn get_subclass_panic_policy(&self, id: &Ident) -> SubclassPanicPolicy {
+        self.subclasses
+            .iter()
+            .filter(|sc| &sc.subclass == id)
+            .find_map(|sc| sc.on_panic)
+            .unwrap_or_default()
+    }
+
     fn is_subclass_cpp(&self, id: &str) -> bool {
         self.subclasses
             .iter()
@@ -561,6 +1138,62 @@ impl ToTokens for IncludeCppConfig {
         for i in &self.constructor_blocklist {
             tokens.extend(quote! { block_constructors!(#i) });
         }
+        for i in &self.slice_params {
+            let function = &i.function;
+            let data = &i.data;
+            let len = &i.len;
+            tokens.extend(quote! { slice_param!(#function, #data, #len) });
+        }
+        for i in &self.pointer_params {
+            let function = &i.function;
+            let param = &i.param;
+            if i.nullable {
+                tokens.extend(quote! { nullable!(#function, #param) });
+            } else {
+                tokens.extend(quote! { nonnull!(#function, #param) });
+            }
+        }
+        for i in &self.lifetime_params {
+            let function = &i.function;
+            let param = &i.param;
+            tokens.extend(quote! { lifetime!(#function, #param) });
+        }
+        for i in &self.default_params {
+            let function = &i.function;
+            let first = &i.first;
+            tokens.extend(quote! { defaults!(#function, #first) });
+        }
+        for i in &self.variadic_calls {
+            let function = &i.function;
+            let extra_params = &i.extra_params;
+            tokens.extend(quote! { variadic!(#function, [#(#extra_params),*]) });
+        }
+        for i in &self.owned_returns {
+            tokens.extend(quote! { returns_owned!(#i) });
+        }
+        for i in &self.ownership_params {
+            let function = &i.function;
+            let param = &i.param;
+            tokens.extend(quote! { takes_ownership!(#function, #param) });
+        }
+        for i in &self.throwing_functions {
+            tokens.extend(quote! { throws!(#i) });
+        }
+        if self.throws_by_default {
+            tokens.extend(quote! { throws!(auto) });
+        }
+        for i in &self.result_ofs {
+            let function = &i.function;
+            let check = match &i.check {
+                StatusCheck::OkWhen(ok_when) => quote! { ok_when = #ok_when },
+                StatusCheck::OkIf(ok_if) => quote! { ok_if = #ok_if },
+            };
+            let out = i.out.as_ref().map(|out| {
+                let out = out.to_string();
+                quote! { , out = #out }
+            });
+            tokens.extend(quote! { result_of!(#function, #check #out) });
+        }
         for path in &self.rust_types {
             tokens.extend(quote! { rust_type!(#path) });
         }
@@ -586,17 +1219,48 @@ impl ToTokens for IncludeCppConfig {
             let s = &i.sig;
             tokens.extend(quote! { extern_rust_fun!(#p,#s) });
         }
-        for i in &self.subclasses {
+        for i in &self.extern_rust_traits {
+            let p = &i.path;
+            let item = &i.item;
+            tokens.extend(quote! { extern_rust_trait!(#p,#item) });
+        }
+        for i in &self.mocks {
+            tokens.extend(quote! { mock!(#i) });
+        }
+        for i in self
+            .subclasses
+            .iter()
+            .filter(|sc| !self.is_mock(&sc.subclass))
+        {
             let superclass = &i.superclass;
             let subclass = &i.subclass;
-            tokens.extend(quote! { subclass!(#superclass,#subclass) });
+            let thread_safe = if i.thread_safe {
+                Some(quote! { ,thread_safe })
+            } else {
+                None
+            };
+            let on_panic = i.on_panic.map(|policy| quote! { ,on_panic(#policy) });
+            let on_cpp_destroy = if i.on_cpp_destroy {
+                Some(quote! { ,on_cpp_destroy })
+            } else {
+                None
+            };
+            let factory = if i.factory {
+                Some(quote! { ,factory })
+            } else {
+                None
+            };
+            tokens.extend(quote! {
+                subclass!(#superclass,#subclass #thread_safe #on_panic #on_cpp_destroy #factory)
+            });
         }
     }
 }
 
 #[cfg(test)]
 mod parse_tests {
-    use crate::config::UnsafePolicy;
+    use crate::config::{IncludeCppConfig, StatusCheck, SubclassPanicPolicy, UnsafePolicy};
+    use quote::quote;
     use syn::parse_quote;
     #[test]
     fn test_safety_unsafe() {
@@ -619,4 +1283,204 @@ mod parse_tests {
         let us: UnsafePolicy = parse_quote! {};
         assert_eq!(us, UnsafePolicy::AllFunctionsUnsafe)
     }
+
+    #[test]
+    fn test_slice_param() {
+        let config: IncludeCppConfig = parse_quote! {
+            slice_param!("ns::write", data, len)
+        };
+        let sp = config.get_slice_params("ns::write").next().unwrap();
+        assert_eq!(sp.data, "data");
+        assert_eq!(sp.len, "len");
+        assert!(config.get_slice_params("ns::read").next().is_none());
+    }
+
+    #[test]
+    fn test_pointer_params() {
+        let config: IncludeCppConfig = parse_quote! {
+            nonnull!("ns::copy", dest)
+            nullable!("ns::copy", src)
+        };
+        let pps: Vec<_> = config.get_pointer_params("ns::copy").collect();
+        assert_eq!(pps.len(), 2);
+        assert_eq!(pps[0].param, "dest");
+        assert!(!pps[0].nullable);
+        assert_eq!(pps[1].param, "src");
+        assert!(pps[1].nullable);
+        assert!(config.get_pointer_params("ns::move").next().is_none());
+    }
+
+    #[test]
+    fn test_lifetime_param() {
+        let config: IncludeCppConfig = parse_quote! {
+            lifetime!("ns::Map::get", self)
+            lifetime!("ns::choose", a)
+        };
+        assert_eq!(config.get_lifetime_param("ns::Map::get").unwrap(), "self");
+        assert_eq!(config.get_lifetime_param("ns::choose").unwrap(), "a");
+        assert!(config.get_lifetime_param("ns::other").is_none());
+    }
+
+    #[test]
+    fn test_default_params() {
+        let config: IncludeCppConfig = parse_quote! {
+            defaults!("ns::draw", color)
+        };
+        assert_eq!(
+            config.get_first_defaulted_param("ns::draw").unwrap(),
+            "color"
+        );
+        assert!(config.get_first_defaulted_param("ns::erase").is_none());
+    }
+
+    #[test]
+    fn test_variadic_calls() {
+        let config: IncludeCppConfig = parse_quote! {
+            variadic!("ns::log", [i32, &CStr])
+            variadic!("ns::log", [])
+        };
+        let calls: Vec<_> = config.get_variadic_calls("ns::log").collect();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].extra_params.len(), 2);
+        assert!(calls[1].extra_params.is_empty());
+        assert!(config.get_variadic_calls("ns::vlog").next().is_none());
+    }
+
+    #[test]
+    fn test_thread_safe_subclass() {
+        let config: IncludeCppConfig = parse_quote! {
+            subclass!("Observer", Worker, thread_safe)
+            subclass!("Observer", Listener)
+        };
+        assert!(config.is_thread_safe_subclass(&parse_quote!(Worker)));
+        assert!(!config.is_thread_safe_subclass(&parse_quote!(Listener)));
+    }
+
+    #[test]
+    fn test_subclass_panic_policy() {
+        let config: IncludeCppConfig = parse_quote! {
+            subclass!("Observer", Worker, thread_safe, on_panic(exception))
+            subclass!("Observer", Listener)
+        };
+        assert_eq!(
+            config.get_subclass_panic_policy(&parse_quote!(Worker)),
+            SubclassPanicPolicy::Exception
+        );
+        assert!(config.is_thread_safe_subclass(&parse_quote!(Worker)));
+        assert_eq!(
+            config.get_subclass_panic_policy(&parse_quote!(Listener)),
+            SubclassPanicPolicy::Abort
+        );
+    }
+
+    #[test]
+    fn test_subclass_cpp_destroy_hook() {
+        let config: IncludeCppConfig = parse_quote! {
+            subclass!("Observer", Watcher, on_cpp_destroy)
+            subclass!("Observer", Listener)
+        };
+        assert!(config.subclass_has_cpp_destroy_hook(&parse_quote!(Watcher)));
+        assert!(!config.subclass_has_cpp_destroy_hook(&parse_quote!(Listener)));
+    }
+
+    #[test]
+    fn test_subclass_factory() {
+        let config: IncludeCppConfig = parse_quote! {
+            subclass!("Plugin", EchoPlugin, thread_safe, factory)
+            subclass!("Plugin", OtherPlugin)
+        };
+        assert!(config.is_factory_subclass(&parse_quote!(EchoPlugin)));
+        assert!(config.is_thread_safe_subclass(&parse_quote!(EchoPlugin)));
+        assert!(!config.is_factory_subclass(&parse_quote!(OtherPlugin)));
+    }
+
+    #[test]
+    fn test_mock() {
+        let config: IncludeCppConfig = parse_quote! {
+            mock!("ns::Observer")
+        };
+        assert!(config.is_mock(&parse_quote!(MockObserver)));
+        assert!(config.is_subclass_holder("MockObserverHolder"));
+    }
+
+    #[test]
+    fn test_templated_superclass() {
+        let config: IncludeCppConfig = parse_quote! {
+            concrete!("ns::Listener<Event>", EventListener)
+            subclass!("ns::Listener<Event>", MyListener)
+            mock!("ns::Listener<Status>")
+        };
+        assert_eq!(
+            config.superclass_name("ns::Listener<Event>"),
+            "EventListener"
+        );
+        assert_eq!(
+            config.superclass_name("ns::Listener<Status>"),
+            "ns_Listener_Status_AutocxxConcrete"
+        );
+        assert_eq!(config.superclass_name("ns::Observer"), "ns::Observer");
+        assert!(config.is_templated_superclass("ns::Listener<Event>"));
+        assert!(config.is_mock(&parse_quote!(MockListener_Status)));
+        assert!(config.is_on_allowlist("EventListener"));
+        let mut templated: Vec<_> = config.templated_superclasses().collect();
+        templated.sort();
+        assert_eq!(templated.len(), 2);
+        assert_eq!(templated[0].1, "EventListener");
+    }
+
+    #[test]
+    fn test_ownership_transfer() {
+        let config: IncludeCppConfig = parse_quote! {
+            returns_owned!("ns::create")
+            takes_ownership!("ns::adopt", widget)
+        };
+        assert!(config.function_returns_owned("ns::create"));
+        assert!(!config.function_returns_owned("ns::adopt"));
+        let params: Vec<_> = config.get_ownership_params("ns::adopt").collect();
+        assert_eq!(params.len(), 1);
+        assert_eq!(params[0], "widget");
+        assert!(config.get_ownership_params("ns::create").next().is_none());
+    }
+
+    #[test]
+    fn test_result_of() {
+        let config: IncludeCppConfig = parse_quote! {
+            result_of!("ns::open", ok_when = "0", out = "handle")
+        };
+        let ro = config.get_result_of("ns::open").unwrap();
+        assert!(matches!(&ro.check, StatusCheck::OkWhen(ok_when) if ok_when == "0"));
+        assert_eq!(ro.out.as_ref().unwrap(), "handle");
+        assert!(config.get_result_of("ns::close").is_none());
+        let config: IncludeCppConfig = parse_quote! {
+            result_of!("ns::close", ok_when = "true")
+        };
+        assert!(config.get_result_of("ns::close").unwrap().out.is_none());
+        let config: IncludeCppConfig = parse_quote! {
+            result_of!("ns::load", ok_if = "status.ok()", out = "data")
+        };
+        let ro = config.get_result_of("ns::load").unwrap();
+        assert!(matches!(&ro.check, StatusCheck::OkIf(ok_if) if ok_if == "status.ok()"));
+        assert_eq!(ro.out.as_ref().unwrap(), "data");
+        let result: Result<IncludeCppConfig, _> = syn::parse2(quote! {
+            result_of!("ns::load", ok_when = "0", ok_if = "status.ok()")
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_throws() {
+        let config: IncludeCppConfig = parse_quote! {
+            throws!("ns::parse")
+        };
+        assert!(config.function_throws("ns::parse", false));
+        assert!(config.function_throws("ns::parse", true));
+        assert!(!config.function_throws("ns::format", false));
+        assert!(!config.throws_by_default());
+        let config: IncludeCppConfig = parse_quote! {
+            throws!(auto)
+        };
+        assert!(config.throws_by_default());
+        assert!(config.function_throws("ns::format", false));
+        assert!(!config.function_throws("ns::format", true));
+    }
 }
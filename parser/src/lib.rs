@@ -13,7 +13,11 @@ pub mod file_locations;
 mod path;
 mod subclass_attrs;
 
-pub use config::{AllowlistEntry, IncludeCppConfig, RustFun, Subclass, UnsafePolicy};
+pub use config::{
+    AllowlistEntry, DefaultParams, IncludeCppConfig, LifetimeParam, OwnershipParam, PointerParam,
+    ResultOf, RustFun, RustTrait, SliceParam, StatusCheck, Subclass, SubclassPanicPolicy,
+    UnsafePolicy,
+};
 use file_locations::FileLocationStrategy;
 pub use path::RustPath;
 use proc_macro2::TokenStream as TokenStream2;
@@ -30,6 +34,7 @@ use syn::{
 pub mod directives {
     pub static EXTERN_RUST_TYPE: &str = "extern_rust_type";
     pub static EXTERN_RUST_FUN: &str = "extern_rust_function";
+    pub static EXTERN_RUST_TRAIT: &str = "extern_rust_trait";
     pub static SUBCLASS: &str = "subclass";
 }
 
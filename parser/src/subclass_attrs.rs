@@ -9,6 +9,8 @@
 use proc_macro2::Ident;
 use quote::ToTokens;
 use syn::Result as ParseResult;
+
+use crate::SubclassPanicPolicy;
 use syn::{
     parse::{Parse, ParseStream},
     token::Comma,
@@ -17,7 +19,13 @@ use syn::{
 #[derive(Default)]
 pub struct SubclassAttrs {
     pub self_owned: bool,
-    pub superclass: Option<String>,
+    pub thread_safe: bool,
+    /// The superclasses, in order. The first is the one whose constructors
+    /// the subclass offers.
+    pub superclasses: Vec<String>,
+    pub on_panic: Option<SubclassPanicPolicy>,
+    pub on_cpp_destroy: bool,
+    pub factory: bool,
 }
 
 impl Parse for SubclassAttrs {
@@ -27,22 +35,30 @@ impl Parse for SubclassAttrs {
         while id.is_some() {
             match id {
                 Some(id) if id == "self_owned" => me.self_owned = true,
+                Some(id) if id == "thread_safe" => me.thread_safe = true,
+                Some(id) if id == "on_cpp_destroy" => me.on_cpp_destroy = true,
+                Some(id) if id == "factory" => me.factory = true,
                 Some(id) if id == "superclass" => {
                     let args;
                     syn::parenthesized!(args in input);
                     let superclass: syn::LitStr = args.parse()?;
-                    if me.superclass.is_some() {
+                    if me.superclasses.contains(&superclass.value()) {
                         return Err(syn::Error::new_spanned(
                             id.into_token_stream(),
-                            "Expected single superclass specification",
+                            "Superclass specified more than once",
                         ));
                     }
-                    me.superclass = Some(superclass.value());
+                    me.superclasses.push(superclass.value());
+                }
+                Some(id) if id == "on_panic" => {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    me.on_panic = Some(args.parse()?);
                 }
                 Some(id) => {
                     return Err(syn::Error::new_spanned(
                         id.into_token_stream(),
-                        "Expected self_owned or superclass",
+                        "Expected self_owned, thread_safe, superclass, on_panic, on_cpp_destroy or factory",
                     ))
                 }
                 None => {}
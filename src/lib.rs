@@ -16,6 +16,7 @@
 
 pub mod subclass;
 mod value_param;
+mod vector;
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
 /// Include some C++ headers in your Rust project.
@@ -222,6 +223,20 @@ macro_rules! concrete {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Treat a pointer parameter and a length parameter of a C++ function
+/// as a single Rust slice, for example
+/// `slice_param!("ns::write_buffer", data, len)`.
+/// The generated Rust function then takes `&[T]` (or `&mut [T]`
+/// if the pointer is non-const) in place of both parameters, and
+/// no longer needs to be `unsafe` on account of the raw pointer.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! slice_param {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 /// Specifies a global safety policy for functions generated
 /// from these headers. By default (without such a `safety!`
 /// directive) all such functions are marked as `unsafe` and
@@ -285,12 +300,165 @@ macro_rules! extern_rust_type {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Declare that a C++ function returns a pointer which the caller then
+/// owns, for example `returns_owned!("ns::create")`. The generated Rust
+/// function returns a `UniquePtr<T>` instead of a `*mut T`, so the object
+/// will be deleted when it's dropped.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! returns_owned {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare that a C++ function takes ownership of the object passed in
+/// a pointer parameter, for example `takes_ownership!("ns::adopt", widget)`.
+/// The generated Rust function takes a `UniquePtr<T>` instead of a `*mut T`,
+/// and releases it before handing the raw pointer to C++.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! takes_ownership {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare a fixed-arity form in which a C-style variadic function
+/// may be called, listing the Rust types of the arguments which take
+/// the place of `...`, for example `variadic!("ns::log", [i32, &CStr])`.
+/// Arguments may be of fixed-size numeric types, raw pointers, or `&CStr`
+/// (which is passed as a `const char*`). Each form is called through
+/// a C++ wrapper function. The first is given the function's own name,
+/// and any others are numbered as if they were overloads.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! variadic {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare that a C++ function has default arguments, starting from
+/// the given parameter, for example `defaults!("ns::draw", color)`.
+/// As well as the usual function taking every parameter, autocxx
+/// generates a variant for each default which may be omitted, named
+/// after the first parameter left out. For
+/// `void draw(Canvas& canvas, Color color = RED, int width = 1)`
+/// you'd get `draw(canvas, color, width)`, `draw_defaulting_width(canvas, color)`
+/// and `draw_defaulting_color(canvas)`. The omitted arguments are
+/// supplied by the C++ compiler, so they always match the C++ defaults.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! defaults {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare which parameter the reference returned by a C++ function
+/// borrows from, for example `lifetime!("ns::Map::get", self)` or
+/// `lifetime!("ns::choose", a)`. This is equivalent to C++'s
+/// `[[clang::lifetimebound]]`, which autocxx also reads from your headers,
+/// so you only need this where that attribute is absent. Without either,
+/// functions which return a reference but take several reference
+/// parameters can't be generated, because the lifetime of the returned
+/// reference is ambiguous.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! lifetime {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare that a pointer parameter of a C++ function is never null, for
+/// example `nonnull!("ns::copy", dest)`. The generated Rust function then
+/// takes `&T` (or `Pin<&mut T>` if the pointer is non-const) in place of
+/// the raw pointer, and no longer needs to be `unsafe` on its account.
+/// This isn't needed for parameters annotated `_Nonnull` in C++.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! nonnull {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare that a pointer parameter of a C++ function may be null, for
+/// example `nullable!("ns::copy", src)`. The generated Rust function then
+/// takes `Option<&T>` (or `Option<Pin<&mut T>>` if the pointer is non-const)
+/// in place of the raw pointer, passing null for `None`. This isn't needed
+/// for parameters annotated `_Nullable` in C++.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! nullable {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Turn the status code returned by a C++ function into a Rust `Result`,
+/// for example `result_of!("ns::open", ok_when = "0", out = "handle")`.
+/// The generated function returns `Ok` if the status code is equal to
+/// the `ok_when` expression, and otherwise `Err` containing the status
+/// code. If `out` names a pointer parameter, the function no longer takes
+/// that parameter; instead, the value written to it by C++ is returned
+/// within the `Ok`. Such a function is `unsafe`, because autocxx relies on
+/// C++ having written the out-parameter whenever it reports success.
+///
+/// With `ok_when`, the status code must be a primitive, enum or POD type.
+/// For other status types, such as `absl::Status`, give `ok_if` instead:
+/// a C++ expression in terms of `status` which is true on success, e.g.
+/// `ok_if = "status.ok()"`. The `Err` then holds the status within a
+/// `cxx::UniquePtr`. Either way, the out-parameter must point to a
+/// primitive, enum or POD type.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! result_of {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Return C++ exceptions thrown by a function to Rust as an `Err`
+/// containing a [`CppException`], for example `throws!("ns::parse")`.
+/// The generated function then returns `Result<T, CppException>`.
+/// Any exception derived from `std::exception` is caught, and its
+/// `what()` is available via [`CppException::what`].
+///
+/// `throws!(auto)` applies this to every function. Without this directive,
+/// an exception thrown by C++ into Rust terminates the program.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! throws {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 /// See [`subclass::subclass`].
 #[macro_export]
 macro_rules! subclass {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Generate a mock subclass of a C++ abstract class, for use in tests,
+/// for example `mock!("ns::Observer")` generates `MockObserver`.
+/// For each pure virtual method `foo`, the mock has `expect_foo`, taking
+/// a closure to run when C++ calls `foo`, and `foo_calls`, the number of
+/// calls so far. Calling a method without an expectation panics.
+/// Create instances using [`subclass::CppSubclassDefault`], and pass them
+/// to C++ using the `unsafe` `with_cpp`. Classes with pure virtual methods returning
+/// references can't be mocked.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! mock {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! usage {
@@ -374,6 +542,25 @@ unsafe impl cxx::ExternType for c_void {
     type Kind = cxx::kind::Trivial;
 }
 
+/// The opaque contents of a C `va_list`. Functions which take a `va_list`
+/// are given a `*mut va_list_tag`, which Rust can't create or inspect,
+/// but can pass on to other C++ functions.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct va_list_tag {
+    _private: [u8; 0],
+    _pinned: core::marker::PhantomData<core::marker::PhantomPinned>,
+}
+
+/// # Safety
+///
+/// We assert that the namespace and type ID refer to a C++
+/// type which is equivalent to this Rust type.
+unsafe impl cxx::ExternType for va_list_tag {
+    type Id = cxx::type_id!(va_list_tag);
+    type Kind = cxx::kind::Opaque;
+}
+
 /// A C++ `char16_t`
 #[allow(non_camel_case_types)]
 #[repr(transparent)]
@@ -388,6 +575,57 @@ unsafe impl cxx::ExternType for c_char16_t {
     type Kind = cxx::kind::Trivial;
 }
 
+/// A C++ `char32_t`
+#[allow(non_camel_case_types)]
+#[repr(transparent)]
+pub struct c_char32_t(pub u32);
+
+/// # Safety
+///
+/// We assert that the namespace and type ID refer to a C++
+/// type which is equivalent to this Rust type.
+unsafe impl cxx::ExternType for c_char32_t {
+    type Id = cxx::type_id!(c_char32_t);
+    type Kind = cxx::kind::Trivial;
+}
+
+/// A C++20 `char8_t`
+#[allow(non_camel_case_types)]
+#[repr(transparent)]
+pub struct c_char8_t(pub u8);
+
+/// # Safety
+///
+/// We assert that the namespace and type ID refer to a C++
+/// type which is equivalent to this Rust type.
+unsafe impl cxx::ExternType for c_char8_t {
+    type Id = cxx::type_id!(c_char8_t);
+    type Kind = cxx::kind::Trivial;
+}
+
+/// A C++ `wchar_t`. This is 16 bits wide on Windows and 32 bits
+/// wide (and signed) elsewhere.
+#[allow(non_camel_case_types)]
+#[repr(transparent)]
+#[cfg(windows)]
+pub struct c_wchar_t(pub u16);
+
+/// A C++ `wchar_t`. This is 16 bits wide on Windows and 32 bits
+/// wide (and signed) elsewhere.
+#[allow(non_camel_case_types)]
+#[repr(transparent)]
+#[cfg(not(windows))]
+pub struct c_wchar_t(pub i32);
+
+/// # Safety
+///
+/// We assert that the namespace and type ID refer to a C++
+/// type which is equivalent to this Rust type.
+unsafe impl cxx::ExternType for c_wchar_t {
+    type Id = cxx::type_id!(c_wchar_t);
+    type Kind = cxx::kind::Trivial;
+}
+
 /// autocxx couldn't generate these bindings.
 /// If you come across a method, type or function which refers to this type,
 /// it indicates that autocxx couldn't generate that binding. A documentation
@@ -438,6 +676,20 @@ pub mod extern_rust {
     /// pub fn call_me_from_cpp() { }
     /// ```
     pub use autocxx_macro::extern_rust_function;
+
+    /// Declare that a Rust trait is to be exposed to C++ as an abstract class
+    /// of the same name, such that a `Box<dyn Trait>` can be handed to C++
+    /// code which expects that class. This is used as an attribute macro on
+    /// a Rust trait, for instance:
+    /// ```
+    /// # use autocxx_macro::extern_rust_trait as extern_rust_trait;
+    /// #[extern_rust_trait]
+    /// pub trait Sink {
+    ///     fn write(&mut self, data: &[u8]);
+    /// }
+    /// ```
+    /// Use `ffi::Sink::from_rust` to wrap a `Box<dyn Sink>` in a C++ object.
+    pub use autocxx_macro::extern_rust_trait;
 }
 
 /// Equivalent to [`std::convert::AsMut`], but returns a pinned mutable reference
@@ -447,11 +699,55 @@ pub trait PinMut<T>: AsRef<T> {
     fn pin_mut(&mut self) -> std::pin::Pin<&mut T>;
 }
 
+/// A checked downcast from a polymorphic C++ base class to a subclass,
+/// implemented using `dynamic_cast`. autocxx implements this for each
+/// pair of classes on the allowlist where `T` derives from `Self`.
+/// It's usually easier to call [`Downcast::downcast_ref`] or
+/// [`Downcast::downcast_mut`].
+pub trait DowncastTo<T> {
+    /// Returns a reference to the subclass, or `None` if this object
+    /// isn't one.
+    fn downcast_to_ref(&self) -> Option<&T>;
+    /// Returns a pinned mutable reference to the subclass, or `None`
+    /// if this object isn't one.
+    fn downcast_to_mut(self: std::pin::Pin<&mut Self>) -> Option<std::pin::Pin<&mut T>>;
+}
+
+/// Allows a C++ object to be downcast to a subclass, for example
+/// `base.downcast_ref::<ffi::Derived>()`.
+pub trait Downcast {
+    /// Returns a reference to the subclass `T`, or `None` if this object
+    /// isn't one.
+    fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        Self: DowncastTo<T>,
+    {
+        self.downcast_to_ref()
+    }
+
+    /// Returns a pinned mutable reference to the subclass `T`, or `None`
+    /// if this object isn't one.
+    fn downcast_mut<T>(self: std::pin::Pin<&mut Self>) -> Option<std::pin::Pin<&mut T>>
+    where
+        Self: DowncastTo<T>,
+    {
+        self.downcast_to_mut()
+    }
+}
+
+impl<B: ?Sized> Downcast for B {}
+
 pub use value_param::as_copy;
 pub use value_param::as_mov;
 pub use value_param::as_new;
 pub use value_param::ValueParam;
 pub use value_param::ValueParamHandler;
+pub use vector::CxxVectorExt;
+pub use vector::PushableVectorElement;
+
+/// An exception thrown by a C++ function which was marked using
+/// [`throws`].
+pub use cxx::Exception as CppException;
 
 /// Imports which you're likely to want to use.
 pub mod prelude {
@@ -470,6 +766,9 @@ pub mod prelude {
     pub use crate::c_void;
     pub use crate::cpp_semantics;
     pub use crate::include_cpp;
+    pub use crate::CppException;
+    pub use crate::CxxVectorExt;
+    pub use crate::Downcast;
     pub use crate::PinMut;
     pub use crate::ValueParam;
     pub use cxx::UniquePtr;
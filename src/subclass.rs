@@ -10,9 +10,14 @@
 // except according to those terms.
 
 use std::{
+    any::Any,
     cell::RefCell,
     pin::Pin,
     rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
 };
 
 use cxx::{memory::UniquePtrTarget, UniquePtr};
@@ -63,8 +68,11 @@ pub use autocxx_macro::subclass;
 /// ```
 pub mod prelude {
     pub use super::{
-        is_subclass, subclass, CppPeerConstructor, CppSubclass, CppSubclassDefault,
-        CppSubclassRustPeerHolder, CppSubclassSelfOwned, CppSubclassSelfOwnedDefault,
+        is_subclass, subclass, CppPeerConstructor, CppPeerConstructorThreadSafe,
+        CppPeerDestroyHook, CppPeerWeak, CppSubclass, CppSubclassDefault,
+        CppSubclassRustPeerHolder, CppSubclassRustPeerHolderThreadSafe, CppSubclassSelfOwned,
+        CppSubclassSelfOwnedDefault, CppSubclassThreadSafe, CppSubclassThreadSafeDefault,
+        CppSubclassThreadSafeSelfOwned,
     };
 }
 
@@ -75,39 +83,153 @@ pub trait CppSubclassCppPeer: UniquePtrTarget {
 }
 
 /// A type used for how the C++ side of a Rust/C++ subclass pair refers to
-/// the Rust side.
+/// the Rust side. The second field records whether the C++ side still
+/// exists.
 #[doc(hidden)]
 pub enum CppSubclassRustPeerHolder<T> {
-    Owned(Rc<RefCell<T>>),
-    Unowned(Weak<RefCell<T>>),
+    Owned(Rc<RefCell<T>>, Arc<AtomicBool>),
+    Unowned(Weak<RefCell<T>>, Arc<AtomicBool>),
 }
 
 impl<T> CppSubclassRustPeerHolder<T> {
     pub fn get(&self) -> Option<Rc<RefCell<T>>> {
         match self {
-            CppSubclassRustPeerHolder::Owned(strong) => Some(strong.clone()),
-            CppSubclassRustPeerHolder::Unowned(weak) => weak.upgrade(),
+            CppSubclassRustPeerHolder::Owned(strong, _) => Some(strong.clone()),
+            CppSubclassRustPeerHolder::Unowned(weak, _) => weak.upgrade(),
         }
     }
     pub fn relinquish_ownership(self) -> Self {
         match self {
-            CppSubclassRustPeerHolder::Owned(strong) => {
-                CppSubclassRustPeerHolder::Unowned(Rc::downgrade(&strong))
+            CppSubclassRustPeerHolder::Owned(strong, alive) => {
+                CppSubclassRustPeerHolder::Unowned(Rc::downgrade(&strong), alive)
             }
             _ => self,
         }
     }
+    /// Called from the destructor of the C++ peer.
+    pub fn cpp_peer_destroyed(&self) {
+        match self {
+            CppSubclassRustPeerHolder::Owned(_, alive)
+            | CppSubclassRustPeerHolder::Unowned(_, alive) => alive.store(false, Ordering::Release),
+        }
+    }
+    /// Called from the destructor of the C++ peer, for subclasses which
+    /// implement [`CppPeerDestroyHook`].
+    pub fn notify_cpp_peer_destroyed(&self)
+    where
+        T: CppPeerDestroyHook,
+    {
+        self.cpp_peer_destroyed();
+        // If the Rust object is being dropped (which is why its C++ peer is
+        // going away) or is in the middle of some call, we can't tell it.
+        // We deliberately skip the hook without complaint: we're within a
+        // C++ destructor, so mustn't panic, and `peer` and `peer_mut` will
+        // in any case refuse to use the destroyed peer.
+        if let Some(me) = self.get() {
+            if let Ok(mut me) = me.try_borrow_mut() {
+                me.on_cpp_destroy();
+            }
+        };
+    }
+}
+
+/// A trait representing the C++ side of a Rust/C++ subclass pair, where
+/// the subclass was declared `thread_safe`.
+///
+/// # Safety
+///
+/// Implementations assert that the C++ peer may be used from any thread.
+#[doc(hidden)]
+pub unsafe trait CppSubclassCppPeerThreadSafe: CppSubclassCppPeer {}
+
+/// A type used for how the C++ side of a Rust/C++ subclass pair refers to
+/// the Rust side, where the subclass may be called from any thread.
+#[doc(hidden)]
+pub enum CppSubclassRustPeerHolderThreadSafe<T> {
+    Owned(Arc<RwLock<T>>, Arc<AtomicBool>),
+    Unowned(std::sync::Weak<RwLock<T>>, Arc<AtomicBool>),
+}
+
+impl<T> CppSubclassRustPeerHolderThreadSafe<T> {
+    pub fn get(&self) -> Option<Arc<RwLock<T>>> {
+        match self {
+            CppSubclassRustPeerHolderThreadSafe::Owned(strong, _) => Some(strong.clone()),
+            CppSubclassRustPeerHolderThreadSafe::Unowned(weak, _) => weak.upgrade(),
+        }
+    }
+    pub fn relinquish_ownership(self) -> Self {
+        match self {
+            CppSubclassRustPeerHolderThreadSafe::Owned(strong, alive) => {
+                CppSubclassRustPeerHolderThreadSafe::Unowned(Arc::downgrade(&strong), alive)
+            }
+            _ => self,
+        }
+    }
+    /// Called from the destructor of the C++ peer.
+    pub fn cpp_peer_destroyed(&self) {
+        match self {
+            CppSubclassRustPeerHolderThreadSafe::Owned(_, alive)
+            | CppSubclassRustPeerHolderThreadSafe::Unowned(_, alive) => {
+                alive.store(false, Ordering::Release)
+            }
+        }
+    }
+    /// Called from the destructor of the C++ peer, for subclasses which
+    /// implement [`CppPeerDestroyHook`].
+    pub fn notify_cpp_peer_destroyed(&self)
+    where
+        T: CppPeerDestroyHook,
+    {
+        self.cpp_peer_destroyed();
+        // As for the non-thread-safe case, we skip the hook if the object
+        // is in use, which also means we don't deadlock if C++ destroys the
+        // peer during some call into this object. This includes a call on
+        // another thread, so the hook is best-effort.
+        if let Some(me) = self.get() {
+            if let Ok(mut me) = me.try_write() {
+                me.on_cpp_destroy();
+            }
+        };
+    }
+}
+
+/// Describes a panic in a Rust subclass which was called from C++.
+#[doc(hidden)]
+pub fn describe_subclass_panic(method: &str, payload: Box<dyn Any + Send>) -> String {
+    let msg = match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(msg) => msg.to_string(),
+            Err(_) => "unknown panic payload".to_string(),
+        },
+    };
+    format!("{} panicked: {}", method, msg)
+}
+
+/// Called when a Rust subclass panics, if it asked us to abort.
+#[doc(hidden)]
+pub fn abort_after_subclass_panic(method: &str, payload: Box<dyn Any + Send>) -> ! {
+    eprintln!("{} - aborting", describe_subclass_panic(method, payload));
+    std::process::abort()
 }
 
 /// A type showing how the Rust side of a Rust/C++ subclass pair refers to
 /// the C++ side.
+///
+/// The second field of each variant records whether the C++ peer still
+/// exists, which is shared with the C++ peer itself and any [`CppPeerWeak`].
 #[doc(hidden)]
 pub enum CppSubclassCppPeerHolder<CppPeer: CppSubclassCppPeer> {
     Empty,
-    Owned(Box<UniquePtr<CppPeer>>),
-    Unowned(*mut CppPeer),
+    Owned(Box<UniquePtr<CppPeer>>, Arc<AtomicBool>),
+    Unowned(*mut CppPeer, Arc<AtomicBool>),
 }
 
+// Safety: the C++ peer has been declared safe to use from any thread, and
+// we hand out mutable access to it only via `&mut self`.
+unsafe impl<CppPeer: CppSubclassCppPeerThreadSafe> Send for CppSubclassCppPeerHolder<CppPeer> {}
+unsafe impl<CppPeer: CppSubclassCppPeerThreadSafe> Sync for CppSubclassCppPeerHolder<CppPeer> {}
+
 impl<CppPeer: CppSubclassCppPeer> Default for CppSubclassCppPeerHolder<CppPeer> {
     fn default() -> Self {
         CppSubclassCppPeerHolder::Empty
@@ -118,33 +240,127 @@ impl<CppPeer: CppSubclassCppPeer> CppSubclassCppPeerHolder<CppPeer> {
     fn pin_mut(&mut self) -> Pin<&mut CppPeer> {
         match self {
             CppSubclassCppPeerHolder::Empty => panic!("Peer not set up"),
-            CppSubclassCppPeerHolder::Owned(peer) => peer.pin_mut(),
-            CppSubclassCppPeerHolder::Unowned(peer) => unsafe {
-                // Safety: guaranteed safe because this is a pointer to a C++ object,
-                // and C++ never moves things in memory.
-                Pin::new_unchecked(peer.as_mut().unwrap())
-            },
+            CppSubclassCppPeerHolder::Owned(peer, _) => peer.pin_mut(),
+            CppSubclassCppPeerHolder::Unowned(peer, alive) => {
+                assert!(alive.load(Ordering::Acquire), "C++ peer has been destroyed");
+                unsafe {
+                    // Safety: guaranteed safe because this is a pointer to a C++ object,
+                    // and C++ never moves things in memory, and C++ hasn't destroyed it.
+                    Pin::new_unchecked(peer.as_mut().unwrap())
+                }
+            }
         }
     }
     fn get(&self) -> &CppPeer {
         match self {
             CppSubclassCppPeerHolder::Empty => panic!("Peer not set up"),
-            CppSubclassCppPeerHolder::Owned(peer) => peer.as_ref(),
-            // Safety: guaranteed safe because this is a pointer to a C++ object,
-            // and C++ never moves things in memory.
-            CppSubclassCppPeerHolder::Unowned(peer) => unsafe { peer.as_ref().unwrap() },
+            CppSubclassCppPeerHolder::Owned(peer, _) => peer.as_ref(),
+            CppSubclassCppPeerHolder::Unowned(peer, alive) => {
+                assert!(alive.load(Ordering::Acquire), "C++ peer has been destroyed");
+                // Safety: guaranteed safe because this is a pointer to a C++ object,
+                // and C++ never moves things in memory, and C++ hasn't destroyed it.
+                unsafe { peer.as_ref().unwrap() }
+            }
+        }
+    }
+    fn weak(&self) -> CppPeerWeak<CppPeer> {
+        match self {
+            CppSubclassCppPeerHolder::Empty => CppPeerWeak {
+                peer: std::ptr::null_mut(),
+                alive: Arc::new(AtomicBool::new(false)),
+            },
+            CppSubclassCppPeerHolder::Owned(peer, alive) => CppPeerWeak {
+                peer: peer.as_ref().as_ref().unwrap() as *const CppPeer as *mut CppPeer,
+                alive: alive.clone(),
+            },
+            CppSubclassCppPeerHolder::Unowned(peer, alive) => CppPeerWeak {
+                peer: *peer,
+                alive: alive.clone(),
+            },
         }
     }
-    fn set_owned(&mut self, peer: UniquePtr<CppPeer>) {
-        *self = Self::Owned(Box::new(peer));
+    fn set_owned(&mut self, peer: UniquePtr<CppPeer>, alive: Arc<AtomicBool>) {
+        *self = Self::Owned(Box::new(peer), alive);
     }
-    fn set_unowned(&mut self, peer: &mut UniquePtr<CppPeer>) {
+    fn set_unowned(&mut self, peer: &mut UniquePtr<CppPeer>, alive: Arc<AtomicBool>) {
         // Safety: guaranteed safe because this is a pointer to a C++ object,
         // and C++ never moves things in memory.
-        *self = Self::Unowned(unsafe {
-            std::pin::Pin::<&mut CppPeer>::into_inner_unchecked(peer.pin_mut())
-        });
+        *self = Self::Unowned(
+            unsafe { std::pin::Pin::<&mut CppPeer>::into_inner_unchecked(peer.pin_mut()) },
+            alive,
+        );
+    }
+}
+
+/// A handle to the C++ peer of a Rust subclass which doesn't keep it alive,
+/// but knows whether C++ has since destroyed it. Obtain one using
+/// [`CppSubclass::peer_weak`]. This is useful for observers which must
+/// check that the object they're observing still exists.
+///
+/// This doesn't offer mutable access to the peer, since any number of
+/// clones may exist; use [`CppSubclass::peer_mut`] for that.
+pub struct CppPeerWeak<CppPeer> {
+    peer: *mut CppPeer,
+    alive: Arc<AtomicBool>,
+}
+
+// Safety: the C++ peer has been declared safe to use from any thread, and
+// we only hand out shared access to it, via an unsafe function.
+unsafe impl<CppPeer: CppSubclassCppPeerThreadSafe> Send for CppPeerWeak<CppPeer> {}
+unsafe impl<CppPeer: CppSubclassCppPeerThreadSafe> Sync for CppPeerWeak<CppPeer> {}
+
+impl<CppPeer> Clone for CppPeerWeak<CppPeer> {
+    fn clone(&self) -> Self {
+        Self {
+            peer: self.peer,
+            alive: self.alive.clone(),
+        }
+    }
+}
+
+impl<CppPeer> CppPeerWeak<CppPeer> {
+    /// Whether the C++ peer still exists.
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Acquire)
     }
+
+    /// Returns a reference to the C++ peer, or `None` if C++ has
+    /// destroyed it.
+    ///
+    /// # Safety
+    ///
+    /// This can only tell whether C++ has destroyed the peer so far, so
+    /// the caller must ensure that C++ doesn't destroy it, on this thread
+    /// or any other, while the returned reference is in use. Nor may C++
+    /// mutate the peer meanwhile.
+    pub unsafe fn get(&self) -> Option<&CppPeer> {
+        if self.is_alive() {
+            // Safety: this is a pointer to a C++ object which C++ hadn't
+            // yet destroyed, and C++ never moves things in memory. Our
+            // caller promises it won't be destroyed while in use.
+            self.peer.as_ref()
+        } else {
+            None
+        }
+    }
+}
+
+/// A hook to be told when C++ destroys the C++ peer of a Rust subclass.
+/// To use this, declare your subclass with `#[subclass(on_cpp_destroy)]`
+/// and implement this trait.
+///
+/// This is called from the destructor of the C++ peer, so long as this
+/// Rust object still exists and isn't in use (for instance, because C++
+/// destroys the peer while calling one of your methods, or, for
+/// `thread_safe` subclasses, because another thread is using it). If it
+/// is in use, this isn't called at all, and nothing reports that. It also
+/// isn't called if the C++ peer is destroyed because this Rust object is
+/// being dropped. Either way, you can no longer use [`CppSubclass::peer`]
+/// or [`CppSubclass::peer_mut`].
+pub trait CppPeerDestroyHook {
+    /// Called when C++ destroys the C++ peer of this object. You can
+    /// no longer use [`CppSubclass::peer`] or [`CppSubclass::peer_mut`].
+    fn on_cpp_destroy(&mut self);
 }
 
 fn make_owning_peer<CppPeer, PeerConstructor, Subclass, PeerBoxer>(
@@ -157,15 +373,17 @@ where
     Subclass: CppSubclass<CppPeer>,
     PeerConstructor:
         FnOnce(&mut Subclass, CppSubclassRustPeerHolder<Subclass>) -> UniquePtr<CppPeer>,
-    PeerBoxer: FnOnce(Rc<RefCell<Subclass>>) -> CppSubclassRustPeerHolder<Subclass>,
+    PeerBoxer:
+        FnOnce(Rc<RefCell<Subclass>>, Arc<AtomicBool>) -> CppSubclassRustPeerHolder<Subclass>,
 {
     let me = Rc::new(RefCell::new(me));
-    let holder = peer_boxer(me.clone());
+    let alive = Arc::new(AtomicBool::new(true));
+    let holder = peer_boxer(me.clone(), alive.clone());
     let cpp_side = peer_constructor(&mut me.as_ref().borrow_mut(), holder);
     me.as_ref()
         .borrow_mut()
         .peer_holder_mut()
-        .set_owned(cpp_side);
+        .set_owned(cpp_side, alive);
     me
 }
 
@@ -268,11 +486,16 @@ pub trait CppPeerConstructor<CppPeer: CppSubclassCppPeer>: Sized {
 ///   [this issue](https://github.com/google/autocxx/issues/622).
 ///
 /// * *Thread safety*. The subclass object is not thread-safe and shouldn't
-///   be passed to different threads in C++. A future version of this code
-///   will give the option to use `Arc` and `Mutex` internally rather than
-///   `Rc` and `RefCell`, solving this problem.
+///   be passed to different threads in C++. If C++ will call your subclass
+///   from other threads, declare it with `#[subclass(thread_safe)]` and use
+///   [`CppSubclassThreadSafe`] instead, which uses `Arc` and `RwLock`
+///   internally rather than `Rc` and `RefCell`.
 ///
-/// * *Protected methods.* We don't do anything clever here - they're public.
+/// * *Protected methods.* Protected virtual methods may be overridden like
+///   any other. Protected non-virtual methods of the superclass can be called
+///   on your C++ peer (via [`CppSubclass::peer`] or [`CppSubclass::peer_mut`]),
+///   which makes them public. Similarly, the peer has accessor methods for
+///   protected fields: `foo()` and, unless it's `const`, `foo_mut()`.
 ///
 /// * *Non-trivial class hierarchies*. We don't yet consider virtual methods
 ///   on base classes of base classes. This is a temporary limitation,
@@ -300,15 +523,22 @@ pub trait CppSubclass<CppPeer: CppSubclassCppPeer>: CppPeerConstructor<CppPeer>
         self.peer_holder_mut().pin_mut()
     }
 
+    /// Return a handle to the C++ part of this object pair which can tell
+    /// whether C++ has since destroyed it. See [`CppPeerWeak`].
+    fn peer_weak(&self) -> CppPeerWeak<CppPeer> {
+        self.peer_holder().weak()
+    }
+
     /// Creates a new instance of this subclass. This instance is owned by the
     /// returned [`cxx::UniquePtr`] and thus would typically be returned immediately
     /// to C++ such that it can be owned on the C++ side.
     fn new_cpp_owned(me: Self) -> UniquePtr<CppPeer> {
         let me = Rc::new(RefCell::new(me));
-        let holder = CppSubclassRustPeerHolder::Owned(me.clone());
+        let alive = Arc::new(AtomicBool::new(true));
+        let holder = CppSubclassRustPeerHolder::Owned(me.clone(), alive.clone());
         let mut borrowed = me.as_ref().borrow_mut();
         let mut cpp_side = borrowed.make_peer(holder);
-        borrowed.peer_holder_mut().set_unowned(&mut cpp_side);
+        borrowed.peer_holder_mut().set_unowned(&mut cpp_side, alive);
         cpp_side
     }
 
@@ -319,7 +549,7 @@ pub trait CppSubclass<CppPeer: CppSubclassCppPeer>: CppPeerConstructor<CppPeer>
         make_owning_peer(
             me,
             |obj, holder| obj.make_peer(holder),
-            |me| CppSubclassRustPeerHolder::Unowned(Rc::downgrade(&me)),
+            |me, alive| CppSubclassRustPeerHolder::Unowned(Rc::downgrade(&me), alive),
         )
     }
 }
@@ -395,3 +625,142 @@ where
         Self::new_self_owned(Self::default())
     }
 }
+
+/// The equivalent of [`CppPeerConstructor`] for subclasses declared
+/// with `#[subclass(thread_safe)]`.
+pub trait CppPeerConstructorThreadSafe<CppPeer: CppSubclassCppPeerThreadSafe>: Sized {
+    /// Create the C++ peer. See [`CppPeerConstructor::make_peer`].
+    fn make_peer(
+        &mut self,
+        peer_holder: CppSubclassRustPeerHolderThreadSafe<Self>,
+    ) -> UniquePtr<CppPeer>;
+}
+
+/// A subclass of a C++ type which C++ may call from any thread. This is
+/// what you get if you declare your subclass using
+/// `#[subclass(thread_safe)]`, and is otherwise just like [`CppSubclass`],
+/// except that the Rust side is shared using [`Arc`] and [`RwLock`] rather
+/// than [`Rc`] and [`RefCell`]. Your subclass must therefore be [`Send`] and
+/// [`Sync`].
+///
+/// Calls to const C++ methods take a read lock on your subclass, and calls
+/// to non-const methods take a write lock, blocking until any other thread
+/// has finished with it. Re-entrant calls from C++ into a non-const method,
+/// as described for [`CppSubclass`], will therefore deadlock rather than
+/// panic.
+pub trait CppSubclassThreadSafe<CppPeer: CppSubclassCppPeerThreadSafe>:
+    CppPeerConstructorThreadSafe<CppPeer> + Send + Sync
+{
+    /// Return the field which holds the C++ peer object. This is normally
+    /// implemented by the #[`subclass`] macro.
+    fn peer_holder(&self) -> &CppSubclassCppPeerHolder<CppPeer>;
+
+    /// Return the field which holds the C++ peer object. This is normally
+    /// implemented by the #[`subclass`] macro.
+    fn peer_holder_mut(&mut self) -> &mut CppSubclassCppPeerHolder<CppPeer>;
+
+    /// Return a reference to the C++ part of this object pair.
+    fn peer(&self) -> &CppPeer {
+        self.peer_holder().get()
+    }
+
+    /// Return a mutable reference to the C++ part of this object pair.
+    fn peer_mut(&mut self) -> Pin<&mut CppPeer> {
+        self.peer_holder_mut().pin_mut()
+    }
+
+    /// Return a handle to the C++ part of this object pair which can tell
+    /// whether C++ has since destroyed it. See [`CppSubclass::peer_weak`].
+    fn peer_weak(&self) -> CppPeerWeak<CppPeer> {
+        self.peer_holder().weak()
+    }
+
+    /// Creates a new instance of this subclass owned by the returned
+    /// [`cxx::UniquePtr`]. See [`CppSubclass::new_cpp_owned`].
+    fn new_cpp_owned(me: Self) -> UniquePtr<CppPeer> {
+        let me = Arc::new(RwLock::new(me));
+        let alive = Arc::new(AtomicBool::new(true));
+        let holder = CppSubclassRustPeerHolderThreadSafe::Owned(me.clone(), alive.clone());
+        let mut borrowed = me.write().unwrap();
+        let mut cpp_side = borrowed.make_peer(holder);
+        borrowed.peer_holder_mut().set_unowned(&mut cpp_side, alive);
+        cpp_side
+    }
+
+    /// Creates a new instance of this subclass owned by Rust. See
+    /// [`CppSubclass::new_rust_owned`].
+    fn new_rust_owned(me: Self) -> Arc<RwLock<Self>> {
+        make_owning_peer_thread_safe(me, |me, alive| {
+            CppSubclassRustPeerHolderThreadSafe::Unowned(Arc::downgrade(me), alive)
+        })
+    }
+}
+
+fn make_owning_peer_thread_safe<CppPeer, Subclass, PeerBoxer>(
+    me: Subclass,
+    peer_boxer: PeerBoxer,
+) -> Arc<RwLock<Subclass>>
+where
+    CppPeer: CppSubclassCppPeerThreadSafe,
+    Subclass: CppSubclassThreadSafe<CppPeer>,
+    PeerBoxer: FnOnce(
+        &Arc<RwLock<Subclass>>,
+        Arc<AtomicBool>,
+    ) -> CppSubclassRustPeerHolderThreadSafe<Subclass>,
+{
+    let me = Arc::new(RwLock::new(me));
+    let alive = Arc::new(AtomicBool::new(true));
+    let holder = peer_boxer(&me, alive.clone());
+    {
+        let mut borrowed = me.write().unwrap();
+        let cpp_side = borrowed.make_peer(holder);
+        borrowed.peer_holder_mut().set_owned(cpp_side, alive);
+    }
+    me
+}
+
+/// The equivalent of [`CppSubclassSelfOwned`] for subclasses declared
+/// with `#[subclass(thread_safe)]`.
+pub trait CppSubclassThreadSafeSelfOwned<CppPeer: CppSubclassCppPeerThreadSafe>:
+    CppSubclassThreadSafe<CppPeer>
+{
+    /// Creates a new instance of this subclass which owns itself. See
+    /// [`CppSubclassSelfOwned::new_self_owned`].
+    fn new_self_owned(me: Self) -> Arc<RwLock<Self>> {
+        make_owning_peer_thread_safe(me, |me, alive| {
+            CppSubclassRustPeerHolderThreadSafe::Owned(me.clone(), alive)
+        })
+    }
+
+    /// Relinquishes ownership from the C++ side. See
+    /// [`CppSubclassSelfOwned::delete_self`].
+    fn delete_self(&self) {
+        self.peer().relinquish_ownership()
+    }
+}
+
+/// Provides default constructors for thread-safe subclasses which
+/// implement `Default`.
+pub trait CppSubclassThreadSafeDefault<CppPeer: CppSubclassCppPeerThreadSafe>:
+    CppSubclassThreadSafe<CppPeer> + Default
+{
+    /// Create a Rust-owned instance of this subclass, initializing with default values.
+    fn default_rust_owned() -> Arc<RwLock<Self>>;
+
+    /// Create a C++-owned instance of this subclass, initializing with default values.
+    fn default_cpp_owned() -> UniquePtr<CppPeer>;
+}
+
+impl<T, CppPeer> CppSubclassThreadSafeDefault<CppPeer> for T
+where
+    T: CppSubclassThreadSafe<CppPeer> + Default,
+    CppPeer: CppSubclassCppPeerThreadSafe,
+{
+    fn default_rust_owned() -> Arc<RwLock<Self>> {
+        Self::new_rust_owned(Self::default())
+    }
+
+    fn default_cpp_owned() -> UniquePtr<CppPeer> {
+        Self::new_cpp_owned(Self::default())
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use cxx::{vector::VectorElement, CxxVector};
+use std::pin::Pin;
+
+use crate::ValueParam;
+
+/// A type which can be pushed onto the end of a [`cxx::CxxVector`] even
+/// though it's not trivial (in `autocxx` terms, it's not POD). `autocxx`
+/// implements this for any non-POD type which has a move constructor and
+/// which appears in a `std::vector` in some function it generates.
+///
+/// POD types don't need this: [`cxx::CxxVector::push`] works for them
+/// already, as does [`cxx::CxxVector::as_slice`].
+///
+/// It is not recommended that you implement this trait, nor that you directly
+/// use its methods, which are for use by `autocxx` generated code only.
+/// Instead use [`CxxVectorExt::push_value`].
+pub trait PushableVectorElement: VectorElement + Sized {
+    /// Move or copy a value onto the end of a vector.
+    fn push_onto_vector(vec: Pin<&mut CxxVector<Self>>, value: impl ValueParam<Self>);
+}
+
+/// Extra methods for [`cxx::CxxVector`]s of types generated by `autocxx`.
+/// The standard [`cxx::CxxVector`] methods such as `len` and `get` work
+/// for all such types; this adds the things which need C++ help.
+pub trait CxxVectorExt<T: PushableVectorElement> {
+    /// Append a value to the vector. As with any other C++ function which
+    /// takes a parameter by value, this accepts anything which implements
+    /// [`ValueParam`], so you can pass a reference to copy the value or a
+    /// [`cxx::UniquePtr`] to move it.
+    fn push_value(self: Pin<&mut Self>, value: impl ValueParam<T>);
+}
+
+impl<T: PushableVectorElement> CxxVectorExt<T> for CxxVector<T> {
+    fn push_value(self: Pin<&mut Self>, value: impl ValueParam<T>) {
+        T::push_onto_vector(self, value)
+    }
+}